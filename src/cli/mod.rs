@@ -1,8 +1,15 @@
-use clap::{Arg, ArgAction, Command};
-use std::io::{self, Write};
-use crate::crypto::{CryptoManager, KeyManager};
-use crate::steganography::{Steganography, ImageSteganography, AudioSteganography, PdfSteganography};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use crate::crypto::{
+    CipherSuite, CryptoManager, KeyManager, MasterKey, Padding, PayloadContext, SecretBytes, SignerPublicKey,
+};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+use crate::steganography::{Carrier, PdfMode, Steganography};
 use crate::error::{Result, SteganoError};
+use crate::rotation::KeyRotation;
+use crate::sharing::SecretSharing;
 
 pub struct Cli;
 
@@ -30,14 +37,14 @@ impl Cli {
                 Arg::new("encrypt-audio")
                     .long("encrypt-audio")
                     .value_name("FILE")
-                    .help("Encrypt and embed data into an audio file")
+                    .help("Encrypt and embed data into an audio file (WAV, AIFF, AIFF-C or AU)")
                     .action(ArgAction::Set),
             )
             .arg(
                 Arg::new("decrypt-audio")
                     .long("decrypt-audio")
                     .value_name("FILE")
-                    .help("Extract and decrypt data from an audio file")
+                    .help("Extract and decrypt data from an audio file (WAV, AIFF, AIFF-C or AU)")
                     .action(ArgAction::Set),
             )
             .arg(
@@ -54,6 +61,15 @@ impl Cli {
                     .help("Extract and decrypt data from a PDF file")
                     .action(ArgAction::Set),
             )
+            .arg(
+                Arg::new("pdf-mode")
+                    .long("pdf-mode")
+                    .value_name("MODE")
+                    .help("Where to hide data in a PDF: 'object' (incremental update, default), 'trailing' (after %%EOF), 'xmp' (metadata padding) or 'content' (content stream whitespace and kerning)")
+                    .value_parser(["object", "trailing", "xmp", "content"])
+                    .default_value("object")
+                    .action(ArgAction::Set),
+            )
             .arg(
                 Arg::new("keyfile")
                     .long("keyfile")
@@ -61,45 +77,465 @@ impl Cli {
                     .help("Path to keyfile for encryption/decryption")
                     .action(ArgAction::Set),
             )
+            .arg(
+                Arg::new("keyring")
+                    .long("keyring")
+                    .value_name("PATH")
+                    .help("Keyring directory or file: hides data under its current key and extracts with whichever key a carrier was made with")
+                    .conflicts_with_all(["keyfile", "passphrase", "secret-key"])
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("add-to-keyring")
+                    .long("add-to-keyring")
+                    .value_name("KEYFILE")
+                    .help("Add KEYFILE to the keyring given with --keyring and make it the current key, then exit")
+                    .requires("keyring")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("generate-recipient")
+                    .long("generate-recipient")
+                    .value_name("FILE")
+                    .help("Generate an X25519 recipient keypair: the secret key goes to FILE, the public key to FILE.pub")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("post-quantum")
+                    .long("post-quantum")
+                    .help("With --generate-recipient, generate a hybrid X25519 + ML-KEM-768 keypair instead")
+                    .requires("generate-recipient")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("recipient")
+                    .long("recipient")
+                    .value_name("PUBLIC_KEY")
+                    .help("Encrypt to a public key (svx25519:..., svhybrid:... or a .pub file); repeat for several recipients, or combine with a keyfile or passphrase")
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                Arg::new("add-keyfile")
+                    .long("add-keyfile")
+                    .value_name("FILE")
+                    .help("Also let the key in FILE open the payload; repeat for several keyfiles")
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                Arg::new("secret-key")
+                    .long("secret-key")
+                    .value_name("FILE")
+                    .help("Decrypt with a recipient secret key (X25519 or hybrid) instead of a keyfile")
+                    .conflicts_with_all(["keyfile", "passphrase"])
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("protect-keyfile")
+                    .long("protect-keyfile")
+                    .help("Wrap the keyfile's key under a passphrase, when creating it or in place for an existing unprotected keyfile")
+                    .requires("keyfile")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("rotate-keys")
+                    .long("rotate-keys")
+                    .value_name("PATH")
+                    .num_args(1..)
+                    .help("Re-encrypt the carriers in these files or directories under --new-keyfile, in place or into --output-dir")
+                    .requires("new-keyfile")
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                Arg::new("new-keyfile")
+                    .long("new-keyfile")
+                    .value_name("FILE")
+                    .help("Keyfile that rotated carriers are encrypted under; created if it does not exist")
+                    .requires("rotate-keys")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("output-dir")
+                    .long("output-dir")
+                    .value_name("DIR")
+                    .help("Directory for rotated carriers instead of replacing them in place, or for the carriers --split writes")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("split")
+                    .long("split")
+                    .value_name("CARRIER")
+                    .num_args(1..)
+                    .help("Split the data into one share per carrier (images, audio and PDFs can be mixed), written to --output-dir; any --threshold of them recover it")
+                    .requires_all(["threshold", "output-dir"])
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                Arg::new("threshold")
+                    .long("threshold")
+                    .value_name("K")
+                    .help("Number of carriers needed to recover data hidden with --split")
+                    .value_parser(clap::value_parser!(u8))
+                    .requires("split")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("combine")
+                    .long("combine")
+                    .value_name("CARRIER")
+                    .num_args(1..)
+                    .help("Recover data split with --split from at least its threshold of carriers")
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                Arg::new("key-purpose")
+                    .long("key-purpose")
+                    .value_name("TEXT")
+                    .help("Purpose recorded in a newly created keyfile (default: encryption)")
+                    .requires("keyfile")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("key-info")
+                    .long("key-info")
+                    .value_name("FILE")
+                    .help("Show a keyfile's key ID, creation time and purpose, then exit")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("passphrase")
+                    .long("passphrase")
+                    .help("Derive the key from a passphrase (Argon2id) instead of a keyfile; prompts for it unless STEGANO_VAULT_PASSPHRASE is set")
+                    .conflicts_with("keyfile")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("generate-signing-key")
+                    .long("generate-signing-key")
+                    .value_name("FILE")
+                    .help("Generate an Ed25519 signing keypair: the signing key goes to FILE, the verification key to FILE.pub")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("sign-with")
+                    .long("sign-with")
+                    .value_name("FILE")
+                    .help("Sign hidden data with the Ed25519 signing key in FILE")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("trusted-signer")
+                    .long("trusted-signer")
+                    .value_name("PUBLIC_KEY")
+                    .help("Only accept data signed by this key (sved25519:... or a .pub file); repeat to trust several signers")
+                    .action(ArgAction::Append),
+            )
+            .arg(
+                Arg::new("bind-carrier")
+                    .long("bind-carrier")
+                    .help("Bind the payload to its carrier (type, PDF mode and a hash of the parts embedding leaves unchanged), so it cannot be moved to another file; also needed to extract")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("context-label")
+                    .long("context-label")
+                    .value_name("LABEL")
+                    .help("Bind the payload to a label that must be given again to extract it")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("cipher")
+                    .long("cipher")
+                    .value_name("CIPHER")
+                    .help("Cipher for new payloads; decryption detects it automatically")
+                    .value_parser(["xchacha20-poly1305", "aes-256-gcm-siv", "aes-256-gcm"])
+                    .default_value("xchacha20-poly1305")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("compress")
+                    .long("compress")
+                    .help("Compress the data before encryption; pair with --pad to keep its compressibility hidden")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("error-correction")
+                    .long("error-correction")
+                    .value_name("COPIES")
+                    .help("Store the payload COPIES times (odd, up to 15) so it survives some damaged carrier bits")
+                    .value_parser(clap::value_parser!(u8))
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("input")
+                    .long("input")
+                    .short('i')
+                    .value_name("FILE")
+                    .help("Hide the contents of FILE instead of prompting for data; it is encrypted in chunks, but must fit in the carrier and is limited to 4 GiB")
+                    .conflicts_with_all(["split", "sign-with"])
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("pad")
+                    .long("pad")
+                    .value_name("MODE")
+                    .help("Pad the data before encryption to hide its length: pow2 rounds up to a power of two, fill uses the carrier's whole capacity")
+                    .value_parser(["none", "pow2", "fill"])
+                    .conflicts_with_all(["input", "combine", "rotate-keys"])
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("extract-to")
+                    .long("extract-to")
+                    .value_name("FILE")
+                    .help("Write the extracted data to FILE instead of printing it")
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("decoy-keyfile")
+                    .long("decoy-keyfile")
+                    .value_name("FILE")
+                    .help("With --encrypt-image or --encrypt-audio, also hide a decoy payload that FILE's key reveals instead of the real one")
+                    .conflicts_with_all(["encrypt-pdf", "split", "combine", "rotate-keys"])
+                    .action(ArgAction::Set),
+            )
+            .arg(
+                Arg::new("decoy-input")
+                    .long("decoy-input")
+                    .value_name("FILE")
+                    .help("Hide the contents of FILE as the decoy instead of prompting for it")
+                    .requires("decoy-keyfile")
+                    .action(ArgAction::Set),
+            )
             .arg(
                 Arg::new("output")
                     .long("output")
                     .short('o')
                     .value_name("FILE")
-                    .help("Path to output file (default: output.png, output.wav, or output.pdf)")
+                    .help("Path to output file (default: output.png, output.wav/.aiff/.au, or output.pdf)")
                     .action(ArgAction::Set),
             )
             .get_matches();
 
-        // Handle keyfile option
-        let keyfile = matches.get_one::<String>("keyfile")
-            .ok_or_else(|| SteganoError::InvalidKey(
-                "No keyfile specified. Use --keyfile option to specify a key file.".to_string()
-            ))?;
+        let encrypting = ["encrypt-image", "encrypt-audio", "encrypt-pdf"]
+            .iter()
+            .any(|id| matches.contains_id(id));
 
-        let crypto_manager = KeyManager::load_or_create(keyfile)?;
-        
-        // Check if this is a new key
-        if !std::path::Path::new(keyfile).exists() {
-            println!("Created and saved new key to {}", keyfile);
+        if let Some(secret_path) = matches.get_one::<String>("generate-recipient") {
+            let public_path = format!("{}.pub", secret_path);
+            if matches.get_flag("post-quantum") {
+                let public_key = KeyManager::generate_hybrid_recipient_key(secret_path, &public_path)?.public_key();
+                println!("Saved hybrid X25519 + ML-KEM-768 secret key to {}", secret_path);
+                println!("Saved public key to {}", public_path);
+                println!("Fingerprint: {}", public_key.fingerprint());
+                return Ok(());
+            }
+            let secret_key = KeyManager::generate_recipient_key(secret_path, &public_path)?;
+            let public_key = secret_key.public_key();
+            println!("Saved recipient secret key to {}", secret_path);
+            println!("Saved public key to {}", public_path);
+            println!("Public key: {}", public_key);
+            println!("Fingerprint: {}", public_key.fingerprint());
+            return Ok(());
+        }
+
+        if let Some(keyfile) = matches.get_one::<String>("key-info") {
+            let info = KeyManager::inspect(keyfile)?;
+            println!("Key ID: {}", info.key_id);
+            if let Some(created) = info.created {
+                println!("Created: {}", Self::format_timestamp(created));
+            }
+            if let Some(purpose) = &info.purpose {
+                println!("Purpose: {}", purpose);
+            }
+            println!("Protected: {}", if info.protected { "yes" } else { "no" });
+            if info.legacy {
+                println!("Format: legacy, without metadata or checksum");
+            }
+            return Ok(());
+        }
+
+        if let Some(secret_path) = matches.get_one::<String>("generate-signing-key") {
+            let public_path = format!("{}.pub", secret_path);
+            let signer = KeyManager::generate_signing_key(secret_path, &public_path)?;
+            let public_key = signer.public_key();
+            println!("Saved signing key to {}", secret_path);
+            println!("Saved verification key to {}", public_path);
+            println!("Verification key: {}", public_key);
+            println!("Fingerprint: {}", public_key.fingerprint());
+            return Ok(());
+        }
+
+        let trusted_signers = matches
+            .get_many::<String>("trusted-signer")
+            .unwrap_or_default()
+            .map(|key| KeyManager::load_signer_public_key(key))
+            .collect::<Result<Vec<_>>>()?;
+
+        let public_keys = matches
+            .get_many::<String>("recipient")
+            .unwrap_or_default()
+            .map(|key| KeyManager::load_recipient(key))
+            .collect::<Result<Vec<_>>>()?;
+        for public_key in &public_keys {
+            if let Some(fingerprint) = public_key.fingerprint() {
+                println!("Encrypting to {}", fingerprint);
+            }
+        }
+        if let Some(keyfile) = matches.get_one::<String>("add-to-keyring") {
+            let keyring = matches.get_one::<String>("keyring").expect("required by clap");
+            let key_id = KeyManager::add_to_keyring(keyring, keyfile)?;
+            println!("Added key {} to {} as the current key", key_id, keyring);
+            return Ok(());
+        }
+
+        let has_own_key = matches.get_flag("passphrase")
+            || matches.contains_id("keyfile")
+            || matches.contains_id("keyring")
+            || matches.contains_id("secret-key");
+
+        let mut crypto_manager = if !has_own_key && !public_keys.is_empty() {
+            CryptoManager::for_recipients(public_keys.clone())?
+        } else if let Some(secret_path) = matches.get_one::<String>("secret-key") {
+            KeyManager::open_secret_key(secret_path)?
+        } else if matches.get_flag("passphrase") {
+            let passphrase = Self::read_passphrase("STEGANO_VAULT_PASSPHRASE", "Passphrase: ", encrypting)?;
+            println!("Deriving key from passphrase...");
+            CryptoManager::with_passphrase(&passphrase)?
+        } else if let Some(keyring_path) = matches.get_one::<String>("keyring") {
+            let keyring = match std::env::var("STEGANO_VAULT_KEYFILE_PASSPHRASE").map(Zeroizing::new) {
+                Ok(passphrase) => KeyManager::load_keyring_with_passphrase(keyring_path, &passphrase)?,
+                Err(_) => KeyManager::load_keyring(keyring_path)?,
+            };
+            let crypto_manager = CryptoManager::with_keyring(&keyring)?;
+            if let Some(key_id) = crypto_manager.key_id() {
+                println!("Using keyring {} ({} keys, current key {})", keyring_path, keyring.len(), key_id);
+            }
+            crypto_manager
+        } else {
+            // Handle keyfile option
+            let keyfile = matches.get_one::<String>("keyfile")
+                .ok_or_else(|| SteganoError::InvalidKey(
+                    "No keyfile specified. Use --keyfile, --keyring, --passphrase, --recipient or --secret-key.".to_string()
+                ))?;
+
+            let purpose = matches.get_one::<String>("key-purpose").map(|s| s.as_str());
+            Self::open_keyfile(keyfile, matches.get_flag("protect-keyfile"), purpose)?
+        };
+
+        // Extra recipients each get their own wrapped copy of the content key
+        if has_own_key {
+            for public_key in public_keys {
+                crypto_manager = crypto_manager.with_recipient(public_key);
+            }
+        }
+        for keyfile in matches.get_many::<String>("add-keyfile").unwrap_or_default() {
+            if !Path::new(keyfile).exists() {
+                return Err(SteganoError::InvalidKey(format!("Keyfile {} does not exist", keyfile)));
+            }
+            if let Some(recipient) = Self::open_keyfile(keyfile, false, None)?.recipient() {
+                crypto_manager = crypto_manager.with_recipient(recipient);
+            }
+        }
+
+        let cipher = match matches.get_one::<String>("cipher").map(|s| s.as_str()) {
+            Some("aes-256-gcm") => CipherSuite::Aes256Gcm,
+            Some("aes-256-gcm-siv") => CipherSuite::Aes256GcmSiv,
+            _ => CipherSuite::XChaCha20Poly1305,
+        };
+        let copies = matches.get_one::<u8>("error-correction").copied().unwrap_or(1);
+        let compress = matches.get_flag("compress");
+        let mut crypto_manager = crypto_manager
+            .with_cipher(cipher)
+            .with_compression(compress)
+            .with_error_correction(copies)?;
+
+        if let Some(signing_key_path) = matches.get_one::<String>("sign-with") {
+            let signer = KeyManager::load_signing_key(signing_key_path)?;
+            println!("Signing as {}", signer.public_key().fingerprint());
+            crypto_manager = crypto_manager.with_signer(signer);
+        }
+
+        if let Some(paths) = matches.get_many::<String>("rotate-keys") {
+            let new_keyfile = matches.get_one::<String>("new-keyfile").expect("required by clap");
+            let new = Self::open_keyfile(new_keyfile, false, None)?
+                .with_cipher(cipher)
+                .with_compression(compress)
+                .with_error_correction(copies)?;
+            let paths: Vec<PathBuf> = paths.map(PathBuf::from).collect();
+            return Self::rotate_keys(&matches, crypto_manager, new, &paths);
         }
 
         // Get output file path if specified
         let output = matches.get_one::<String>("output");
 
+        let pdf_mode = match matches.get_one::<String>("pdf-mode").map(|s| s.as_str()) {
+            Some("trailing") => PdfMode::Trailing,
+            Some("xmp") => PdfMode::Xmp,
+            Some("content") => PdfMode::ContentStream,
+            _ => PdfMode::Object,
+        };
+
+        // Payloads bound to a context only decrypt with the same context
+        let bind_carrier = matches.get_flag("bind-carrier");
+        let label = matches.get_one::<String>("context-label");
+        let context = if bind_carrier || label.is_some() {
+            Some(Self::payload_context(&matches, pdf_mode, bind_carrier, label)?)
+        } else {
+            None
+        };
+        let crypto_manager = match &context {
+            Some(context) => crypto_manager.with_context(context),
+            None => crypto_manager,
+        };
+        let padding = Self::padding(&matches, pdf_mode, crypto_manager.carrier_key())?;
+        let crypto_manager = crypto_manager.with_padding(padding);
+
+        // The decoy is encrypted like the real payload, under its own key
+        let decoy = match matches.get_one::<String>("decoy-keyfile") {
+            Some(keyfile) => {
+                let mut decoy_manager = Self::open_keyfile(keyfile, false, None)?
+                    .with_cipher(cipher)
+                    .with_compression(compress)
+                    .with_error_correction(copies)?
+                    .with_padding(padding);
+                if let Some(context) = &context {
+                    decoy_manager = decoy_manager.with_context(context);
+                }
+                let data = match matches.get_one::<String>("decoy-input") {
+                    Some(path) => SecretBytes::from(std::fs::read(path)?),
+                    None => Self::prompt("Enter the decoy data to encrypt: ")?,
+                };
+                println!("Encrypting decoy data...");
+                Some((decoy_manager.carrier_key().clone(), decoy_manager.encrypt(&data)?))
+            }
+            None => None,
+        };
+        let decoy = decoy.as_ref().map(|(key, data)| (key, data.as_slice()));
+
+        let input = matches.get_one::<String>("input");
+        let extract_to = matches.get_one::<String>("extract-to");
+
         // Handle operations
-        if let Some(file) = matches.get_one::<String>("encrypt-image") {
-            Self::encrypt_image(&crypto_manager, file, output)?;
+        if let Some(carriers) = matches.get_many::<String>("split") {
+            let threshold = *matches.get_one::<u8>("threshold").expect("required by clap");
+            let output_dir = matches.get_one::<String>("output-dir").expect("required by clap");
+            let carriers: Vec<PathBuf> = carriers.map(PathBuf::from).collect();
+            Self::split(crypto_manager, &carriers, threshold, Path::new(output_dir), pdf_mode)?;
+        } else if let Some(carriers) = matches.get_many::<String>("combine") {
+            let carriers: Vec<PathBuf> = carriers.map(PathBuf::from).collect();
+            Self::combine(crypto_manager, &carriers, &trusted_signers, extract_to)?;
+        } else if let Some(file) = matches.get_one::<String>("encrypt-image") {
+            Self::encrypt_image(&crypto_manager, file, input, decoy, output)?;
         } else if let Some(file) = matches.get_one::<String>("decrypt-image") {
-            Self::decrypt_image(&crypto_manager, file)?;
+            Self::decrypt_image(&crypto_manager, file, &trusted_signers, extract_to)?;
         } else if let Some(file) = matches.get_one::<String>("encrypt-audio") {
-            Self::encrypt_audio(&crypto_manager, file, output)?;
+            Self::encrypt_audio(&crypto_manager, file, input, decoy, output)?;
         } else if let Some(file) = matches.get_one::<String>("decrypt-audio") {
-            Self::decrypt_audio(&crypto_manager, file)?;
+            Self::decrypt_audio(&crypto_manager, file, &trusted_signers, extract_to)?;
         } else if let Some(file) = matches.get_one::<String>("encrypt-pdf") {
-            Self::encrypt_pdf(&crypto_manager, file, output)?;
+            Self::encrypt_pdf(&crypto_manager, file, input, output, pdf_mode)?;
         } else if let Some(file) = matches.get_one::<String>("decrypt-pdf") {
-            Self::decrypt_pdf(&crypto_manager, file)?;
+            Self::decrypt_pdf(&crypto_manager, file, pdf_mode, &trusted_signers, extract_to)?;
         } else {
             return Err(SteganoError::InvalidData(
                 "No operation specified. Use --help for usage information.".to_string()
@@ -109,44 +545,362 @@ impl Cli {
         Ok(())
     }
 
-    fn get_user_input() -> Result<Vec<u8>> {
-        print!("Enter the sensitive data to encrypt: ");
+    /// Move carriers to a new key and print what happened to each one
+    fn rotate_keys(matches: &ArgMatches, old: CryptoManager, new: CryptoManager, paths: &[PathBuf]) -> Result<()> {
+        let mut rotation = KeyRotation::new(old, new);
+        if let Some(dir) = matches.get_one::<String>("output-dir") {
+            rotation = rotation.with_output_dir(Path::new(dir));
+        }
+        if matches.get_flag("bind-carrier") {
+            rotation = rotation.with_bound_carriers();
+        }
+        if let Some(label) = matches.get_one::<String>("context-label") {
+            rotation = rotation.with_label(label);
+        }
+
+        let report = rotation.rotate(paths);
+        println!("{}", report);
+        match report.failed().count() {
+            0 => Ok(()),
+            failed => Err(SteganoError::InvalidData(
+                format!("{} of {} carriers could not be rotated", failed, report.files.len())
+            )),
+        }
+    }
+
+    /// Hide the data as shares, one per carrier, written under `output_dir`
+    /// with the carriers' file names (JPEG carriers become PNG)
+    fn split(
+        crypto_manager: CryptoManager,
+        carriers: &[PathBuf],
+        threshold: u8,
+        output_dir: &Path,
+        pdf_mode: PdfMode,
+    ) -> Result<()> {
+        let data = Self::get_user_input()?;
+        std::fs::create_dir_all(output_dir)?;
+
+        let mut pairs = Vec::new();
+        for carrier in carriers {
+            let name = carrier.file_name().ok_or_else(|| SteganoError::InvalidData(
+                format!("{} is not a file", carrier.display())
+            ))?;
+            let output = Carrier::output_path(&output_dir.join(name));
+            if pairs.iter().any(|(_, other)| *other == output) {
+                return Err(SteganoError::InvalidData(
+                    format!("Two carriers would both be written to {}", output.display())
+                ));
+            }
+            pairs.push((carrier.clone(), output));
+        }
+
+        println!("Encrypting data and splitting it into {} shares...", carriers.len());
+        SecretSharing::new(crypto_manager).with_pdf_mode(pdf_mode).hide(&data, threshold, &pairs)?;
+        for (_, output) in &pairs {
+            println!("Share hidden inside {}", output.display());
+        }
+        println!("Any {} of the {} carriers recover the data", threshold, carriers.len());
+        Ok(())
+    }
+
+    fn combine(
+        crypto_manager: CryptoManager,
+        carriers: &[PathBuf],
+        trusted_signers: &[SignerPublicKey],
+        extract_to: Option<&String>,
+    ) -> Result<()> {
+        println!("Recovering data from {} carriers...", carriers.len());
+        let (decrypted_data, signer) = SecretSharing::new(crypto_manager).recover(carriers)?;
+        Self::check_signer(signer, trusted_signers)?;
+
+        if let Some(path) = extract_to {
+            return Self::write_extracted(&decrypted_data, path);
+        }
+        match std::str::from_utf8(&decrypted_data) {
+            Ok(text) => println!("Decrypted data: {}", text),
+            Err(_) => {
+                println!("Decrypted data (binary, {} bytes):", decrypted_data.len());
+                println!("{:02X?}", &decrypted_data[..decrypted_data.len().min(50)]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Context for the requested operation: the user's label and, when binding
+    /// to the carrier, its type, embedding parameters and invariant digest
+    fn payload_context(
+        matches: &ArgMatches,
+        pdf_mode: PdfMode,
+        bind_carrier: bool,
+        label: Option<&String>,
+    ) -> Result<PayloadContext> {
+        let mut context = PayloadContext::new();
+        if let Some(label) = label {
+            context = context.with_label(label);
+        }
+        if !bind_carrier {
+            return Ok(context);
+        }
+
+        let (carrier, file) = [
+            (Carrier::Image, "encrypt-image"),
+            (Carrier::Image, "decrypt-image"),
+            (Carrier::Audio, "encrypt-audio"),
+            (Carrier::Audio, "decrypt-audio"),
+            (Carrier::Pdf(pdf_mode), "encrypt-pdf"),
+            (Carrier::Pdf(pdf_mode), "decrypt-pdf"),
+        ]
+        .into_iter()
+        .find_map(|(carrier, id)| matches.get_one::<String>(id).map(|file| (carrier, file)))
+        .ok_or_else(|| SteganoError::InvalidData(
+            "--bind-carrier needs an image, audio or PDF operation".to_string()
+        ))?;
+
+        carrier.bound_context(file, context)
+    }
+
+    /// Padding chosen with --pad. Filling the carrier pads every payload to
+    /// the capacity of the carrier being written, so it needs one that
+    /// cannot grow.
+    fn padding(matches: &ArgMatches, pdf_mode: PdfMode, carrier_key: &MasterKey) -> Result<Padding> {
+        match matches.get_one::<String>("pad").map(|s| s.as_str()) {
+            Some("pow2") => return Ok(Padding::PowerOfTwo),
+            Some("fill") => {}
+            _ => return Ok(Padding::None),
+        }
+
+        let (carrier, file) = [
+            (Carrier::Image, "encrypt-image"),
+            (Carrier::Audio, "encrypt-audio"),
+            (Carrier::Pdf(pdf_mode), "encrypt-pdf"),
+        ]
+        .into_iter()
+        .find_map(|(carrier, id)| matches.get_one::<String>(id).map(|file| (carrier, file)))
+        .ok_or_else(|| SteganoError::InvalidData(
+            "--pad fill needs --encrypt-image, --encrypt-audio or --encrypt-pdf".to_string()
+        ))?;
+
+        let capacity = carrier.steganography(file, carrier_key).capacity(file)?.ok_or_else(|| {
+            SteganoError::InvalidData(
+                "This PDF mode grows the file to fit its payload; use --pdf-mode xmp or content to fill it".to_string()
+            )
+        })?;
+        println!("Padding the payload to the carrier's capacity of {} bytes", capacity);
+        Ok(Padding::ToLength(capacity))
+    }
+
+    /// Read a passphrase from `env_var` or prompt for it without echo, asking
+    /// twice when it protects new data
+    fn read_passphrase(env_var: &str, prompt: &str, confirm: bool) -> Result<Zeroizing<String>> {
+        if let Ok(passphrase) = std::env::var(env_var) {
+            return Ok(Zeroizing::new(passphrase));
+        }
+
+        let read = |prompt: &str| {
+            rpassword::prompt_password(prompt).map(Zeroizing::new).map_err(|e| SteganoError::InvalidKey(format!(
+                "Could not read passphrase ({}); set {} when no terminal is available",
+                e, env_var
+            )))
+        };
+
+        let passphrase = read(prompt)?;
+        if confirm && read("Confirm passphrase: ")? != passphrase {
+            return Err(SteganoError::InvalidKey("Passphrases do not match".to_string()));
+        }
+        Ok(passphrase)
+    }
+
+    /// Load the keyfile, creating it if needed. Protected keyfiles are unlocked
+    /// with STEGANO_VAULT_KEYFILE_PASSPHRASE or a prompt; with `protect` a new or
+    /// unprotected keyfile is wrapped under a passphrase. New keyfiles record
+    /// `purpose`.
+    fn open_keyfile(keyfile: &str, protect: bool, purpose: Option<&str>) -> Result<CryptoManager> {
+        const ENV_VAR: &str = "STEGANO_VAULT_KEYFILE_PASSPHRASE";
+        let prompt = format!("Passphrase for {}: ", keyfile);
+
+        if !Path::new(keyfile).exists() {
+            let purpose = purpose.unwrap_or("encryption");
+            let crypto_manager = if protect {
+                let passphrase = Self::read_passphrase(ENV_VAR, &prompt, true)?;
+                let crypto_manager = KeyManager::create_with_purpose(keyfile, purpose, Some(&passphrase))?;
+                println!("Created and saved new passphrase-protected key to {}", keyfile);
+                crypto_manager
+            } else {
+                let crypto_manager = KeyManager::create_with_purpose(keyfile, purpose, None)?;
+                println!("Created and saved new key to {}", keyfile);
+                crypto_manager
+            };
+            if let Some(key_id) = crypto_manager.key_id() {
+                println!("Key ID: {}", key_id);
+            }
+            return Ok(crypto_manager);
+        }
+
+        if KeyManager::is_protected(keyfile)? {
+            let passphrase = Self::read_passphrase(ENV_VAR, &prompt, false)?;
+            return KeyManager::load_with_passphrase(keyfile, &passphrase);
+        }
+
+        if protect {
+            let passphrase = Self::read_passphrase(ENV_VAR, &prompt, true)?;
+            let crypto_manager = KeyManager::protect(keyfile, &passphrase)?;
+            println!("Protected {} with a passphrase", keyfile);
+            return Ok(crypto_manager);
+        }
+        KeyManager::load(keyfile)
+    }
+
+    /// Render seconds since the Unix epoch as a UTC date and time
+    fn format_timestamp(secs: u64) -> String {
+        let days = (secs / 86_400) as i64;
+        let (hour, minute, second) = (secs % 86_400 / 3600, secs % 3600 / 60, secs % 60);
+
+        // Civil date from a day count, after Howard Hinnant's days_from_civil inverse
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, hour, minute, second)
+    }
+
+    /// Encrypt the file given with --input, or else the data typed at the
+    /// prompt, and hide it in the carrier `steg` makes of `carrier_path`. A
+    /// file is encrypted in chunks straight into the carrier as it is read,
+    /// so its size is bounded only by what the carrier holds; one that
+    /// cannot fit is rejected before it is read.
+    fn embed_input(
+        crypto_manager: &CryptoManager,
+        input: Option<&String>,
+        steg: &dyn Steganography,
+        carrier_path: &str,
+        output_path: &str,
+    ) -> Result<()> {
+        let Some(path) = input else {
+            let data = Self::get_user_input()?;
+            println!("Encrypting data...");
+            let encrypted_data = crypto_manager.encrypt(&data)?;
+            println!("Embedding encrypted data in {}...", carrier_path);
+            return steg.embed(carrier_path, &encrypted_data, output_path);
+        };
+
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        // Compressed data may still fit, so only uncompressed input is checked
+        if let Some(capacity) = steg.capacity(carrier_path)? {
+            if !crypto_manager.compresses() && file_len > capacity as u64 {
+                return Err(SteganoError::EmbedError(format!(
+                    "{} is {} bytes, but {} holds at most {} bytes",
+                    path, file_len, carrier_path, capacity
+                )));
+            }
+        }
+
+        println!("Encrypting {} into {}...", path, carrier_path);
+        let mut len = 0;
+        steg.embed_stream(
+            carrier_path,
+            &mut |writer| {
+                len = crypto_manager.encrypt_stream(BufReader::new(&file), writer)?;
+                Ok(())
+            },
+            output_path,
+        )?;
+        println!("Encrypted {} bytes", len);
+        Ok(())
+    }
+
+    /// Decrypt a streamed payload into `path` a chunk at a time. It goes to
+    /// a hidden sibling first, so a payload that fails part way through
+    /// leaves neither a truncated file nor a clobbered old one.
+    fn stream_extracted(crypto_manager: &CryptoManager, payload: &[u8], path: &str) -> Result<()> {
+        let destination = Path::new(path);
+        let name = destination
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| SteganoError::InvalidData(format!("{} is not a file path", path)))?;
+        let temp = destination.with_file_name(format!(".extracting-{}-{}", std::process::id(), name));
+
+        let written = File::create(&temp).map_err(SteganoError::from).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            let len = crypto_manager.decrypt_stream(payload, &mut writer)?;
+            writer.flush()?;
+            Ok(len)
+        });
+        let renamed = written.and_then(|len| {
+            std::fs::rename(&temp, destination)?;
+            Ok(len)
+        });
+        match renamed {
+            Ok(len) => {
+                println!("Wrote {} bytes of decrypted data to {}", len, path);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp);
+                Err(e)
+            }
+        }
+    }
+
+    /// Save decrypted data to `path` rather than printing it
+    fn write_extracted(decrypted_data: &[u8], path: &str) -> Result<()> {
+        std::fs::write(path, decrypted_data)?;
+        println!("Wrote {} bytes of decrypted data to {}", decrypted_data.len(), path);
+        Ok(())
+    }
+
+    fn get_user_input() -> Result<SecretBytes> {
+        Self::prompt("Enter the sensitive data to encrypt: ")
+    }
+
+    fn prompt(prompt: &str) -> Result<SecretBytes> {
+        print!("{}", prompt);
         io::stdout().flush()?;
 
-        let mut input = String::new();
+        let mut input = Zeroizing::new(String::new());
         io::stdin().read_line(&mut input)?;
 
-        Ok(input.trim().as_bytes().to_vec())
+        Ok(SecretBytes::from(input.trim().as_bytes()))
     }
 
     fn encrypt_image(
         crypto_manager: &CryptoManager,
         image_path: &str,
+        input: Option<&String>,
+        decoy: Option<(&MasterKey, &[u8])>,
         output: Option<&String>,
     ) -> Result<()> {
-        let data = Self::get_user_input()?;
-        println!("Encrypting data...");
-        let encrypted_data = crypto_manager.encrypt(&data)?;
-        
+        let steg = Self::carrier_steganography(Carrier::Image, image_path, crypto_manager, decoy)?;
         let output_path = output.map(|s| s.as_str()).unwrap_or("output.png");
-        println!("Embedding encrypted data in image...");
-        
-        let steg = ImageSteganography::new();
-        steg.embed(image_path, &encrypted_data, output_path)?;
+        Self::embed_input(crypto_manager, input, steg.as_ref(), image_path, output_path)?;
         println!("Data encrypted and hidden inside {}", output_path);
         
         Ok(())
     }
 
-    fn decrypt_image(crypto_manager: &CryptoManager, image_path: &str) -> Result<()> {
+    fn decrypt_image(
+        crypto_manager: &CryptoManager,
+        image_path: &str,
+        trusted_signers: &[SignerPublicKey],
+        extract_to: Option<&String>,
+    ) -> Result<()> {
         println!("Extracting data from image...");
-        let steg = ImageSteganography::new();
-        let extracted_data = steg.extract(image_path)?;
-        
-        println!("Decrypting extracted data...");
-        let decrypted_data = crypto_manager.decrypt(&extracted_data)?;
-        
-        match String::from_utf8(decrypted_data.clone()) {
+        let Some(decrypted_data) =
+            Self::extract_and_decrypt(crypto_manager, Carrier::Image, image_path, trusted_signers, extract_to)?
+        else {
+            return Ok(());
+        };
+
+        if let Some(path) = extract_to {
+            return Self::write_extracted(&decrypted_data, path);
+        }
+        match std::str::from_utf8(&decrypted_data) {
             Ok(text) => println!("Decrypted Data: {}", text),
             Err(_) => {
                 println!("Decrypted data (binary, {} bytes):", decrypted_data.len());
@@ -157,34 +911,156 @@ impl Cli {
         Ok(())
     }
 
+    /// Report who signed the payload, rejecting it if trusted signers were
+    /// given and it was not signed by one of them
+    fn check_signer(signer: Option<SignerPublicKey>, trusted_signers: &[SignerPublicKey]) -> Result<()> {
+        match signer {
+            Some(signer) => {
+                println!("Signed by {} (signature verified)", signer.fingerprint());
+                if !trusted_signers.is_empty() && !trusted_signers.contains(&signer) {
+                    return Err(SteganoError::DecryptionError(format!(
+                        "Data was signed by {}, which is not a trusted signer",
+                        signer.fingerprint()
+                    )));
+                }
+            }
+            None if !trusted_signers.is_empty() => {
+                return Err(SteganoError::DecryptionError(
+                    "Data is not signed, but a trusted signer was required".to_string()
+                ));
+            }
+            None => println!("Data is not signed"),
+        }
+        Ok(())
+    }
+
+    /// The method for `carrier` under the carrier key, with the decoy if given
+    fn carrier_steganography(
+        carrier: Carrier,
+        path: &str,
+        crypto_manager: &CryptoManager,
+        decoy: Option<(&MasterKey, &[u8])>,
+    ) -> Result<Box<dyn Steganography>> {
+        match decoy {
+            Some((decoy_key, decoy_data)) => {
+                println!("Hiding the decoy payload alongside the real one");
+                carrier.steganography_with_decoy(path, crypto_manager.carrier_key(), decoy_key, decoy_data)
+            }
+            None => Ok(carrier.steganography(path, crypto_manager.carrier_key())),
+        }
+    }
+
+    /// Extract and decrypt a payload, trying each carrier key in turn, and
+    /// check its signer. Payloads for several recipients are hidden under
+    /// the public carrier key, and carriers made with older keyring keys
+    /// under those keys. A streamed payload bound for `extract_to` is
+    /// decrypted straight into it a chunk at a time, and `None` returned.
+    fn extract_and_decrypt(
+        crypto_manager: &CryptoManager,
+        carrier: Carrier,
+        path: &str,
+        trusted_signers: &[SignerPublicKey],
+        extract_to: Option<&String>,
+    ) -> Result<Option<SecretBytes>> {
+        println!("Decrypting extracted data...");
+        let public_carrier_key = CryptoManager::public_carrier_key();
+        let mut extract_error = None;
+        let mut decrypt_error = None;
+        for carrier_key in crypto_manager.carrier_keys().chain([&public_carrier_key]) {
+            let extracted_data = match carrier.steganography(path, carrier_key).extract(path) {
+                Ok(extracted_data) => extracted_data,
+                Err(e) => {
+                    extract_error.get_or_insert(e);
+                    continue;
+                }
+            };
+            if let (Some(output), true) = (extract_to, CryptoManager::is_streamed(&extracted_data)) {
+                // Streamed payloads are never signed
+                Self::check_signer(None, trusted_signers)?;
+                match Self::stream_extracted(crypto_manager, &extracted_data, output) {
+                    Ok(()) => return Ok(None),
+                    Err(e) => {
+                        decrypt_error.get_or_insert(e);
+                    }
+                }
+                continue;
+            }
+            match crypto_manager.decrypt_signed(&extracted_data) {
+                Ok((data, signer)) => {
+                    Self::check_signer(signer, trusted_signers)?;
+                    return Ok(Some(data));
+                }
+                Err(e) => {
+                    decrypt_error.get_or_insert(e);
+                }
+            }
+        }
+
+        // A payload that was found but would not decrypt says more, such as
+        // which key made it, than a carrier no key could open
+        if let Some(e) = decrypt_error {
+            return Err(e);
+        }
+        let keys = match crypto_manager.carrier_keys().count() {
+            1 => "the supplied key".to_string(),
+            count => format!("any of the {} keyring keys", count),
+        };
+        let reason = match extract_error {
+            Some(SteganoError::ExtractError(reason)) => reason,
+            e => e.map_or_else(String::new, |e| e.to_string()),
+        };
+        Err(SteganoError::ExtractError(format!(
+            "No payload opened with {} or the public carrier key ({})",
+            keys, reason
+        )))
+    }
+
+    /// Default output for an audio carrier, in the input's format
+    fn audio_output(audio_path: &str) -> &'static str {
+        let extension = Path::new(audio_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("aif") | Some("aiff") | Some("aifc") => "output.aiff",
+            Some("au") | Some("snd") => "output.au",
+            _ => "output.wav",
+        }
+    }
+
     fn encrypt_audio(
         crypto_manager: &CryptoManager,
         audio_path: &str,
+        input: Option<&String>,
+        decoy: Option<(&MasterKey, &[u8])>,
         output: Option<&String>,
     ) -> Result<()> {
-        let data = Self::get_user_input()?;
-        println!("Encrypting data...");
-        let encrypted_data = crypto_manager.encrypt(&data)?;
-        
-        let output_path = output.map(|s| s.as_str()).unwrap_or("output.wav");
-        println!("Embedding encrypted data in audio...");
-        
-        let steg = AudioSteganography::new();
-        steg.embed(audio_path, &encrypted_data, output_path)?;
+        let steg = Self::carrier_steganography(Carrier::Audio, audio_path, crypto_manager, decoy)?;
+        let output_path = output.map(|s| s.as_str()).unwrap_or(Self::audio_output(audio_path));
+        Self::embed_input(crypto_manager, input, steg.as_ref(), audio_path, output_path)?;
         println!("Data encrypted and hidden inside {}", output_path);
         
         Ok(())
     }
 
-    fn decrypt_audio(crypto_manager: &CryptoManager, audio_path: &str) -> Result<()> {
+    fn decrypt_audio(
+        crypto_manager: &CryptoManager,
+        audio_path: &str,
+        trusted_signers: &[SignerPublicKey],
+        extract_to: Option<&String>,
+    ) -> Result<()> {
         println!("Extracting data from audio...");
-        let steg = AudioSteganography::new();
-        let extracted_data = steg.extract(audio_path)?;
-        
-        println!("Decrypting extracted data...");
-        let decrypted_data = crypto_manager.decrypt(&extracted_data)?;
-        
-        match String::from_utf8(decrypted_data.clone()) {
+        let Some(decrypted_data) =
+            Self::extract_and_decrypt(crypto_manager, Carrier::Audio, audio_path, trusted_signers, extract_to)?
+        else {
+            return Ok(());
+        };
+
+        if let Some(path) = extract_to {
+            return Self::write_extracted(&decrypted_data, path);
+        }
+        match std::str::from_utf8(&decrypted_data) {
             Ok(text) => println!("Decrypted data: {}", text),
             Err(_) => {
                 println!("Decrypted data (binary, {} bytes):", decrypted_data.len());
@@ -198,31 +1074,36 @@ impl Cli {
     fn encrypt_pdf(
         crypto_manager: &CryptoManager,
         pdf_path: &str,
+        input: Option<&String>,
         output: Option<&String>,
+        mode: PdfMode,
     ) -> Result<()> {
-        let data = Self::get_user_input()?;
-        println!("Encrypting data...");
-        let encrypted_data = crypto_manager.encrypt(&data)?;
-        
+        let steg = Carrier::Pdf(mode).steganography(pdf_path, crypto_manager.carrier_key());
         let output_path = output.map(|s| s.as_str()).unwrap_or("output.pdf");
-        println!("Embedding encrypted data in PDF...");
-        
-        let steg = PdfSteganography::new();
-        steg.embed(pdf_path, &encrypted_data, output_path)?;
+        Self::embed_input(crypto_manager, input, steg.as_ref(), pdf_path, output_path)?;
         println!("Data encrypted and hidden inside {}", output_path);
         
         Ok(())
     }
 
-    fn decrypt_pdf(crypto_manager: &CryptoManager, pdf_path: &str) -> Result<()> {
+    fn decrypt_pdf(
+        crypto_manager: &CryptoManager,
+        pdf_path: &str,
+        mode: PdfMode,
+        trusted_signers: &[SignerPublicKey],
+        extract_to: Option<&String>,
+    ) -> Result<()> {
         println!("Extracting data from PDF...");
-        let steg = PdfSteganography::new();
-        let extracted_data = steg.extract(pdf_path)?;
-        
-        println!("Decrypting extracted data...");
-        let decrypted_data = crypto_manager.decrypt(&extracted_data)?;
-        
-        match String::from_utf8(decrypted_data.clone()) {
+        let Some(decrypted_data) =
+            Self::extract_and_decrypt(crypto_manager, Carrier::Pdf(mode), pdf_path, trusted_signers, extract_to)?
+        else {
+            return Ok(());
+        };
+
+        if let Some(path) = extract_to {
+            return Self::write_extracted(&decrypted_data, path);
+        }
+        match std::str::from_utf8(&decrypted_data) {
             Ok(text) => println!("Decrypted data: {}", text),
             Err(_) => {
                 println!("Decrypted data (binary, {} bytes):", decrypted_data.len());
@@ -0,0 +1,174 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit, Nonce, Payload},
+    Aes256Gcm,
+};
+use aes_gcm_siv::Aes256GcmSiv;
+use chacha20poly1305::XChaCha20Poly1305;
+use rand::{rng, RngCore};
+use std::fmt;
+use crate::error::{Result, SteganoError};
+
+/// Length of the authentication tag every suite appends
+const TAG_LEN: usize = 16;
+
+/// Authenticated cipher used for a payload, recorded by ID in its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CipherSuite {
+    /// AES-256-GCM with random 96-bit nonces
+    Aes256Gcm,
+    /// XChaCha20-Poly1305: 192-bit random nonces never collide in practice,
+    /// however many payloads share a key
+    #[default]
+    XChaCha20Poly1305,
+    /// AES-256-GCM-SIV: a repeated nonce only reveals whether two payloads are equal
+    Aes256GcmSiv,
+}
+
+impl CipherSuite {
+    /// ID byte stored in the ciphertext header
+    pub fn id(&self) -> u8 {
+        match self {
+            CipherSuite::Aes256Gcm => 1,
+            CipherSuite::XChaCha20Poly1305 => 2,
+            CipherSuite::Aes256GcmSiv => 3,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Result<Self> {
+        match id {
+            1 => Ok(CipherSuite::Aes256Gcm),
+            2 => Ok(CipherSuite::XChaCha20Poly1305),
+            3 => Ok(CipherSuite::Aes256GcmSiv),
+            _ => Err(SteganoError::DecryptionError(format!("Unknown cipher ID {}", id))),
+        }
+    }
+
+    /// Encrypt with a fresh random nonce, returning nonce followed by ciphertext
+    pub(crate) fn encrypt(&self, key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            CipherSuite::Aes256Gcm => encrypt_with::<Aes256Gcm>(*self, key, aad, plaintext),
+            CipherSuite::XChaCha20Poly1305 => encrypt_with::<XChaCha20Poly1305>(*self, key, aad, plaintext),
+            CipherSuite::Aes256GcmSiv => encrypt_with::<Aes256GcmSiv>(*self, key, aad, plaintext),
+        }
+    }
+
+    /// Decrypt nonce followed by ciphertext as written by [`CipherSuite::encrypt`]
+    pub(crate) fn decrypt(&self, key: &[u8; 32], aad: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            CipherSuite::Aes256Gcm => decrypt_with::<Aes256Gcm>(*self, key, aad, data),
+            CipherSuite::XChaCha20Poly1305 => decrypt_with::<XChaCha20Poly1305>(*self, key, aad, data),
+            CipherSuite::Aes256GcmSiv => decrypt_with::<Aes256GcmSiv>(*self, key, aad, data),
+        }
+    }
+
+    /// Nonce size in bytes
+    pub(crate) fn nonce_len(&self) -> usize {
+        match self {
+            CipherSuite::Aes256Gcm | CipherSuite::Aes256GcmSiv => 12,
+            CipherSuite::XChaCha20Poly1305 => 24,
+        }
+    }
+
+    /// Bytes that [`CipherSuite::encrypt`] adds to a plaintext: nonce and tag
+    pub(crate) fn overhead(&self) -> usize {
+        self.nonce_len() + TAG_LEN
+    }
+
+    /// Encrypt under a nonce chosen by the caller, returning the ciphertext
+    /// and tag alone. The caller must never repeat a nonce under one key.
+    pub(crate) fn seal(&self, key: &[u8; 32], nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            CipherSuite::Aes256Gcm => seal_with::<Aes256Gcm>(*self, key, nonce, aad, plaintext),
+            CipherSuite::XChaCha20Poly1305 => seal_with::<XChaCha20Poly1305>(*self, key, nonce, aad, plaintext),
+            CipherSuite::Aes256GcmSiv => seal_with::<Aes256GcmSiv>(*self, key, nonce, aad, plaintext),
+        }
+    }
+
+    /// Decrypt ciphertext and tag written by [`CipherSuite::seal`]
+    pub(crate) fn open(&self, key: &[u8; 32], nonce: &[u8], aad: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            CipherSuite::Aes256Gcm => open_with::<Aes256Gcm>(*self, key, nonce, aad, data),
+            CipherSuite::XChaCha20Poly1305 => open_with::<XChaCha20Poly1305>(*self, key, nonce, aad, data),
+            CipherSuite::Aes256GcmSiv => open_with::<Aes256GcmSiv>(*self, key, nonce, aad, data),
+        }
+    }
+}
+
+impl fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CipherSuite::Aes256Gcm => write!(f, "AES-256-GCM"),
+            CipherSuite::XChaCha20Poly1305 => write!(f, "XChaCha20-Poly1305"),
+            CipherSuite::Aes256GcmSiv => write!(f, "AES-256-GCM-SIV"),
+        }
+    }
+}
+
+fn encrypt_with<C: Aead + KeyInit>(suite: CipherSuite, key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = C::new_from_slice(key)
+        .map_err(|_| SteganoError::EncryptionError("Invalid key length".to_string()))?;
+
+    let mut nonce = Nonce::<C>::default();
+    rng().fill_bytes(&mut nonce);
+
+    let encrypted = cipher
+        .encrypt(&nonce, Payload { msg: plaintext, aad })
+        .map_err(|e| SteganoError::EncryptionError(format!("{} encryption failed: {}", suite, e)))?;
+
+    let mut result = nonce.to_vec();
+    result.extend_from_slice(&encrypted);
+    Ok(result)
+}
+
+fn decrypt_with<C: Aead + KeyInit>(suite: CipherSuite, key: &[u8; 32], aad: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let cipher = C::new_from_slice(key)
+        .map_err(|_| SteganoError::DecryptionError("Invalid key length".to_string()))?;
+
+    let nonce_len = Nonce::<C>::default().len();
+    if data.len() < nonce_len + TAG_LEN {
+        return Err(SteganoError::DecryptionError(
+            "Invalid ciphertext: too short".to_string()
+        ));
+    }
+
+    let (nonce, encrypted) = data.split_at(nonce_len);
+    cipher
+        .decrypt(Nonce::<C>::from_slice(nonce), Payload { msg: encrypted, aad })
+        .map_err(|_| SteganoError::DecryptionError(format!(
+            "{} decryption failed: wrong key, corrupted data or a different bound context",
+            suite
+        )))
+}
+
+fn seal_with<C: Aead + KeyInit>(
+    suite: CipherSuite,
+    key: &[u8; 32],
+    nonce: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>> {
+    let cipher = C::new_from_slice(key)
+        .map_err(|_| SteganoError::EncryptionError("Invalid key length".to_string()))?;
+    if nonce.len() != Nonce::<C>::default().len() {
+        return Err(SteganoError::EncryptionError(format!("Invalid {} nonce length", suite)));
+    }
+
+    cipher
+        .encrypt(Nonce::<C>::from_slice(nonce), Payload { msg: plaintext, aad })
+        .map_err(|e| SteganoError::EncryptionError(format!("{} encryption failed: {}", suite, e)))
+}
+
+fn open_with<C: Aead + KeyInit>(suite: CipherSuite, key: &[u8; 32], nonce: &[u8], aad: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let cipher = C::new_from_slice(key)
+        .map_err(|_| SteganoError::DecryptionError("Invalid key length".to_string()))?;
+    if nonce.len() != Nonce::<C>::default().len() {
+        return Err(SteganoError::DecryptionError(format!("Invalid {} nonce length", suite)));
+    }
+
+    cipher
+        .decrypt(Nonce::<C>::from_slice(nonce), Payload { msg: data, aad })
+        .map_err(|_| SteganoError::DecryptionError(format!(
+            "{} decryption failed: wrong key, corrupted data or a different bound context",
+            suite
+        )))
+}
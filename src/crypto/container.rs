@@ -0,0 +1,265 @@
+//! The payload container every carrier holds:
+//!
+//! ```text
+//! prefix:  magic "SVLT" (4) | version (1) | flags (1) | error-correction scheme (1) | parameter (1)
+//! header:  cipher ID (1) | KDF ID (1) [| KDF params (12) | salt (16)] [| chunk size (4)]
+//!          | key kind (1) | key ID (8) or wrapped content keys [| nonce prefix]
+//! body:    nonce | ciphertext | tag, or STREAM chunks
+//! ```
+//!
+//! The prefix is stored as is. Everything after it is stored through the
+//! error-correction scheme, and the prefix and header, as decoded, are
+//! authenticated with every chunk of the body. Flags say whether the
+//! plaintext was compressed, signed or padded and whether the body is
+//! chunked. There is no
+//! length: carriers frame the container themselves.
+
+use flate2::write::{DeflateDecoder, DeflateEncoder};
+use flate2::Compression;
+use std::io::{self, Read, Write};
+use crate::error::{Result, SteganoError};
+use super::secret::SecretBytes;
+
+pub(crate) const MAGIC: &[u8; 4] = b"SVLT";
+pub(crate) const VERSION: u8 = 1;
+/// magic | version | flags | error-correction scheme | parameter
+pub(crate) const PREFIX_LEN: usize = 4 + 1 + 1 + 1 + 1;
+
+/// The plaintext was compressed with Deflate before padding and encryption
+pub(crate) const FLAG_COMPRESSED: u8 = 0x01;
+/// The body is a series of STREAM chunks
+pub(crate) const FLAG_CHUNKED: u8 = 0x02;
+/// The plaintext starts with its signer's verification key and signature
+pub(crate) const FLAG_SIGNED: u8 = 0x04;
+/// The plaintext starts with its true length and ends with zero padding
+pub(crate) const FLAG_PADDED: u8 = 0x08;
+const KNOWN_FLAGS: u8 = FLAG_COMPRESSED | FLAG_CHUNKED | FLAG_SIGNED | FLAG_PADDED;
+
+/// No key derivation: the key comes from a keyfile or a wrapped content key
+pub(crate) const KDF_NONE: u8 = 0;
+/// Argon2id over a passphrase, with its parameters and salt in the header
+pub(crate) const KDF_ARGON2ID: u8 = 1;
+
+/// Encrypted under the encryption subkey of the key with the ID that follows
+pub(crate) const KEY_ID: u8 = 1;
+/// Encrypted under the encryption subkey of the passphrase-derived key
+pub(crate) const KEY_PASSPHRASE: u8 = 2;
+/// Encrypted under a random content key, wrapped in the slots that follow
+pub(crate) const KEY_WRAPPED: u8 = 3;
+
+/// Error-correction scheme that stores every byte once
+const ECC_NONE: u8 = 0;
+/// Error-correction scheme that stores every byte an odd number of times,
+/// given by the parameter, and takes the majority of each bit
+const ECC_REPETITION: u8 = 1;
+/// Most copies the repetition scheme may ask for
+pub(crate) const MAX_COPIES: u8 = 15;
+
+/// The part of a container stored without error correction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Prefix {
+    pub flags: u8,
+    /// Times every byte after the prefix is stored; 1 means no error correction
+    pub copies: u8,
+}
+
+impl Prefix {
+    pub(crate) fn to_bytes(self) -> [u8; PREFIX_LEN] {
+        let (scheme, parameter) = match self.copies {
+            1 => (ECC_NONE, 0),
+            copies => (ECC_REPETITION, copies),
+        };
+        let mut bytes = [0u8; PREFIX_LEN];
+        bytes[..4].copy_from_slice(MAGIC);
+        bytes[4] = VERSION;
+        bytes[5] = self.flags;
+        bytes[6] = scheme;
+        bytes[7] = parameter;
+        bytes
+    }
+
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < PREFIX_LEN || !bytes.starts_with(MAGIC) {
+            return Err(SteganoError::DecryptionError("Data is not a SteganoVault container".to_string()));
+        }
+        if bytes[4] != VERSION {
+            return Err(SteganoError::DecryptionError(format!("Unsupported container version {}", bytes[4])));
+        }
+        if bytes[5] & !KNOWN_FLAGS != 0 {
+            return Err(SteganoError::DecryptionError(format!("Unsupported container flags {:#04x}", bytes[5])));
+        }
+        let copies = match (bytes[6], bytes[7]) {
+            (ECC_NONE, _) => 1,
+            (ECC_REPETITION, copies) => {
+                check_copies(copies).map_err(|e| SteganoError::DecryptionError(e.to_string()))?;
+                copies
+            }
+            (scheme, _) => {
+                return Err(SteganoError::DecryptionError(format!(
+                    "Unsupported error-correction scheme {}",
+                    scheme
+                )));
+            }
+        };
+        Ok(Self { flags: bytes[5], copies })
+    }
+
+    pub(crate) fn has(self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Bytes after the prefix, before error correction, in a container of
+    /// `len` bytes
+    pub(crate) fn decoded_len(self, len: usize) -> usize {
+        PREFIX_LEN + len.saturating_sub(PREFIX_LEN) / self.copies as usize
+    }
+}
+
+/// Check a number of copies for the repetition scheme
+pub(crate) fn check_copies(copies: u8) -> Result<()> {
+    if copies == 0 || copies > MAX_COPIES || copies.is_multiple_of(2) {
+        return Err(SteganoError::InvalidData(format!(
+            "Error correction needs an odd number of copies from 1 to {}, got {}",
+            MAX_COPIES, copies
+        )));
+    }
+    Ok(())
+}
+
+/// Writes every byte `copies` times
+pub(crate) struct RepetitionWriter<W> {
+    inner: W,
+    copies: usize,
+}
+
+impl<W: Write> RepetitionWriter<W> {
+    pub(crate) fn new(inner: W, copies: u8) -> Self {
+        Self { inner, copies: copies as usize }
+    }
+}
+
+impl<W: Write> Write for RepetitionWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.copies == 1 {
+            return self.inner.write(buf);
+        }
+        let repeated: Vec<u8> = buf
+            .iter()
+            .flat_map(|&byte| std::iter::repeat_n(byte, self.copies))
+            .collect();
+        self.inner.write_all(&repeated)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads bytes written by [`RepetitionWriter`], correcting any bit that
+/// fewer than half of its copies got wrong
+pub(crate) struct RepetitionReader<R> {
+    inner: R,
+    copies: usize,
+    group: Vec<u8>,
+}
+
+impl<R: Read> RepetitionReader<R> {
+    pub(crate) fn new(inner: R, copies: u8) -> Self {
+        Self { inner, copies: copies as usize, group: vec![0u8; copies as usize] }
+    }
+}
+
+impl<R: Read> Read for RepetitionReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.copies == 1 {
+            return self.inner.read(buf);
+        }
+        for (count, byte) in buf.iter_mut().enumerate() {
+            // A group cut short by the end of the data ends it
+            match self.inner.read_exact(&mut self.group) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(count),
+                Err(e) => return Err(e),
+            }
+            *byte = (0..8).fold(0u8, |byte, bit| {
+                let ones = self.group.iter().filter(|copy| (*copy >> bit) & 1 == 1).count();
+                byte | (u8::from(ones * 2 > self.copies) << bit)
+            });
+        }
+        Ok(buf.len())
+    }
+}
+
+/// Deflate `data` into a buffer of exactly the compressed size, so no
+/// partial copy is left behind by a reallocation
+pub(crate) fn compress(data: &[u8]) -> Result<SecretBytes> {
+    let mut counter = DeflateEncoder::new(io::sink(), Compression::default());
+    counter.write_all(data)?;
+    counter.try_finish()?;
+
+    let mut compressed = SecretBytes::from(vec![0u8; counter.total_out() as usize]);
+    let mut encoder = DeflateEncoder::new(compressed.expose_mut(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()?;
+    Ok(compressed)
+}
+
+/// Inflate data made by [`compress`], sized up front like it
+pub(crate) fn decompress(data: &[u8]) -> Result<SecretBytes> {
+    let invalid = |_| SteganoError::DecryptionError("Compressed data is corrupted".to_string());
+    let mut counter = DeflateDecoder::new(io::sink());
+    counter.write_all(data).map_err(invalid)?;
+    counter.try_finish().map_err(invalid)?;
+
+    let mut plaintext = SecretBytes::from(vec![0u8; counter.total_out() as usize]);
+    let mut decoder = DeflateDecoder::new(plaintext.expose_mut());
+    decoder.write_all(data).map_err(invalid)?;
+    decoder.finish().map_err(invalid)?;
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_round_trips() {
+        let prefix = Prefix { flags: FLAG_COMPRESSED | FLAG_SIGNED, copies: 5 };
+        assert_eq!(Prefix::parse(&prefix.to_bytes()).unwrap(), prefix);
+    }
+
+    #[test]
+    fn unknown_flags_and_versions_are_refused() {
+        let mut bytes = Prefix { flags: 0, copies: 1 }.to_bytes();
+        bytes[5] = 0x80;
+        assert!(Prefix::parse(&bytes).is_err());
+
+        let mut bytes = Prefix { flags: 0, copies: 1 }.to_bytes();
+        bytes[4] = VERSION + 1;
+        assert!(Prefix::parse(&bytes).is_err());
+        assert!(Prefix::parse(b"SVLT").is_err());
+    }
+
+    #[test]
+    fn repetition_corrects_a_minority_of_copies() {
+        let mut encoded = Vec::new();
+        RepetitionWriter::new(&mut encoded, 3).write_all(b"data").unwrap();
+        assert_eq!(encoded.len(), 12);
+        encoded[0] ^= 0xff;
+        encoded[4] ^= 0x0f;
+
+        let mut decoded = Vec::new();
+        RepetitionReader::new(&encoded[..], 3).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"data");
+    }
+
+    #[test]
+    fn compression_round_trips() {
+        let data = vec![42u8; 1000];
+        let compressed = compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&compressed).unwrap().expose(), &data[..]);
+        assert!(decompress(&[0xff; 8]).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+/// Field tags in the encoded context
+const TAG_CARRIER: u8 = 1;
+const TAG_PARAMETERS: u8 = 2;
+const TAG_LABEL: u8 = 3;
+const TAG_CARRIER_DIGEST: u8 = 4;
+
+/// Where a payload is meant to live. It is authenticated as associated data,
+/// so decryption fails unless exactly the same context is given again: a
+/// payload moved to another carrier, or read with other parameters, is rejected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PayloadContext {
+    carrier: Option<String>,
+    parameters: Option<String>,
+    label: Option<String>,
+    carrier_digest: Option<[u8; 32]>,
+}
+
+impl PayloadContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Carrier type, such as `png` or `pdf`
+    pub fn with_carrier(mut self, carrier: &str) -> Self {
+        self.carrier = Some(carrier.to_string());
+        self
+    }
+
+    /// Embedding parameters, such as the PDF mode
+    pub fn with_parameters(mut self, parameters: &str) -> Self {
+        self.parameters = Some(parameters.to_string());
+        self
+    }
+
+    /// Free-form label chosen by the user
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// Hash of the carrier regions that embedding leaves unchanged
+    pub fn with_carrier_digest(mut self, digest: [u8; 32]) -> Self {
+        self.carrier_digest = Some(digest);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Tagged, length-prefixed fields in a fixed order; empty when nothing is set,
+    /// so unbound payloads authenticate exactly what they did before
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let fields: [(u8, Option<&[u8]>); 4] = [
+            (TAG_CARRIER, self.carrier.as_deref().map(str::as_bytes)),
+            (TAG_PARAMETERS, self.parameters.as_deref().map(str::as_bytes)),
+            (TAG_LABEL, self.label.as_deref().map(str::as_bytes)),
+            (TAG_CARRIER_DIGEST, self.carrier_digest.as_ref().map(|digest| &digest[..])),
+        ];
+
+        let mut bytes = Vec::new();
+        for (tag, value) in fields {
+            if let Some(value) = value {
+                bytes.push(tag);
+                bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(value);
+            }
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_context_encodes_to_nothing() {
+        assert!(PayloadContext::new().is_empty());
+        assert!(PayloadContext::new().to_bytes().is_empty());
+    }
+
+    #[test]
+    fn fields_cannot_stand_in_for_each_other() {
+        let label = PayloadContext::new().with_label("pdf");
+        let carrier = PayloadContext::new().with_carrier("pdf");
+        assert_ne!(label.to_bytes(), carrier.to_bytes());
+
+        // Length prefixes keep field boundaries from shifting
+        let split = PayloadContext::new().with_carrier("pd").with_parameters("f");
+        let joined = PayloadContext::new().with_carrier("pdf").with_parameters("");
+        assert_ne!(split.to_bytes(), joined.to_bytes());
+    }
+}
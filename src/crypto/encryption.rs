@@ -2,61 +2,660 @@ use aes_gcm::{
     aead::Aead,
     Aes256Gcm, Key, KeyInit, Nonce,
 };
+use flate2::{read::DeflateEncoder, write::DeflateDecoder, Compression};
 use rand::{rng, RngCore};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
 use crate::error::{Result, SteganoError};
+use super::cipher::CipherSuite;
+use super::container::{
+    self, Prefix, RepetitionReader, RepetitionWriter, FLAG_CHUNKED, FLAG_COMPRESSED, KDF_ARGON2ID, KDF_NONE,
+    FLAG_PADDED, FLAG_SIGNED, KEY_ID, KEY_PASSPHRASE, KEY_WRAPPED, PREFIX_LEN,
+};
+use super::context::PayloadContext;
+use super::kdf::{self, KdfParams, KDF_PARAMS_LEN};
+use super::keyring::Keyring;
+use super::hybrid::{self, HybridSecretKey};
+use super::master::MasterKey;
+use super::padding::{self, Padding};
+use super::recipient::{self, Recipient, RecipientSecretKey};
+use super::secret::{KeyId, SecretBytes, SecretKey};
+use super::signature::{self, SignerKeypair, SignerPublicKey};
+use super::stream::{self, DEFAULT_CHUNK_SIZE};
+
+const SALT_LEN: usize = 16;
 
+/// Fixed salt for the carrier key of a passphrase, which has to be derivable
+/// before the payload (and its random salt) can be located
+const CARRIER_KEY_SALT: &[u8] = b"stegano-vault carrier key";
+
+#[derive(Clone)]
+struct Passphrase {
+    secret: SecretBytes,
+    params: KdfParams,
+}
+
+#[derive(Clone)]
 pub struct CryptoManager {
-    aes_key: [u8; 32],
+    /// Key for carrier layouts, lent out by `carrier_key`
+    carrier_key: MasterKey,
+    /// Symmetric payload key, for keyfile-based managers
+    key: Option<MasterKey>,
+    /// Older keys from a keyring, tried when decrypting only
+    previous_keys: Vec<MasterKey>,
+    passphrase: Option<Passphrase>,
+    secret_key: Option<RecipientSecretKey>,
+    hybrid_secret_key: Option<HybridSecretKey>,
+    /// Additional recipients that new payloads are also encrypted to
+    recipients: Vec<Recipient>,
+    signer: Option<SignerKeypair>,
+    /// Encoded [`PayloadContext`], authenticated after every payload header
+    context: Vec<u8>,
+    cipher: CipherSuite,
+    /// Plaintext bytes per chunk of a streamed payload
+    chunk_size: u32,
+    padding: Padding,
+    /// Whether new payloads compress their plaintext
+    compress: bool,
+    /// Times every byte after the container prefix is stored
+    copies: u8,
 }
 
 impl CryptoManager {
     /// Create a new instance with a random key
     pub fn new() -> Self {
-        let mut key = [0u8; 32];
-        rng().fill_bytes(&mut key);
-        Self { aes_key: key }
+        Self::with_key(SecretKey::generate())
     }
     
-    /// Create with an existing key
-    pub fn with_key(key: [u8; 32]) -> Self {
-        Self { aes_key: key }
+    /// Create with an existing key. Payloads and carriers use subkeys
+    /// derived from it, never the key itself.
+    pub fn with_key(key: SecretKey) -> Self {
+        let key = MasterKey::new(key);
+        Self { key: Some(key.clone()), ..Self::with_carrier_key(key) }
+    }
+
+    /// Encrypt under the keyring's current key and decrypt with whichever of
+    /// its keys a payload was made with
+    pub fn with_keyring(keyring: &Keyring) -> Result<Self> {
+        let current = keyring.current().ok_or_else(|| SteganoError::InvalidKey(
+            if keyring.is_empty() {
+                "The keyring holds no keys".to_string()
+            } else {
+                "The keyring has no current key".to_string()
+            }
+        ))?;
+        Ok(Self {
+            previous_keys: keyring
+                .keys()
+                .map(|(key, _)| key)
+                .filter(|key| key.id() != current.id())
+                .map(|key| MasterKey::new(key.clone()))
+                .collect(),
+            ..Self::with_key(current.clone())
+        })
+    }
+
+    /// Encrypt to X25519 or hybrid public keys. Each payload gets a random
+    /// content key, wrapped for every recipient through an ephemeral key
+    /// exchange, so no symmetric key has to be shared.
+    pub fn for_recipients<R: Into<Recipient>>(recipients: Vec<R>) -> Result<Self> {
+        if recipients.is_empty() {
+            return Err(SteganoError::InvalidKey("At least one recipient is required".to_string()));
+        }
+        Ok(Self {
+            recipients: recipients.into_iter().map(Into::into).collect(),
+            ..Self::with_carrier_key(Self::public_carrier_key())
+        })
+    }
+
+    /// Decrypt payloads encrypted to the public key of `secret_key`
+    pub fn with_secret_key(secret_key: RecipientSecretKey) -> Self {
+        Self { secret_key: Some(secret_key), ..Self::with_carrier_key(Self::public_carrier_key()) }
+    }
+
+    /// Decrypt payloads encrypted to the public key of a hybrid `secret_key`
+    pub fn with_hybrid_secret_key(secret_key: HybridSecretKey) -> Self {
+        Self { hybrid_secret_key: Some(secret_key), ..Self::with_carrier_key(Self::public_carrier_key()) }
     }
 
-    /// Get a copy of the current key
-    pub fn get_key(&self) -> [u8; 32] {
-        self.aes_key
+    /// Create from a passphrase. Each payload gets its own random salt, and the
+    /// salt and Argon2id parameters travel in the payload header, so only the
+    /// passphrase is needed to decrypt.
+    pub fn with_passphrase(passphrase: &str) -> Result<Self> {
+        Self::with_passphrase_params(passphrase, KdfParams::default())
     }
 
-    /// Encrypt plaintext using AES-256-GCM
+    /// Create from a passphrase with custom Argon2id costs for new payloads
+    pub fn with_passphrase_params(passphrase: &str, params: KdfParams) -> Result<Self> {
+        if passphrase.is_empty() {
+            return Err(SteganoError::InvalidKey("Passphrase must not be empty".to_string()));
+        }
+
+        // The carrier key (see carrier_key) always uses the default costs so it
+        // can be rebuilt from the passphrase alone
+        let carrier_key = kdf::derive_key(passphrase.as_bytes(), CARRIER_KEY_SALT, &KdfParams::default())?;
+        let carrier_key = MasterKey::new(carrier_key);
+        Ok(Self {
+            passphrase: Some(Passphrase { secret: SecretBytes::from(passphrase.as_bytes()), params }),
+            ..Self::with_carrier_key(carrier_key)
+        })
+    }
+
+    /// Also encrypt new payloads to `recipient`. The payload then carries a
+    /// random content key wrapped once for this manager's own key and once for
+    /// every added recipient, and any of them can open it.
+    pub fn with_recipient(mut self, recipient: Recipient) -> Self {
+        self.carrier_key = Self::public_carrier_key();
+        self.recipients.push(recipient);
+        self
+    }
+
+    /// Sign the plaintext of new payloads with `signer`. The signature and the
+    /// signer's key are encrypted with the data, so only readers learn who signed.
+    pub fn with_signer(mut self, signer: SignerKeypair) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Bind new payloads to `context`; decryption then needs the same context
+    pub fn with_context(mut self, context: &PayloadContext) -> Self {
+        self.context = context.to_bytes();
+        self
+    }
+
+    /// Use `cipher` for new payloads. Decryption reads the cipher from the
+    /// payload header whatever is set here.
+    pub fn with_cipher(mut self, cipher: CipherSuite) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Plaintext bytes per chunk for [`CryptoManager::encrypt_stream`]; larger
+    /// chunks cost more memory and less space
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Result<Self> {
+        stream::check_chunk_size(chunk_size)?;
+        self.chunk_size = chunk_size;
+        Ok(self)
+    }
+
+    /// Pad the plaintext of new payloads as `padding` asks, hiding the length
+    /// of the data. Decryption strips any padding whatever is set here.
+    pub fn with_padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Compress the plaintext of new payloads with Deflate before padding
+    /// and encryption. How well data compresses shows in the payload length,
+    /// so pad as well when that matters.
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Whether new payloads compress their plaintext
+    pub fn compresses(&self) -> bool {
+        self.compress
+    }
+
+    /// Store everything after the container prefix `copies` times, so a
+    /// payload survives carrier bits flipped in fewer than half of the
+    /// copies. Must be odd; 1 turns error correction off.
+    pub fn with_error_correction(mut self, copies: u8) -> Result<Self> {
+        container::check_copies(copies)?;
+        self.copies = copies;
+        Ok(self)
+    }
+
+    /// Master key for carrier layouts such as image slots and PDF stealth
+    /// masking, lent rather than copied; carriers derive their permutation,
+    /// mask and MAC keys from it. For a keyfile this is its key; for a
+    /// passphrase a key derived with a fixed salt, while payloads themselves
+    /// are encrypted under a freshly salted key. Payloads for public keys or
+    /// several recipients have no single shared secret, so their carriers use
+    /// [`CryptoManager::public_carrier_key`].
+    pub fn carrier_key(&self) -> &MasterKey {
+        &self.carrier_key
+    }
+
+    /// Carrier keys to try when extracting: [`CryptoManager::carrier_key`],
+    /// then the carrier key of every older keyring key
+    pub fn carrier_keys(&self) -> impl Iterator<Item = &MasterKey> {
+        std::iter::once(&self.carrier_key).chain(&self.previous_keys)
+    }
+
+    /// Symmetric payload key as stored in a keyfile, for keyfile-based managers
+    pub(crate) fn key(&self) -> Option<&SecretKey> {
+        self.key.as_ref().map(MasterKey::root_key)
+    }
+
+    /// ID of the symmetric payload key, for keyfile-based managers
+    pub fn key_id(&self) -> Option<KeyId> {
+        self.key.as_ref().map(MasterKey::id)
+    }
+
+    /// Carrier key shared by every public-key and multi-recipient payload
+    pub fn public_carrier_key() -> MasterKey {
+        MasterKey::new(SecretKey::new(&mut Sha256::digest(b"stegano-vault public-key carrier").into()))
+    }
+
+    /// This manager's own credential as a recipient, so another manager can
+    /// encrypt to it as well
+    pub fn recipient(&self) -> Option<Recipient> {
+        if let Some(key) = &self.key {
+            return Some(Recipient::Key(key.root_key().clone()));
+        }
+        if let Some(passphrase) = &self.passphrase {
+            return Some(Recipient::Passphrase(passphrase.secret.clone(), passphrase.params));
+        }
+        if let Some(secret_key) = &self.hybrid_secret_key {
+            return Some(Recipient::Hybrid(secret_key.public_key()));
+        }
+        self.secret_key
+            .as_ref()
+            .map(|secret_key| Recipient::PublicKey(secret_key.public_key()))
+    }
+
+    /// Encrypt plaintext with the selected cipher suite
     pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
-        let key = Key::<Aes256Gcm>::from_slice(&self.aes_key);
-        let cipher = Aes256Gcm::new(key);
-        
-        // Generate a random nonce
-        let mut nonce_bytes = [0u8; 12];
-        rng().fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
-        
-        // Encrypt the data
-        let encrypted = cipher
-            .encrypt(nonce, plaintext.as_ref())
-            .map_err(|e| SteganoError::EncryptionError(format!("AES encryption failed: {}", e)))?;
-        
-        // Prepend nonce to ciphertext for later decryption
-        let mut result = nonce_bytes.to_vec();
-        result.extend_from_slice(&encrypted);
-        
-        // Include a length prefix for easier extraction
-        let len = result.len() as u32;
-        let len_bytes = len.to_le_bytes();
-        let mut final_result = len_bytes.to_vec();
-        final_result.extend_from_slice(&result);
-        
-        Ok(final_result)
+        match &self.signer {
+            Some(signer) => self.encrypt_payload(&SecretBytes::from(signature::sign(signer, plaintext)), true),
+            None => self.encrypt_payload(plaintext, false),
+        }
+    }
+
+    /// Encrypt everything `reader` yields into `writer` in chunks, holding
+    /// one chunk in memory at a time. The container puts no limit on the
+    /// length, but carriers do: slot headers and PDF payloads record it in
+    /// 32 bits. Returns the number of plaintext bytes. Streamed payloads cannot be
+    /// signed or padded, since both need the length of the whole plaintext.
+    pub fn encrypt_stream<R: Read, W: Write>(&self, reader: R, mut writer: W) -> Result<u64> {
+        if self.signer.is_some() {
+            return Err(SteganoError::EncryptionError("Streamed payloads cannot be signed".to_string()));
+        }
+        if self.padding != Padding::None {
+            return Err(SteganoError::EncryptionError("Streamed payloads cannot be padded".to_string()));
+        }
+
+        let flags = FLAG_CHUNKED | if self.compress { FLAG_COMPRESSED } else { 0 };
+        let (mut header, key) = self.container_header(flags, Some(self.chunk_size))?;
+        let mut nonce_prefix = vec![0u8; stream::nonce_prefix_len(self.cipher)];
+        rng().fill_bytes(&mut nonce_prefix);
+        header.extend_from_slice(&nonce_prefix);
+        let aad = self.aad(&header);
+
+        writer.write_all(&header[..PREFIX_LEN])?;
+        let mut writer = RepetitionWriter::new(writer, self.copies);
+        writer.write_all(&header[PREFIX_LEN..])?;
+
+        if self.compress {
+            let mut reader = DeflateEncoder::new(reader, Compression::default());
+            stream::encrypt(self.cipher, &key, &nonce_prefix, self.chunk_size, &aad, &mut reader, writer)?;
+            return Ok(reader.total_in());
+        }
+        stream::encrypt(self.cipher, &key, &nonce_prefix, self.chunk_size, &aad, reader, writer)
+    }
+
+    /// Decrypt a payload written by [`CryptoManager::encrypt_stream`] from
+    /// `reader` into `writer`, one chunk at a time. Returns the number of
+    /// plaintext bytes. On an error, whatever was already written must be
+    /// discarded: it has been authenticated, but may be incomplete.
+    pub fn decrypt_stream<R: Read, W: Write>(&self, reader: R, writer: W) -> Result<u64> {
+        self.decrypt_chunks(reader, writer, true)
+    }
+
+    /// Whether `ciphertext` is a streamed payload, which `decrypt_stream`
+    /// can write out a chunk at a time instead of holding it in memory
+    pub fn is_streamed(ciphertext: &[u8]) -> bool {
+        Prefix::parse(ciphertext).is_ok_and(|prefix| prefix.has(FLAG_CHUNKED))
+    }
+
+    /// Decrypt a streamed payload, inflating compressed plaintext only if
+    /// `decompress` is set. Returns the number of bytes written.
+    fn decrypt_chunks<R: Read, W: Write>(&self, mut reader: R, writer: W, decompress: bool) -> Result<u64> {
+        let mut prefix = [0u8; PREFIX_LEN];
+        read_header(&mut reader, &mut prefix)?;
+        let parsed = Prefix::parse(&prefix)?;
+        if !parsed.has(FLAG_CHUNKED) {
+            return Err(SteganoError::DecryptionError("Data is not a streamed payload".to_string()));
+        }
+        if parsed.has(FLAG_SIGNED) || parsed.has(FLAG_PADDED) {
+            return Err(SteganoError::DecryptionError("Streamed payloads cannot be signed or padded".to_string()));
+        }
+
+        let mut reader = RepetitionReader::new(reader, parsed.copies);
+        let (mut header, cipher, key, chunk_size) = self.read_container_header(&prefix, &mut reader)?;
+        let chunk_size = chunk_size.expect("chunked containers have a chunk size");
+        let start = read_more(&mut reader, &mut header, stream::nonce_prefix_len(cipher))?;
+        let nonce_prefix = header[start..].to_vec();
+        let aad = self.aad(&header);
+
+        if decompress && parsed.has(FLAG_COMPRESSED) {
+            let mut writer = DeflateDecoder::new(writer);
+            stream::decrypt(cipher, &key, &nonce_prefix, chunk_size, &aad, reader, &mut writer)?;
+            writer.try_finish()?;
+            return Ok(writer.total_out());
+        }
+        stream::decrypt(cipher, &key, &nonce_prefix, chunk_size, &aad, reader, writer)
+    }
+
+    /// Decrypt a payload made for this manager and encrypt it again for
+    /// `target`. A signature travels along unchanged rather than being
+    /// replaced by the target's signer, while compression and error
+    /// correction follow the target. A padded payload stays padded unless
+    /// the target pads of its own accord: to its old length if the target's
+    /// header leaves room, or else to a power of two.
+    pub fn rekey(&self, ciphertext: &[u8], target: &CryptoManager) -> Result<Vec<u8>> {
+        let (message, signed) = self.decrypt_payload(ciphertext)?;
+        let padded = Prefix::parse(ciphertext).is_ok_and(|prefix| prefix.has(FLAG_PADDED));
+        if !padded || target.padding != Padding::None {
+            return target.encrypt_payload(&message, signed);
+        }
+        target
+            .clone()
+            .with_padding(Padding::ToLength(ciphertext.len()))
+            .encrypt_payload(&message, signed)
+            .or_else(|_| target.clone().with_padding(Padding::PowerOfTwo).encrypt_payload(&message, signed))
+    }
+
+    /// A container holding `message`, compressed and padded as configured
+    /// and flagged as signed if it was made by [`signature::sign`]
+    fn encrypt_payload(&self, message: &[u8], signed: bool) -> Result<Vec<u8>> {
+        let compressed;
+        let plaintext = if self.compress {
+            compressed = container::compress(message)?;
+            &compressed[..]
+        } else {
+            message
+        };
+
+        let mut flags = if self.compress { FLAG_COMPRESSED } else { 0 };
+        if signed {
+            flags |= FLAG_SIGNED;
+        }
+        if self.padding != Padding::None {
+            flags |= FLAG_PADDED;
+        }
+        let (header, key) = self.container_header(flags, None)?;
+        let padded = self.pad(plaintext, header.len())?;
+        let plaintext = padded.as_deref().unwrap_or(plaintext);
+        let body = self.cipher.encrypt(key.expose(), &self.aad(&header), plaintext)?;
+
+        // Error correction covers everything after the prefix
+        let mut result = header[..PREFIX_LEN].to_vec();
+        let mut writer = RepetitionWriter::new(&mut result, self.copies);
+        writer.write_all(&header[PREFIX_LEN..])?;
+        writer.write_all(&body)?;
+        Ok(result)
+    }
+
+    /// Decrypt ciphertext, picking the cipher suite from its header. Payloads
+    /// from before cipher IDs existed are decrypted as AES-256-GCM. A signed
+    /// payload is checked and its signature stripped.
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<SecretBytes> {
+        self.decrypt_signed(ciphertext).map(|(plaintext, _)| plaintext)
+    }
+
+    /// Decrypt ciphertext and return the verified signer's key alongside the
+    /// plaintext, or `None` if the payload is not signed
+    pub fn decrypt_signed(&self, ciphertext: &[u8]) -> Result<(SecretBytes, Option<SignerPublicKey>)> {
+        let (plaintext, signed) = self.decrypt_payload(ciphertext)?;
+        if !signed {
+            return Ok((plaintext, None));
+        }
+        let (message, signer) = signature::verify(&plaintext)?;
+        Ok((message, Some(signer)))
+    }
+
+    /// The message in a payload, without padding and decompressed, but
+    /// still signed if it was, and whether it is signed
+    fn decrypt_payload(&self, ciphertext: &[u8]) -> Result<(SecretBytes, bool)> {
+        if !ciphertext.starts_with(container::MAGIC) {
+            return Ok((self.decrypt_before_container(ciphertext)?, false));
+        }
+        let signed = Prefix::parse(ciphertext)?.has(FLAG_SIGNED);
+        Ok((self.open_container(ciphertext)?, signed))
     }
 
-    /// Decrypt ciphertext using AES-256-GCM
-    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    fn open_container(&self, ciphertext: &[u8]) -> Result<SecretBytes> {
+        let prefix = Prefix::parse(ciphertext)?;
+        if prefix.has(FLAG_CHUNKED) {
+            // The compressed plaintext is shorter than the payload, so a
+            // buffer of that size never has to grow
+            let mut plaintext = SecretBytes::from(vec![0u8; ciphertext.len()]);
+            let len = self.decrypt_chunks(ciphertext, plaintext.expose_mut(), false)? as usize;
+            let plaintext = SecretBytes::from(&plaintext[..len]);
+            return match prefix.has(FLAG_COMPRESSED) {
+                true => container::decompress(&plaintext),
+                false => Ok(plaintext),
+            };
+        }
+
+        let mut reader = RepetitionReader::new(&ciphertext[PREFIX_LEN..], prefix.copies);
+        let (header, cipher, key, _) = self.read_container_header(&ciphertext[..PREFIX_LEN], &mut reader)?;
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body)?;
+
+        let plaintext = cipher.decrypt(key.expose(), &self.aad(&header), &body).map(SecretBytes::from)?;
+        let plaintext = match prefix.has(FLAG_PADDED) {
+            true => padding::unpad(&plaintext)?,
+            false => plaintext,
+        };
+        match prefix.has(FLAG_COMPRESSED) {
+            true => container::decompress(&plaintext),
+            false => Ok(plaintext),
+        }
+    }
+
+    /// Length-prefixed payloads of SteganoVault 0.2, under the keyfile key
+    /// or any older keyring key
+    fn decrypt_before_container(&self, ciphertext: &[u8]) -> Result<SecretBytes> {
+        if self.key.is_none() {
+            return Err(SteganoError::DecryptionError("Data is protected by a keyfile".to_string()));
+        }
+        if !self.context.is_empty() {
+            return Err(SteganoError::DecryptionError(
+                "Data predates context binding and cannot match a context".to_string()
+            ));
+        }
+        self.first_success(|key| Self::decrypt_legacy(key.root_key(), ciphertext))
+    }
+
+    /// The keyfile key followed by any older keyring keys
+    fn keys(&self) -> impl Iterator<Item = &MasterKey> {
+        self.key.iter().chain(&self.previous_keys)
+    }
+
+    /// Decrypt with each key in turn, returning the first success or the
+    /// error from the current key
+    fn first_success<T: Into<SecretBytes>>(&self, decrypt: impl Fn(&MasterKey) -> Result<T>) -> Result<SecretBytes> {
+        let mut first_error = None;
+        for key in self.keys() {
+            match decrypt(key) {
+                Ok(plaintext) => return Ok(plaintext.into()),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        Err(first_error.expect("a keyfile-based manager has a key"))
+    }
+
+    /// "the supplied key is ..." or "the supplied keys are ...", for errors
+    fn supplied_keys(&self) -> String {
+        let ids: Vec<String> = self.keys().map(|key| key.id().to_string()).collect();
+        match ids.len() {
+            1 => format!("the supplied key is {}", ids[0]),
+            _ => format!("the supplied keys are {}", ids.join(", ")),
+        }
+    }
+
+    /// Associated data for a payload: its header, then the bound context
+    fn aad(&self, header: &[u8]) -> Vec<u8> {
+        let mut aad = header.to_vec();
+        aad.extend_from_slice(&self.context);
+        aad
+    }
+
+    /// The plaintext padded for a container whose header is `header_len`
+    /// bytes, or `None` if no padding is set
+    fn pad(&self, plaintext: &[u8], header_len: usize) -> Result<Option<SecretBytes>> {
+        let overhead = header_len + self.cipher.overhead();
+        match self.padding {
+            // A length to fill counts every copy that error correction stores
+            Padding::ToLength(len) if self.copies > 1 => {
+                let decoded_len = Prefix { flags: 0, copies: self.copies }.decoded_len(len);
+                padding::pad(Padding::ToLength(decoded_len), plaintext, overhead).map_err(|_| {
+                    SteganoError::EncryptionError(format!(
+                        "Data does not fit in {} bytes when error correction stores {} copies",
+                        len, self.copies
+                    ))
+                })
+            }
+            padding => padding::pad(padding, plaintext, overhead),
+        }
+    }
+
+    /// Prefix and header of a new container, and the key its body is
+    /// encrypted under. A lone keyfile or passphrase encrypts directly;
+    /// with recipients, or with only a recipient's public key, a random
+    /// content key is wrapped for each of them.
+    fn container_header(&self, flags: u8, chunk_size: Option<u32>) -> Result<(Vec<u8>, SecretKey)> {
+        let mut header = Prefix { flags, copies: self.copies }.to_bytes().to_vec();
+        header.push(self.cipher.id());
+
+        let passphrase = self.passphrase.as_ref().filter(|_| self.recipients.is_empty());
+        let mut salt = [0u8; SALT_LEN];
+        match passphrase {
+            Some(passphrase) => {
+                rng().fill_bytes(&mut salt);
+                header.push(KDF_ARGON2ID);
+                header.extend_from_slice(&passphrase.params.to_bytes());
+                header.extend_from_slice(&salt);
+            }
+            None => header.push(KDF_NONE),
+        }
+        if let Some(chunk_size) = chunk_size {
+            header.extend_from_slice(&chunk_size.to_le_bytes());
+        }
+
+        if let Some(passphrase) = passphrase {
+            header.push(KEY_PASSPHRASE);
+            let key = kdf::derive_key(&passphrase.secret, &salt, &passphrase.params)?;
+            return Ok((header, MasterKey::new(key).encryption_key()));
+        }
+        if let Some(key) = self.key.as_ref().filter(|_| self.recipients.is_empty()) {
+            header.push(KEY_ID);
+            header.extend_from_slice(&key.id().to_bytes());
+            return Ok((header, key.encryption_key()));
+        }
+
+        // Every slot authenticates the header before it
+        header.push(KEY_WRAPPED);
+        let (content_key, slots) = self.wrap_content_key(&header)?;
+        header.extend_from_slice(&slots);
+        Ok((header, content_key))
+    }
+
+    /// Read a container header from `reader`, positioned after `prefix`,
+    /// and find the key for its body. Returns the decoded prefix and header,
+    /// the cipher, the key and the chunk size of a chunked container.
+    fn read_container_header<R: Read>(
+        &self,
+        prefix: &[u8],
+        reader: &mut R,
+    ) -> Result<(Vec<u8>, CipherSuite, SecretKey, Option<u32>)> {
+        let chunked = Prefix::parse(prefix)?.has(FLAG_CHUNKED);
+        let mut header = prefix.to_vec();
+
+        let start = read_more(reader, &mut header, 2)?;
+        let cipher = CipherSuite::from_id(header[start])?;
+        let kdf = match header[start + 1] {
+            KDF_NONE => None,
+            KDF_ARGON2ID => {
+                let start = read_more(reader, &mut header, KDF_PARAMS_LEN + SALT_LEN)?;
+                let params = KdfParams::from_bytes(&header[start..start + KDF_PARAMS_LEN])
+                    .map_err(|e| SteganoError::DecryptionError(e.to_string()))?;
+                Some((params, start + KDF_PARAMS_LEN))
+            }
+            kdf => {
+                return Err(SteganoError::DecryptionError(format!("Unsupported key derivation {}", kdf)));
+            }
+        };
+        let chunk_size = match chunked {
+            true => {
+                let start = read_more(reader, &mut header, 4)?;
+                let chunk_size = u32::from_le_bytes(header[start..].try_into().expect("slice is 4 bytes"));
+                stream::check_chunk_size(chunk_size).map_err(|e| SteganoError::DecryptionError(e.to_string()))?;
+                Some(chunk_size)
+            }
+            false => None,
+        };
+
+        let start = read_more(reader, &mut header, 1)?;
+        let key = match header[start] {
+            KEY_PASSPHRASE => {
+                let (params, salt_start) = kdf.ok_or_else(|| SteganoError::DecryptionError(
+                    "Passphrase payload has no key derivation parameters".to_string()
+                ))?;
+                let passphrase = self.passphrase.as_ref().ok_or_else(|| SteganoError::DecryptionError(
+                    "Data is protected by a passphrase, not a keyfile".to_string()
+                ))?;
+                let salt = &header[salt_start..salt_start + SALT_LEN];
+                MasterKey::new(kdf::derive_key(&passphrase.secret, salt, &params)?).encryption_key()
+            }
+            KEY_ID => {
+                if self.key.is_none() {
+                    return Err(SteganoError::DecryptionError("Data is protected by a keyfile".to_string()));
+                }
+                let start = read_more(reader, &mut header, 8)?;
+                let id = KeyId::from_bytes(header[start..].try_into().expect("slice is 8 bytes"));
+                let key = self.keys().find(|key| key.id() == id).ok_or_else(|| SteganoError::DecryptionError(
+                    format!("This carrier was made with key {}, but {}", id, self.supplied_keys())
+                ))?;
+                key.encryption_key()
+            }
+            KEY_WRAPPED => {
+                let slots_start = header.len();
+                read_slots(reader, &mut header)?;
+                let (prefix, slots) = header.split_at(slots_start);
+                self.unwrap_content_key(cipher, prefix, slots)?.0
+            }
+            kind => {
+                return Err(SteganoError::DecryptionError(format!("Unsupported key kind {}", kind)));
+            }
+        };
+        Ok((header, cipher, key, chunk_size))
+    }
+
+    fn with_carrier_key(carrier_key: MasterKey) -> Self {
+        Self {
+            carrier_key,
+            key: None,
+            previous_keys: Vec::new(),
+            passphrase: None,
+            secret_key: None,
+            hybrid_secret_key: None,
+            recipients: Vec::new(),
+            signer: None,
+            context: Vec::new(),
+            cipher: CipherSuite::default(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            padding: Padding::None,
+            compress: false,
+            copies: 1,
+        }
+    }
+
+    /// Whether `payload` is a SteganoVault 0.2 payload that `key` opens. Its
+    /// authentication tag is the only thing that tells such a payload apart
+    /// from random carrier bits.
+    pub(crate) fn is_legacy_payload(key: &MasterKey, payload: &[u8]) -> bool {
+        Self::decrypt_legacy(key.root_key(), payload).is_ok()
+    }
+
+    /// Length-prefixed AES-256-GCM payloads written by SteganoVault 0.2
+    fn decrypt_legacy(key: &SecretKey, ciphertext: &[u8]) -> Result<SecretBytes> {
         // Need at least 4 bytes for length + 12 bytes for nonce + 1 byte for data
         if ciphertext.len() < 17 {
             return Err(SteganoError::DecryptionError(
@@ -83,14 +682,136 @@ impl CryptoManager {
         // Extract the nonce from the first 12 bytes
         let nonce = Nonce::from_slice(&actual_data[0..12]);
         
-        let key = Key::<Aes256Gcm>::from_slice(&self.aes_key);
+        let key = Key::<Aes256Gcm>::from_slice(key.expose());
         let cipher = Aes256Gcm::new(key);
         
         // Decrypt using the extracted nonce and the rest of the ciphertext
         cipher
             .decrypt(nonce, &actual_data[12..])
+            .map(SecretBytes::from)
             .map_err(|e| SteganoError::DecryptionError(format!("AES decryption failed: {}", e)))
     }
+
+    /// A random content key and the slot count and slots wrapping it for this
+    /// manager's credential and every added recipient, each slot
+    /// authenticating `prefix`
+    fn wrap_content_key(&self, prefix: &[u8]) -> Result<(SecretKey, Vec<u8>)> {
+        let recipients: Vec<Recipient> = self
+            .recipient()
+            .into_iter()
+            .chain(self.recipients.iter().cloned())
+            .collect();
+        if recipients.is_empty() {
+            return Err(SteganoError::EncryptionError(
+                "A recipient secret key can only decrypt; add a recipient to encrypt".to_string()
+            ));
+        }
+        if recipients.len() > u8::MAX as usize {
+            return Err(SteganoError::EncryptionError(
+                format!("Too many recipients: at most {} are supported", u8::MAX)
+            ));
+        }
+
+        let content_key = SecretKey::generate();
+        let mut slots = vec![recipients.len() as u8];
+        for recipient in &recipients {
+            let (kind, slot) = recipient.wrap(self.cipher, prefix, &content_key)?;
+            slots.push(kind);
+            slots.extend_from_slice(&(slot.len() as u16).to_le_bytes());
+            slots.extend_from_slice(&slot);
+        }
+        Ok((content_key, slots))
+    }
+
+    /// Walk the slot count and slots at the start of `slots`, unwrapping the
+    /// content key with this manager's key. Returns the key and the length of
+    /// the slot section.
+    fn unwrap_content_key(&self, cipher: CipherSuite, prefix: &[u8], slots: &[u8]) -> Result<(SecretKey, usize)> {
+        let too_short = || SteganoError::DecryptionError("Invalid ciphertext: too short".to_string());
+
+        // Walk every slot: the header ends after the last one
+        let mut pos = 1;
+        let mut content_key = None;
+        let mut key_ids = Vec::new();
+        for _ in 0..*slots.first().ok_or_else(too_short)? {
+            let kind = *slots.get(pos).ok_or_else(too_short)?;
+            let len_bytes = slots.get(pos + 1..pos + 3).ok_or_else(too_short)?;
+            let len = u16::from_le_bytes([len_bytes[0], len_bytes[1]]) as usize;
+            let slot = slots.get(pos + 3..pos + 3 + len).ok_or_else(too_short)?;
+            pos += 3 + len;
+
+            if kind == recipient::SLOT_NAMED_KEY {
+                key_ids.extend(recipient::slot_key_id(slot));
+            }
+            if content_key.is_some() {
+                continue;
+            }
+            content_key = match (kind, &self.passphrase, &self.secret_key) {
+                (recipient::SLOT_NAMED_KEY, _, _) => {
+                    self.keys().find_map(|key| recipient::unwrap_with_named_key(key.root_key(), cipher, prefix, slot))
+                }
+                (recipient::SLOT_PASSPHRASE, Some(passphrase), _) => {
+                    recipient::unwrap_with_passphrase(&passphrase.secret, cipher, prefix, slot)
+                }
+                (recipient::SLOT_X25519, _, Some(secret_key)) => {
+                    recipient::unwrap_with_secret_key(secret_key, cipher, prefix, slot)
+                }
+                (recipient::SLOT_HYBRID, _, _) => self
+                    .hybrid_secret_key
+                    .as_ref()
+                    .and_then(|secret_key| hybrid::unwrap_with_secret_key(secret_key, cipher, prefix, slot)),
+                _ => None,
+            };
+        }
+
+        let content_key = content_key.ok_or_else(|| SteganoError::DecryptionError(
+            match (&self.secret_key, &self.hybrid_secret_key, &self.key) {
+                (Some(secret_key), _, _) => format!(
+                    "Data is not encrypted to public key {}",
+                    secret_key.public_key().fingerprint()
+                ),
+                (None, Some(secret_key), _) => format!(
+                    "Data is not encrypted to hybrid public key {}",
+                    secret_key.public_key().fingerprint()
+                ),
+                (None, None, Some(_)) if !key_ids.is_empty() => format!(
+                    "This carrier was made for {} {}, but {}",
+                    if key_ids.len() == 1 { "key" } else { "keys" },
+                    key_ids.iter().map(KeyId::to_string).collect::<Vec<_>>().join(", "),
+                    self.supplied_keys()
+                ),
+                _ => "None of the payload's recipients matches this key".to_string(),
+            }
+        ))?;
+        Ok((content_key, pos))
+    }
+}
+
+/// Read part of a payload's header, which must not end early
+fn read_header<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<()> {
+    reader.read_exact(buffer).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => SteganoError::DecryptionError("Invalid ciphertext: too short".to_string()),
+        _ => e.into(),
+    })
+}
+
+/// Append the next `len` bytes of a header to `header`, returning where they start
+fn read_more<R: Read>(reader: &mut R, header: &mut Vec<u8>, len: usize) -> Result<usize> {
+    let start = header.len();
+    header.resize(start + len, 0);
+    read_header(reader, &mut header[start..])?;
+    Ok(start)
+}
+
+/// Append a slot count and the kind, length and data of every slot
+fn read_slots<R: Read>(reader: &mut R, header: &mut Vec<u8>) -> Result<()> {
+    let count_at = read_more(reader, header, 1)?;
+    for _ in 0..header[count_at] {
+        let start = read_more(reader, header, 3)?;
+        let len = u16::from_le_bytes([header[start + 1], header[start + 2]]) as usize;
+        read_more(reader, header, len)?;
+    }
+    Ok(())
 }
 
 impl Default for CryptoManager {
@@ -98,3 +819,245 @@ impl Default for CryptoManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_recipient_opens_the_payload() {
+        let other = SecretKey::generate();
+        let manager = CryptoManager::new().with_recipient(Recipient::Key(other.clone()));
+        let ciphertext = manager.encrypt(b"secret").unwrap();
+        assert_eq!(manager.decrypt(&ciphertext).unwrap().expose(), b"secret");
+        assert_eq!(CryptoManager::with_key(other).decrypt(&ciphertext).unwrap().expose(), b"secret");
+        assert!(CryptoManager::new().decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn keyfile_passphrase_and_public_key_recipients_share_one_payload() {
+        let keyfile = SecretKey::generate();
+        let secret_key = RecipientSecretKey::generate();
+        let params = KdfParams::new(8, 1, 1);
+        let manager = CryptoManager::for_recipients(vec![secret_key.public_key()])
+            .unwrap()
+            .with_recipient(Recipient::Key(keyfile.clone()))
+            .with_recipient(Recipient::Passphrase(SecretBytes::from(b"hunter2".as_slice()), params));
+        let ciphertext = manager.encrypt(b"secret").unwrap();
+
+        let openers = [
+            CryptoManager::with_key(keyfile),
+            CryptoManager::with_secret_key(secret_key),
+            CryptoManager::with_passphrase_params("hunter2", params).unwrap(),
+        ];
+        for opener in openers {
+            assert_eq!(opener.decrypt(&ciphertext).unwrap().expose(), b"secret");
+        }
+        assert!(CryptoManager::with_passphrase_params("hunter3", params).unwrap().decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn unmatched_keyfile_is_told_which_keys_the_payload_names() {
+        let first = SecretKey::generate();
+        let manager = CryptoManager::new().with_recipient(Recipient::Key(first.clone()));
+        let ciphertext = manager.encrypt(b"secret").unwrap();
+
+        let error = CryptoManager::new().decrypt(&ciphertext).unwrap_err().to_string();
+        assert!(error.contains(&first.id().to_string()), "{}", error);
+    }
+
+    #[test]
+    fn signed_payloads_report_their_signer() {
+        let signer = SignerKeypair::generate();
+        let manager = CryptoManager::new().with_signer(signer.clone());
+        let ciphertext = manager.encrypt(b"secret").unwrap();
+        assert!(Prefix::parse(&ciphertext).unwrap().has(FLAG_SIGNED));
+        let (plaintext, public_key) = manager.decrypt_signed(&ciphertext).unwrap();
+        assert_eq!(plaintext.expose(), b"secret");
+        assert_eq!(public_key, Some(signer.public_key()));
+
+        let unsigned = CryptoManager::new();
+        let (_, public_key) = unsigned.decrypt_signed(&unsigned.encrypt(b"secret").unwrap()).unwrap();
+        assert_eq!(public_key, None);
+    }
+
+    #[test]
+    fn bound_payload_opens_only_in_the_same_context() {
+        let context = PayloadContext::new().with_carrier("png").with_label("Q3 audit");
+        let manager = CryptoManager::new().with_context(&context);
+        let ciphertext = manager.encrypt(b"secret").unwrap();
+        assert_eq!(manager.decrypt(&ciphertext).unwrap().expose(), b"secret");
+
+        let moved = manager.clone().with_context(&PayloadContext::new().with_carrier("wav").with_label("Q3 audit"));
+        assert!(moved.decrypt(&ciphertext).is_err());
+        let unbound = manager.clone().with_context(&PayloadContext::new());
+        assert!(unbound.decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn payloads_are_not_encrypted_under_the_root_key() {
+        let key = SecretKey::generate();
+        let ciphertext = CryptoManager::with_key(key.clone()).encrypt(b"secret").unwrap();
+        let (header, cipher, _, _) = CryptoManager::with_key(key.clone())
+            .read_container_header(&ciphertext[..PREFIX_LEN], &mut &ciphertext[PREFIX_LEN..])
+            .unwrap();
+        let body = &ciphertext[header.len()..];
+        assert!(cipher.decrypt(key.expose(), &header, body).is_err());
+        assert!(cipher.decrypt(MasterKey::new(key).encryption_key().expose(), &header, body).is_ok());
+    }
+
+    /// A streamed payload of five 1 KiB chunks, the last one short
+    fn streamed(manager: &CryptoManager) -> (Vec<u8>, Vec<u8>) {
+        let plaintext: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+        let mut ciphertext = Vec::new();
+        assert_eq!(manager.encrypt_stream(&plaintext[..], &mut ciphertext).unwrap(), 5000);
+        (plaintext, ciphertext)
+    }
+
+    #[test]
+    fn streamed_payloads_decrypt_a_chunk_at_a_time() {
+        let manager = CryptoManager::new().with_chunk_size(1024).unwrap();
+        let (plaintext, ciphertext) = streamed(&manager);
+        assert!(CryptoManager::is_streamed(&ciphertext));
+        assert!(!CryptoManager::is_streamed(&manager.encrypt(b"secret").unwrap()));
+
+        let mut decrypted = Vec::new();
+        assert_eq!(manager.decrypt_stream(&ciphertext[..], &mut decrypted).unwrap(), 5000);
+        assert_eq!(decrypted, plaintext);
+        assert_eq!(manager.decrypt(&ciphertext).unwrap().expose(), plaintext.as_slice());
+    }
+
+    #[test]
+    fn reordered_and_truncated_chunks_are_refused() {
+        let manager = CryptoManager::new().with_chunk_size(1024).unwrap();
+        let (_, ciphertext) = streamed(&manager);
+        // Sealed chunks are 1040 bytes, the last 904 + 16
+        let first = ciphertext.len() - 920 - 4 * 1040;
+
+        let mut reordered = ciphertext.clone();
+        reordered[first..first + 2080].rotate_left(1040);
+        let error = manager.decrypt_stream(&reordered[..], &mut Vec::new()).unwrap_err().to_string();
+        assert!(error.contains("Chunk 0"), "{}", error);
+
+        let truncated = &ciphertext[..ciphertext.len() - 920];
+        let error = manager.decrypt_stream(truncated, &mut Vec::new()).unwrap_err().to_string();
+        assert!(error.contains("Chunk 3"), "{}", error);
+        assert!(manager.decrypt(truncated).is_err());
+    }
+
+    #[test]
+    fn padded_payloads_hide_the_message_length() {
+        let crypto = CryptoManager::new().with_padding(Padding::ToLength(256));
+        let short = crypto.encrypt(b"hi").unwrap();
+        let long = crypto.encrypt(&[7u8; 100]).unwrap();
+        assert_eq!(short.len(), 256);
+        assert_eq!(long.len(), 256);
+        assert!(Prefix::parse(&short).unwrap().has(FLAG_PADDED));
+        assert_eq!(crypto.decrypt(&short).unwrap().expose(), b"hi");
+        assert_eq!(crypto.decrypt(&long).unwrap().expose(), [7u8; 100]);
+        assert!(crypto.encrypt(&[0u8; 300]).is_err());
+    }
+
+    #[test]
+    fn rekeyed_padded_payloads_stay_padded() {
+        let old = CryptoManager::new().with_padding(Padding::PowerOfTwo);
+        let ciphertext = old.encrypt(b"secret").unwrap();
+        let target = CryptoManager::new();
+        let rekeyed = old.rekey(&ciphertext, &target).unwrap();
+        assert_eq!(rekeyed.len(), ciphertext.len());
+        assert!(Prefix::parse(&rekeyed).unwrap().has(FLAG_PADDED));
+        assert_eq!(target.decrypt(&rekeyed).unwrap().expose(), b"secret");
+
+        // A passphrase header is longer, so the payload no longer fits its
+        // old length and is padded to a power of two instead
+        let passphrase = CryptoManager::with_passphrase_params("hunter2", KdfParams::new(8, 1, 1)).unwrap();
+        let rekeyed = old.rekey(&ciphertext, &passphrase).unwrap();
+        assert!(rekeyed.len() > ciphertext.len());
+        assert!(Prefix::parse(&rekeyed).unwrap().has(FLAG_PADDED));
+        assert_eq!(passphrase.decrypt(&rekeyed).unwrap().expose(), b"secret");
+
+        let unpadded = CryptoManager::new();
+        let rekeyed = unpadded.rekey(&unpadded.encrypt(b"secret").unwrap(), &target).unwrap();
+        assert!(!Prefix::parse(&rekeyed).unwrap().has(FLAG_PADDED));
+    }
+
+    #[test]
+    fn rekeyed_payloads_keep_their_signature() {
+        let signer = SignerKeypair::generate();
+        let source = CryptoManager::new().with_signer(signer.clone());
+        let ciphertext = source.encrypt(b"secret").unwrap();
+        let target = CryptoManager::new();
+        let rekeyed = source.rekey(&ciphertext, &target).unwrap();
+        let (plaintext, public_key) = target.decrypt_signed(&rekeyed).unwrap();
+        assert_eq!(plaintext.expose(), b"secret");
+        assert_eq!(public_key, Some(signer.public_key()));
+        assert!(source.decrypt(&rekeyed).is_err());
+    }
+
+    #[test]
+    fn container_round_trips() {
+        let manager = CryptoManager::new();
+        let ciphertext = manager.encrypt(b"secret").unwrap();
+        assert!(ciphertext.starts_with(container::MAGIC));
+        assert_eq!(manager.decrypt(&ciphertext).unwrap().expose(), b"secret");
+    }
+
+    #[test]
+    fn compressed_padded_and_corrected_containers_round_trip() {
+        let manager = CryptoManager::new()
+            .with_compression(true)
+            .with_padding(Padding::PowerOfTwo)
+            .with_error_correction(3)
+            .unwrap();
+        let ciphertext = manager.encrypt(&[7u8; 300]).unwrap();
+        let prefix = Prefix::parse(&ciphertext).unwrap();
+        assert!(prefix.has(FLAG_COMPRESSED) && prefix.has(FLAG_PADDED) && !prefix.has(FLAG_SIGNED));
+        assert_eq!(manager.decrypt(&ciphertext).unwrap().expose(), &[7u8; 300]);
+    }
+
+    #[test]
+    fn wrong_key_is_refused() {
+        let ciphertext = CryptoManager::new().encrypt(b"secret").unwrap();
+        assert!(CryptoManager::new().decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn tampered_body_is_refused() {
+        let manager = CryptoManager::new();
+        let mut ciphertext = manager.encrypt(b"secret").unwrap();
+        *ciphertext.last_mut().unwrap() ^= 1;
+        assert!(manager.decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn tampered_flags_are_refused() {
+        // The prefix is authenticated, so a flag cannot be added or removed
+        let manager = CryptoManager::new();
+        let mut ciphertext = manager.encrypt(b"secret").unwrap();
+        ciphertext[5] |= FLAG_PADDED;
+        assert!(manager.decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn error_correction_repairs_a_damaged_byte() {
+        let manager = CryptoManager::new().with_error_correction(3).unwrap();
+        let mut ciphertext = manager.encrypt(b"secret").unwrap();
+        ciphertext[PREFIX_LEN + 4] ^= 0xff;
+        assert_eq!(manager.decrypt(&ciphertext).unwrap().expose(), b"secret");
+    }
+
+    #[test]
+    fn version_0_2_payloads_decrypt() {
+        let key = SecretKey::generate();
+        let nonce = [3u8; 12];
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.expose()));
+        let sealed = cipher.encrypt(Nonce::from_slice(&nonce), b"zero two".as_ref()).unwrap();
+        let mut payload = ((12 + sealed.len()) as u32).to_le_bytes().to_vec();
+        payload.extend_from_slice(&nonce);
+        payload.extend_from_slice(&sealed);
+
+        assert_eq!(CryptoManager::with_key(key.clone()).decrypt(&payload).unwrap().expose(), b"zero two");
+        assert!(CryptoManager::is_legacy_payload(&MasterKey::new(key), &payload));
+        assert!(CryptoManager::new().decrypt(&payload).is_err());
+    }
+}
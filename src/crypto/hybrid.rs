@@ -0,0 +1,275 @@
+use hkdf::Hkdf;
+use ml_kem::ml_kem_768::{DecapsulationKey, EncapsulationKey};
+use ml_kem::{Decapsulate, Encapsulate, Key, KeyExport, Seed};
+use rand::{rng, RngCore};
+use sha2::Sha256;
+use std::fmt;
+use crate::error::{Result, SteganoError};
+use super::cipher::CipherSuite;
+use super::recipient::{self, decode_hex, encode_hex, RecipientPublicKey, RecipientSecretKey};
+use super::secret::{SecretBytes, SecretKey};
+
+/// Prefix of an exported hybrid public key
+const PUBLIC_KEY_PREFIX: &str = "svhybrid:";
+
+/// ML-KEM-768 encapsulation key, ciphertext and seed sizes
+const ML_KEM_PUBLIC_KEY_LEN: usize = 1184;
+const ML_KEM_CIPHERTEXT_LEN: usize = 1088;
+const ML_KEM_SEED_LEN: usize = 64;
+
+/// X25519 secret key followed by the ML-KEM-768 seed
+pub const SECRET_KEY_LEN: usize = 32 + ML_KEM_SEED_LEN;
+
+/// A hybrid X25519 + ML-KEM-768 public key. Payloads encrypted to it stay
+/// confidential as long as either key exchange holds, so data captured now
+/// is protected against a future quantum computer breaking X25519.
+#[derive(Clone, PartialEq, Eq)]
+pub struct HybridPublicKey {
+    x25519: RecipientPublicKey,
+    ml_kem: Vec<u8>,
+}
+
+impl HybridPublicKey {
+    /// X25519 public key followed by the ML-KEM-768 encapsulation key
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let invalid = || SteganoError::InvalidKey(format!(
+            "Hybrid public key must be {} bytes",
+            32 + ML_KEM_PUBLIC_KEY_LEN
+        ));
+        if bytes.len() != 32 + ML_KEM_PUBLIC_KEY_LEN {
+            return Err(invalid());
+        }
+
+        let x25519 = RecipientPublicKey::from_bytes(bytes[..32].try_into().expect("length checked above"));
+        let public_key = Self { x25519, ml_kem: bytes[32..].to_vec() };
+        public_key.encapsulation_key()?;
+        Ok(public_key)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.x25519.to_bytes().to_vec();
+        bytes.extend_from_slice(&self.ml_kem);
+        bytes
+    }
+
+    /// Parse the `svhybrid:<hex>` form produced by `Display`
+    pub fn parse(text: &str) -> Result<Self> {
+        let hex = text
+            .trim()
+            .strip_prefix(PUBLIC_KEY_PREFIX)
+            .ok_or_else(|| SteganoError::InvalidKey(format!(
+                "Hybrid public key must start with '{}'",
+                PUBLIC_KEY_PREFIX
+            )))?;
+
+        let bytes = decode_hex(hex).ok_or_else(|| SteganoError::InvalidKey(
+            "Hybrid public key is not valid hexadecimal".to_string()
+        ))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Short SHA-256 fingerprint over both keys
+    pub fn fingerprint(&self) -> String {
+        recipient::fingerprint(&self.to_bytes())
+    }
+
+    fn encapsulation_key(&self) -> Result<EncapsulationKey> {
+        Key::<EncapsulationKey>::try_from(self.ml_kem.as_slice())
+            .ok()
+            .and_then(|key| EncapsulationKey::new(&key).ok())
+            .ok_or_else(|| SteganoError::InvalidKey("Hybrid public key holds an invalid ML-KEM-768 key".to_string()))
+    }
+}
+
+impl fmt::Debug for HybridPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HybridPublicKey({})", self.fingerprint())
+    }
+}
+
+impl fmt::Display for HybridPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", PUBLIC_KEY_PREFIX, encode_hex(&self.to_bytes()))
+    }
+}
+
+/// The secret half of a [`HybridPublicKey`]. Wiped when dropped.
+#[derive(Clone)]
+pub struct HybridSecretKey {
+    x25519: RecipientSecretKey,
+    /// The ML-KEM-768 decapsulation key is rebuilt from this seed
+    ml_kem_seed: SecretBytes,
+}
+
+impl HybridSecretKey {
+    pub fn generate() -> Self {
+        let mut bytes = SecretBytes::from(vec![0u8; SECRET_KEY_LEN]);
+        rng().fill_bytes(bytes.expose_mut());
+        Self::from_bytes(&bytes).expect("generated key has the right length")
+    }
+
+    /// X25519 secret key followed by the 64-byte ML-KEM-768 seed
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != SECRET_KEY_LEN {
+            return Err(SteganoError::InvalidKey(
+                format!("Hybrid secret key must be {} bytes", SECRET_KEY_LEN)
+            ));
+        }
+        let x25519 = SecretKey::from_slice(&bytes[..32]).expect("length checked above");
+        Ok(Self {
+            x25519: RecipientSecretKey::from_bytes(&x25519),
+            ml_kem_seed: SecretBytes::from(bytes[32..].to_vec()),
+        })
+    }
+
+    pub fn to_bytes(&self) -> SecretBytes {
+        let mut bytes = self.x25519.to_bytes().expose().to_vec();
+        bytes.extend_from_slice(&self.ml_kem_seed);
+        SecretBytes::from(bytes)
+    }
+
+    pub fn public_key(&self) -> HybridPublicKey {
+        HybridPublicKey {
+            x25519: self.x25519.public_key(),
+            ml_kem: self.decapsulation_key().encapsulation_key().to_bytes().to_vec(),
+        }
+    }
+
+    fn decapsulation_key(&self) -> DecapsulationKey {
+        let seed = Seed::try_from(&self.ml_kem_seed[..]).expect("seed length checked on creation");
+        DecapsulationKey::from_seed(seed)
+    }
+}
+
+impl fmt::Debug for HybridSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HybridSecretKey({}, [REDACTED])", self.public_key().fingerprint())
+    }
+}
+
+/// Wrap `content_key` for `recipient` with a fresh ephemeral X25519 key and
+/// an ML-KEM-768 encapsulation. Returns the ephemeral public key, the ML-KEM
+/// ciphertext and the wrapped key.
+pub(crate) fn wrap_for(
+    recipient: &HybridPublicKey,
+    cipher: CipherSuite,
+    aad: &[u8],
+    content_key: &SecretKey,
+) -> Result<Vec<u8>> {
+    let ephemeral = RecipientSecretKey::generate();
+    let ephemeral_public = ephemeral.public_key();
+    let x25519_shared = recipient::diffie_hellman(&ephemeral, &recipient.x25519).ok_or_else(|| {
+        SteganoError::InvalidKey(format!("Public key {} is not a usable X25519 key", recipient.fingerprint()))
+    })?;
+
+    let (ml_kem_ciphertext, ml_kem_shared) = recipient.encapsulation_key()?.encapsulate();
+    let ml_kem_shared = SecretKey::new(&mut ml_kem_shared.into());
+
+    let wrapping_key = combine(&x25519_shared, &ml_kem_shared, &ephemeral_public, &ml_kem_ciphertext, recipient);
+
+    let mut slot = ephemeral_public.to_bytes().to_vec();
+    slot.extend_from_slice(&ml_kem_ciphertext);
+    slot.extend_from_slice(&cipher.encrypt(wrapping_key.expose(), aad, content_key.expose())?);
+    Ok(slot)
+}
+
+/// Recover the content key from a slot made by [`wrap_for`], or `None` if the
+/// slot was made for another key
+pub(crate) fn unwrap_with_secret_key(
+    secret: &HybridSecretKey,
+    cipher: CipherSuite,
+    aad: &[u8],
+    slot: &[u8],
+) -> Option<SecretKey> {
+    let ephemeral_public = RecipientPublicKey::from_bytes(slot.get(..32)?.try_into().ok()?);
+    let ml_kem_ciphertext = slot.get(32..32 + ML_KEM_CIPHERTEXT_LEN)?;
+
+    let x25519_shared = recipient::diffie_hellman(&secret.x25519, &ephemeral_public)?;
+    // ML-KEM decapsulation never fails: a ciphertext for another key yields
+    // an unrelated secret, which the AEAD then rejects
+    let ml_kem_shared = secret.decapsulation_key().decapsulate_slice(ml_kem_ciphertext).ok()?;
+    let ml_kem_shared = SecretKey::new(&mut ml_kem_shared.into());
+
+    let wrapping_key = combine(
+        &x25519_shared,
+        &ml_kem_shared,
+        &ephemeral_public,
+        ml_kem_ciphertext,
+        &secret.public_key(),
+    );
+    let content_key = SecretBytes::from(
+        cipher.decrypt(wrapping_key.expose(), aad, &slot[32 + ML_KEM_CIPHERTEXT_LEN..]).ok()?
+    );
+    SecretKey::from_slice(&content_key)
+}
+
+/// HKDF-SHA256 over both shared secrets, bound to the ephemeral key, the
+/// ML-KEM ciphertext and the recipient's keys, so the result is secret as long
+/// as either exchange is
+fn combine(
+    x25519_shared: &SecretKey,
+    ml_kem_shared: &SecretKey,
+    ephemeral_public: &RecipientPublicKey,
+    ml_kem_ciphertext: &[u8],
+    recipient: &HybridPublicKey,
+) -> SecretKey {
+    let mut secrets = x25519_shared.expose().to_vec();
+    secrets.extend_from_slice(ml_kem_shared.expose());
+    let secrets = SecretBytes::from(secrets);
+
+    let mut salt = ephemeral_public.to_bytes().to_vec();
+    salt.extend_from_slice(ml_kem_ciphertext);
+    salt.extend_from_slice(&recipient.to_bytes());
+
+    let mut key = SecretKey::new(&mut [0u8; 32]);
+    Hkdf::<Sha256>::new(Some(&salt), &secrets)
+        .expand(b"stegano-vault hybrid key wrap", key.expose_mut())
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{CryptoManager, Recipient};
+
+    #[test]
+    fn keys_survive_export() {
+        let secret_key = HybridSecretKey::generate();
+        let public_key = secret_key.public_key();
+        assert_eq!(HybridPublicKey::parse(&public_key.to_string()).unwrap(), public_key);
+        let restored = HybridSecretKey::from_bytes(&secret_key.to_bytes()).unwrap();
+        assert_eq!(restored.public_key(), public_key);
+        assert!(HybridPublicKey::from_bytes(&public_key.to_bytes()[..100]).is_err());
+    }
+
+    #[test]
+    fn payload_opens_only_with_the_hybrid_secret_key() {
+        let secret_key = HybridSecretKey::generate();
+        let ciphertext = CryptoManager::for_recipients(vec![Recipient::Hybrid(secret_key.public_key())])
+            .unwrap()
+            .encrypt(b"post-quantum")
+            .unwrap();
+
+        let opened = CryptoManager::with_hybrid_secret_key(secret_key).decrypt(&ciphertext).unwrap();
+        assert_eq!(opened.expose(), b"post-quantum");
+        let other = CryptoManager::with_hybrid_secret_key(HybridSecretKey::generate());
+        assert!(other.decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn slot_needs_both_key_exchanges() {
+        let secret_key = HybridSecretKey::generate();
+        let content_key = SecretKey::generate();
+        let slot = wrap_for(&secret_key.public_key(), CipherSuite::default(), b"aad", &content_key).unwrap();
+        let unwrapped = unwrap_with_secret_key(&secret_key, CipherSuite::default(), b"aad", &slot).unwrap();
+        assert_eq!(unwrapped, content_key);
+
+        // A damaged ephemeral X25519 key or ML-KEM ciphertext each break the slot
+        for position in [0, 32 + ML_KEM_CIPHERTEXT_LEN / 2] {
+            let mut damaged = slot.clone();
+            damaged[position] ^= 1;
+            assert!(unwrap_with_secret_key(&secret_key, CipherSuite::default(), b"aad", &damaged).is_none());
+        }
+    }
+}
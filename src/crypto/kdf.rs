@@ -0,0 +1,73 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use crate::error::{Result, SteganoError};
+use super::secret::SecretKey;
+
+/// Size of the encoded parameters: memory, iterations and lanes as u32 LE
+pub const KDF_PARAMS_LEN: usize = 12;
+
+/// Largest memory cost accepted from a payload header (1 GiB), so a crafted
+/// carrier cannot make decryption allocate without bound
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 64;
+const MAX_LANES: u32 = 16;
+
+/// Argon2id cost parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of passes over the memory
+    pub iterations: u32,
+    /// Degree of parallelism
+    pub lanes: u32,
+}
+
+impl KdfParams {
+    pub fn new(memory_kib: u32, iterations: u32, lanes: u32) -> Self {
+        Self { memory_kib, iterations, lanes }
+    }
+
+    pub fn to_bytes(&self) -> [u8; KDF_PARAMS_LEN] {
+        let mut bytes = [0u8; KDF_PARAMS_LEN];
+        bytes[0..4].copy_from_slice(&self.memory_kib.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.iterations.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.lanes.to_le_bytes());
+        bytes
+    }
+
+    /// Decode parameters read from untrusted input, rejecting costs above our limits
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < KDF_PARAMS_LEN {
+            return Err(SteganoError::InvalidData("Truncated key derivation parameters".to_string()));
+        }
+        let field = |idx: usize| u32::from_le_bytes([bytes[idx], bytes[idx + 1], bytes[idx + 2], bytes[idx + 3]]);
+        let params = Self::new(field(0), field(4), field(8));
+
+        if params.memory_kib > MAX_MEMORY_KIB || params.iterations > MAX_ITERATIONS || params.lanes > MAX_LANES {
+            return Err(SteganoError::InvalidData(format!(
+                "Key derivation parameters exceed limits: {} KiB, {} iterations, {} lanes",
+                params.memory_kib, params.iterations, params.lanes
+            )));
+        }
+        Ok(params)
+    }
+}
+
+impl Default for KdfParams {
+    /// 64 MiB, 3 passes, 1 lane
+    fn default() -> Self {
+        Self::new(64 * 1024, 3, 1)
+    }
+}
+
+/// Derive a 256-bit key from a passphrase with Argon2id
+pub fn derive_key(passphrase: &[u8], salt: &[u8], params: &KdfParams) -> Result<SecretKey> {
+    let argon_params = Params::new(params.memory_kib, params.iterations, params.lanes, Some(32))
+        .map_err(|e| SteganoError::InvalidKey(format!("Invalid key derivation parameters: {}", e)))?;
+
+    let mut key = SecretKey::new(&mut [0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params)
+        .hash_password_into(passphrase, salt, key.expose_mut())
+        .map_err(|e| SteganoError::InvalidKey(format!("Key derivation failed: {}", e)))?;
+    Ok(key)
+}
@@ -1,7 +1,131 @@
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes256Gcm, Key, KeyInit, Nonce,
+};
+use rand::{rng, RngCore};
+use sha2::{Digest, Sha256};
 use std::fs;
+use std::io::{Read, Write};
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 use crate::error::{Result, SteganoError};
 use super::encryption::CryptoManager;
+use super::hybrid::{self, HybridPublicKey, HybridSecretKey};
+use super::kdf::{self, KdfParams, KDF_PARAMS_LEN};
+use super::keyring::Keyring;
+use super::recipient::{Recipient, RecipientPublicKey, RecipientSecretKey};
+use super::secret::{KeyId, SecretBytes, SecretKey};
+use super::signature::{SignerKeypair, SignerPublicKey};
+
+/// Marks a keyfile with metadata. Keyfiles from before it are 32 bare bytes.
+const KEY_MAGIC: &[u8; 4] = b"SVKY";
+const KEY_VERSION: u8 = 1;
+
+/// Marks a keyfile whose key is wrapped under a passphrase
+const KEYFILE_MAGIC: &[u8; 4] = b"SVKF";
+const KEYFILE_VERSION: u8 = 2;
+const SALT_LEN: usize = 16;
+/// magic | version | KDF params | salt
+const KEYFILE_HEADER_LEN: usize = 4 + 1 + KDF_PARAMS_LEN + SALT_LEN;
+
+/// Truncated SHA-256 of everything before it, ending every keyfile
+const CHECKSUM_LEN: usize = 4;
+/// created | key ID | purpose length, before the purpose itself
+const METADATA_FIXED_LEN: usize = 8 + 8 + 1;
+/// Purpose recorded in keyfiles created without one
+const DEFAULT_PURPOSE: &str = "encryption";
+
+/// Marks a keyring file: the current key's ID and several keyfiles
+const KEYRING_MAGIC: &[u8; 4] = b"SVKR";
+const KEYRING_VERSION: u8 = 1;
+/// magic | version | current key ID | keyfile count
+const KEYRING_HEADER_LEN: usize = 4 + 1 + 8 + 2;
+/// File in a keyring directory naming the current key by ID or file name
+const KEYRING_CURRENT_FILE: &str = "CURRENT";
+/// Extension of the keyfiles a keyring directory is made of
+const KEYRING_KEY_EXTENSION: &str = "key";
+
+/// Marks an X25519 recipient secret key file
+const SECRET_KEY_MAGIC: &[u8; 4] = b"SVXS";
+const SECRET_KEY_VERSION: u8 = 1;
+
+/// Marks a hybrid X25519 + ML-KEM-768 recipient secret key file
+const HYBRID_SECRET_KEY_MAGIC: &[u8; 4] = b"SVHS";
+const HYBRID_SECRET_KEY_VERSION: u8 = 1;
+
+/// Marks an Ed25519 signing key file
+const SIGNING_KEY_MAGIC: &[u8; 4] = b"SVES";
+const SIGNING_KEY_VERSION: u8 = 1;
+
+/// What a keyfile records about its key, readable without its passphrase
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInfo {
+    pub key_id: KeyId,
+    /// Creation time in seconds since the Unix epoch
+    pub created: Option<u64>,
+    pub purpose: Option<String>,
+    pub protected: bool,
+    /// Written in a format without metadata or checksum
+    pub legacy: bool,
+}
+
+/// The entry naming a keyring's current key, and its named keyfiles
+type KeyringEntries = (Option<String>, Vec<(String, SecretBytes)>);
+
+/// Metadata stored in current keyfiles
+struct Metadata {
+    created: u64,
+    key_id: KeyId,
+    purpose: String,
+}
+
+impl Metadata {
+    fn new(key: &SecretKey, purpose: &str) -> Result<Self> {
+        if purpose.len() > u8::MAX as usize {
+            return Err(SteganoError::InvalidKey(
+                format!("Key purpose is too long: at most {} bytes", u8::MAX)
+            ));
+        }
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        Ok(Self { created, key_id: key.id(), purpose: purpose.to_string() })
+    }
+
+    /// created | key ID | purpose length | purpose
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.created.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.key_id.to_bytes());
+        bytes.push(self.purpose.len() as u8);
+        bytes.extend_from_slice(self.purpose.as_bytes());
+        bytes
+    }
+
+    /// Parse metadata at the start of `data`, returning it and its length
+    fn from_bytes(data: &[u8]) -> Result<(Self, usize)> {
+        let truncated = || SteganoError::InvalidKey("Keyfile metadata is truncated".to_string());
+
+        let fixed = data.get(..METADATA_FIXED_LEN).ok_or_else(truncated)?;
+        let created = u64::from_le_bytes(fixed[..8].try_into().expect("slice is 8 bytes"));
+        let key_id = KeyId::from_bytes(fixed[8..16].try_into().expect("slice is 8 bytes"));
+        let len = METADATA_FIXED_LEN + fixed[16] as usize;
+        let purpose = String::from_utf8(data.get(METADATA_FIXED_LEN..len).ok_or_else(truncated)?.to_vec())
+            .map_err(|_| SteganoError::InvalidKey("Keyfile purpose is not valid UTF-8".to_string()))?;
+
+        Ok((Self { created, key_id, purpose }, len))
+    }
+
+    fn info(&self, protected: bool) -> KeyInfo {
+        KeyInfo {
+            key_id: self.key_id,
+            created: Some(self.created),
+            purpose: Some(self.purpose.clone()),
+            protected,
+            legacy: false,
+        }
+    }
+}
 
 pub struct KeyManager;
 
@@ -9,7 +133,7 @@ impl KeyManager {
     /// Load an existing key from a file or create a new one if it doesn't exist
     pub fn load_or_create(keyfile_path: &str) -> Result<CryptoManager> {
         let path = Path::new(keyfile_path);
-        
+
         if path.exists() {
             Self::load(keyfile_path)
         } else {
@@ -17,32 +141,744 @@ impl KeyManager {
         }
     }
 
-    /// Load an existing key from a file
+    /// Load an existing key from a file, prompting for the passphrase if the
+    /// key is wrapped
     pub fn load(keyfile_path: &str) -> Result<CryptoManager> {
-        let key_data = fs::read(keyfile_path)?;
-        
-        if key_data.len() != 32 {
-            return Err(SteganoError::InvalidKey(
-                format!("Invalid key size: expected 32 bytes, got {}", key_data.len())
-            ));
+        let key_data = SecretBytes::from(fs::read(keyfile_path)?);
+
+        let passphrase = || -> Result<SecretBytes> {
+            Ok(SecretBytes::from(rpassword::prompt_password(format!("Passphrase for {}: ", keyfile_path))?))
+        };
+        Self::read_keyfile(keyfile_path, &key_data, passphrase).map(|(key, _)| CryptoManager::with_key(key))
+    }
+
+    /// Load an existing key from a file, unwrapping it with `passphrase` if needed.
+    /// Unprotected keyfiles are accepted as they are.
+    pub fn load_with_passphrase(keyfile_path: &str, passphrase: &str) -> Result<CryptoManager> {
+        let key_data = SecretBytes::from(fs::read(keyfile_path)?);
+
+        let passphrase = || Ok(SecretBytes::from(passphrase.as_bytes()));
+        Self::read_keyfile(keyfile_path, &key_data, passphrase).map(|(key, _)| CryptoManager::with_key(key))
+    }
+
+    /// Whether the keyfile at `keyfile_path` is protected by a passphrase
+    pub fn is_protected(keyfile_path: &str) -> Result<bool> {
+        Ok(Self::is_wrapped(&fs::read(keyfile_path)?))
+    }
+
+    /// Read a keyfile's ID, creation time and purpose, checking it for
+    /// corruption. Protected keyfiles are not unlocked.
+    pub fn inspect(keyfile_path: &str) -> Result<KeyInfo> {
+        Self::inspect_data(keyfile_path, &SecretBytes::from(fs::read(keyfile_path)?))
+    }
+
+    /// Load a keyring: either a directory of `.key` files, whose `CURRENT`
+    /// file names the current key, or a keyring file. Prompts for the
+    /// passphrase of each protected key.
+    pub fn load_keyring(keyring_path: &str) -> Result<Keyring> {
+        Self::read_keyring(keyring_path, |name| {
+            Ok(SecretBytes::from(rpassword::prompt_password(format!("Passphrase for {}: ", name))?))
+        })
+    }
+
+    /// Load a keyring, unlocking every protected key with `passphrase`
+    pub fn load_keyring_with_passphrase(keyring_path: &str, passphrase: &str) -> Result<Keyring> {
+        Self::read_keyring(keyring_path, |_| Ok(SecretBytes::from(passphrase.as_bytes())))
+    }
+
+    /// Add a keyfile to a keyring and make it the current key. An existing
+    /// directory gets a copy of the keyfile; any other path is a keyring file,
+    /// created if needed.
+    pub fn add_to_keyring(keyring_path: &str, keyfile_path: &str) -> Result<KeyId> {
+        let key_data = SecretBytes::from(fs::read(keyfile_path)?);
+        let key_id = Self::inspect_data(keyfile_path, &key_data)?.key_id;
+
+        let keyring = Path::new(keyring_path);
+        if keyring.is_dir() {
+            let file_name = Path::new(keyfile_path).file_name().ok_or_else(|| SteganoError::InvalidKey(
+                format!("{} is not a file", keyfile_path)
+            ))?;
+            let mut target = keyring.join(file_name);
+            target.set_extension(KEYRING_KEY_EXTENSION);
+            if target.exists() && fs::read(&target)? != key_data.expose() {
+                return Err(SteganoError::InvalidKey(
+                    format!("{} already holds a different key", target.display())
+                ));
+            }
+            Self::write_private(&target, key_data.expose())?;
+            Self::write_private(keyring.join(KEYRING_CURRENT_FILE), format!("{}\n", key_id).as_bytes())?;
+            return Ok(key_id);
         }
-        
-        let mut key = [0u8; 32];
-        key.copy_from_slice(&key_data);
-        
-        Ok(CryptoManager::with_key(key))
+
+        let mut entries = if keyring.exists() {
+            Self::parse_keyring_file(keyring_path, &SecretBytes::from(fs::read(keyring_path)?))?.1
+        } else {
+            Vec::new()
+        };
+        entries.retain(|(name, data)| {
+            Self::inspect_data(name, data).map(|info| info.key_id).ok() != Some(key_id)
+        });
+        entries.push((keyfile_path.to_string(), key_data));
+
+        if entries.len() > u16::MAX as usize {
+            return Err(SteganoError::InvalidKey(format!("A keyring holds at most {} keys", u16::MAX)));
+        }
+        let mut data = KEYRING_MAGIC.to_vec();
+        data.push(KEYRING_VERSION);
+        data.extend_from_slice(&key_id.to_bytes());
+        data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (_, entry) in &entries {
+            data.extend_from_slice(&(entry.len() as u16).to_le_bytes());
+            data.extend_from_slice(entry);
+        }
+        data.extend_from_slice(&Self::checksum(&data));
+        Self::write_private(keyring_path, SecretBytes::from(data).expose())?;
+        Ok(key_id)
     }
 
     /// Create a new key and save it to a file
     pub fn create(keyfile_path: &str) -> Result<CryptoManager> {
+        Self::create_with_purpose(keyfile_path, DEFAULT_PURPOSE, None)
+    }
+
+    /// Create a new key and save it to a file, wrapped under `passphrase`
+    pub fn create_with_passphrase(keyfile_path: &str, passphrase: &str) -> Result<CryptoManager> {
+        Self::create_with_purpose(keyfile_path, DEFAULT_PURPOSE, Some(passphrase))
+    }
+
+    /// Create a new key recording what it is for, wrapped under `passphrase`
+    /// if one is given
+    pub fn create_with_purpose(
+        keyfile_path: &str,
+        purpose: &str,
+        passphrase: Option<&str>,
+    ) -> Result<CryptoManager> {
         let crypto_manager = CryptoManager::new();
-        fs::write(keyfile_path, &crypto_manager.get_key())?;
+        let key = Self::keyfile_key(&crypto_manager)?;
+        Self::write_keyfile(keyfile_path, key, &Metadata::new(key, purpose)?, passphrase)?;
         Ok(crypto_manager)
     }
 
     /// Save a key to a file
     pub fn save(crypto_manager: &CryptoManager, keyfile_path: &str) -> Result<()> {
-        fs::write(keyfile_path, &crypto_manager.get_key())?;
+        let key = Self::keyfile_key(crypto_manager)?;
+        Self::write_keyfile(keyfile_path, key, &Metadata::new(key, DEFAULT_PURPOSE)?, None)
+    }
+
+    /// Save a key to a file, wrapped under a key derived from `passphrase`
+    pub fn save_with_passphrase(
+        crypto_manager: &CryptoManager,
+        keyfile_path: &str,
+        passphrase: &str,
+    ) -> Result<()> {
+        let key = Self::keyfile_key(crypto_manager)?;
+        Self::write_keyfile(keyfile_path, key, &Metadata::new(key, DEFAULT_PURPOSE)?, Some(passphrase))
+    }
+
+    /// Wrap an unprotected keyfile under `passphrase` in place, keeping its
+    /// creation time and purpose
+    pub fn protect(keyfile_path: &str, passphrase: &str) -> Result<CryptoManager> {
+        let key_data = SecretBytes::from(fs::read(keyfile_path)?);
+        if Self::is_wrapped(&key_data) {
+            return Err(SteganoError::InvalidKey(format!("{} is already protected", keyfile_path)));
+        }
+
+        let (key, info) = Self::read_keyfile(keyfile_path, &key_data, || unreachable!("keyfile is not protected"))?;
+        let mut metadata = Metadata::new(&key, info.purpose.as_deref().unwrap_or(DEFAULT_PURPOSE))?;
+        metadata.created = info.created.unwrap_or(metadata.created);
+
+        Self::write_keyfile(keyfile_path, &key, &metadata, Some(passphrase))?;
+        Ok(CryptoManager::with_key(key))
+    }
+
+    /// Generate an X25519 recipient keypair, saving the secret key to
+    /// `secret_path` and the exported public key to `public_path`
+    pub fn generate_recipient_key(secret_path: &str, public_path: &str) -> Result<RecipientSecretKey> {
+        let secret_key = RecipientSecretKey::generate();
+        Self::save_recipient_key(&secret_key, secret_path)?;
+        Self::save_public_key(&secret_key.public_key(), public_path)?;
+        Ok(secret_key)
+    }
+
+    /// Save an X25519 secret key to a file
+    pub fn save_recipient_key(secret_key: &RecipientSecretKey, secret_path: &str) -> Result<()> {
+        Self::write_secret(secret_path, SECRET_KEY_MAGIC, SECRET_KEY_VERSION, secret_key.to_bytes().expose())
+    }
+
+    /// Load an X25519 secret key from a file
+    pub fn load_recipient_key(secret_path: &str) -> Result<RecipientSecretKey> {
+        let data = SecretBytes::from(fs::read(secret_path)?);
+
+        if !data.starts_with(SECRET_KEY_MAGIC) || data.len() != SECRET_KEY_MAGIC.len() + 1 + 32 {
+            return Err(SteganoError::InvalidKey(
+                format!("{} is not a recipient secret key file", secret_path)
+            ));
+        }
+        if data[4] != SECRET_KEY_VERSION {
+            return Err(SteganoError::InvalidKey(
+                format!("Unsupported secret key file version {}", data[4])
+            ));
+        }
+
+        let secret_key = SecretKey::from_slice(&data[5..]).expect("length checked above");
+        Ok(RecipientSecretKey::from_bytes(&secret_key))
+    }
+
+    /// Generate a hybrid X25519 + ML-KEM-768 recipient keypair, saving the
+    /// secret key to `secret_path` and the exported public key to `public_path`
+    pub fn generate_hybrid_recipient_key(secret_path: &str, public_path: &str) -> Result<HybridSecretKey> {
+        let secret_key = HybridSecretKey::generate();
+        Self::save_hybrid_recipient_key(&secret_key, secret_path)?;
+        fs::write(public_path, format!("{}\n", secret_key.public_key()))?;
+        Ok(secret_key)
+    }
+
+    /// Save a hybrid secret key to a file
+    pub fn save_hybrid_recipient_key(secret_key: &HybridSecretKey, secret_path: &str) -> Result<()> {
+        Self::write_secret(secret_path, HYBRID_SECRET_KEY_MAGIC, HYBRID_SECRET_KEY_VERSION, &secret_key.to_bytes())
+    }
+
+    /// Load a hybrid secret key from a file
+    pub fn load_hybrid_recipient_key(secret_path: &str) -> Result<HybridSecretKey> {
+        let data = SecretBytes::from(fs::read(secret_path)?);
+
+        if !data.starts_with(HYBRID_SECRET_KEY_MAGIC)
+            || data.len() != HYBRID_SECRET_KEY_MAGIC.len() + 1 + hybrid::SECRET_KEY_LEN
+        {
+            return Err(SteganoError::InvalidKey(
+                format!("{} is not a hybrid recipient secret key file", secret_path)
+            ));
+        }
+        if data[4] != HYBRID_SECRET_KEY_VERSION {
+            return Err(SteganoError::InvalidKey(
+                format!("Unsupported hybrid secret key file version {}", data[4])
+            ));
+        }
+        HybridSecretKey::from_bytes(&data[5..])
+    }
+
+    /// A manager that decrypts with the X25519 or hybrid secret key in
+    /// `secret_path`, whichever kind the file holds
+    pub fn open_secret_key(secret_path: &str) -> Result<CryptoManager> {
+        let mut magic = [0u8; 4];
+        fs::File::open(secret_path)?.read_exact(&mut magic).map_err(|_| SteganoError::InvalidKey(
+            format!("{} is not a recipient secret key file", secret_path)
+        ))?;
+
+        if &magic == HYBRID_SECRET_KEY_MAGIC {
+            Ok(CryptoManager::with_hybrid_secret_key(Self::load_hybrid_recipient_key(secret_path)?))
+        } else {
+            Ok(CryptoManager::with_secret_key(Self::load_recipient_key(secret_path)?))
+        }
+    }
+
+    /// Read an X25519 (`svx25519:...`) or hybrid (`svhybrid:...`) public key
+    /// given as text or as the path of a file holding it
+    pub fn load_recipient(key_or_path: &str) -> Result<Recipient> {
+        let parse = |text: &str| -> Result<Recipient> {
+            if text.trim().starts_with("svhybrid:") {
+                Ok(Recipient::Hybrid(HybridPublicKey::parse(text)?))
+            } else {
+                Ok(Recipient::PublicKey(RecipientPublicKey::parse(text)?))
+            }
+        };
+        if key_or_path.starts_with("svhybrid:") || key_or_path.starts_with("svx25519:") {
+            return parse(key_or_path);
+        }
+        parse(&fs::read_to_string(key_or_path)?)
+    }
+
+    /// Write a public key in its shareable text form
+    pub fn save_public_key(public_key: &RecipientPublicKey, public_path: &str) -> Result<()> {
+        fs::write(public_path, format!("{}\n", public_key))?;
+        Ok(())
+    }
+
+    /// Read a public key given either as text (`svx25519:...`) or as the path
+    /// of a file holding it
+    pub fn load_public_key(key_or_path: &str) -> Result<RecipientPublicKey> {
+        if let Ok(public_key) = RecipientPublicKey::parse(key_or_path) {
+            return Ok(public_key);
+        }
+        RecipientPublicKey::parse(&fs::read_to_string(key_or_path)?)
+    }
+
+    /// Generate an Ed25519 signing keypair, saving the signing key to
+    /// `secret_path` and the exported verification key to `public_path`
+    pub fn generate_signing_key(secret_path: &str, public_path: &str) -> Result<SignerKeypair> {
+        let signer = SignerKeypair::generate();
+        Self::save_signing_key(&signer, secret_path)?;
+        fs::write(public_path, format!("{}\n", signer.public_key()))?;
+        Ok(signer)
+    }
+
+    /// Save an Ed25519 signing key to a file
+    pub fn save_signing_key(signer: &SignerKeypair, secret_path: &str) -> Result<()> {
+        Self::write_secret(secret_path, SIGNING_KEY_MAGIC, SIGNING_KEY_VERSION, signer.to_bytes().expose())
+    }
+
+    /// Load an Ed25519 signing key from a file
+    pub fn load_signing_key(secret_path: &str) -> Result<SignerKeypair> {
+        let data = SecretBytes::from(fs::read(secret_path)?);
+
+        if !data.starts_with(SIGNING_KEY_MAGIC) || data.len() != SIGNING_KEY_MAGIC.len() + 1 + 32 {
+            return Err(SteganoError::InvalidKey(
+                format!("{} is not a signing key file", secret_path)
+            ));
+        }
+        if data[4] != SIGNING_KEY_VERSION {
+            return Err(SteganoError::InvalidKey(
+                format!("Unsupported signing key file version {}", data[4])
+            ));
+        }
+
+        let seed = SecretKey::from_slice(&data[5..]).expect("length checked above");
+        Ok(SignerKeypair::from_bytes(&seed))
+    }
+
+    /// Read a verification key given either as text (`sved25519:...`) or as
+    /// the path of a file holding it
+    pub fn load_signer_public_key(key_or_path: &str) -> Result<SignerPublicKey> {
+        if let Ok(public_key) = SignerPublicKey::parse(key_or_path) {
+            return Ok(public_key);
+        }
+        SignerPublicKey::parse(&fs::read_to_string(key_or_path)?)
+    }
+
+    fn keyfile_key(crypto_manager: &CryptoManager) -> Result<&SecretKey> {
+        crypto_manager.key().ok_or_else(|| SteganoError::InvalidKey(
+            "Only a keyfile-based key can be saved to a keyfile".to_string()
+        ))
+    }
+
+    /// Write magic, version and a secret key, built in a buffer that is wiped
+    fn write_secret(path: &str, magic: &[u8; 4], version: u8, key: &[u8]) -> Result<()> {
+        let mut data = Vec::with_capacity(magic.len() + 1 + key.len());
+        data.extend_from_slice(magic);
+        data.push(version);
+        data.extend_from_slice(key);
+        Self::write_private(path, SecretBytes::from(data).expose())
+    }
+
+    /// Write a file that only its owner can read, through a temporary file
+    /// renamed into place so an interrupted write never leaves it truncated
+    fn write_private(path: impl AsRef<Path>, data: &[u8]) -> Result<()> {
+        let path = path.as_ref();
+        let file_name = path.file_name().ok_or_else(|| SteganoError::InvalidKey(
+            format!("{} is not a file", path.display())
+        ))?;
+        let temp_path = path.with_file_name(
+            format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id())
+        );
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let written = options.open(&temp_path).and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        });
+        if let Err(e) = written.and_then(|_| fs::rename(&temp_path, path)) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
         Ok(())
     }
+
+    fn is_wrapped(key_data: &[u8]) -> bool {
+        key_data.starts_with(KEYFILE_MAGIC)
+    }
+
+    /// Read what a keyfile records without unlocking it
+    fn inspect_data(keyfile_path: &str, key_data: &[u8]) -> Result<KeyInfo> {
+        if Self::is_wrapped(key_data) {
+            let (metadata, _, _) = Self::parse_wrapped(keyfile_path, key_data)?;
+            return Ok(metadata.info(true));
+        }
+
+        Self::read_keyfile(keyfile_path, key_data, || unreachable!("keyfile is not protected")).map(|(_, info)| info)
+    }
+
+    fn read_keyring(
+        keyring_path: &str,
+        mut passphrase: impl FnMut(&str) -> Result<SecretBytes>,
+    ) -> Result<Keyring> {
+        let (current, entries) = if Path::new(keyring_path).is_dir() {
+            Self::read_keyring_dir(keyring_path)?
+        } else {
+            Self::parse_keyring_file(keyring_path, &SecretBytes::from(fs::read(keyring_path)?))?
+        };
+
+        let mut keyring = Keyring::new();
+        let mut current_id = None;
+        for (name, data) in &entries {
+            let (key, info) = Self::read_keyfile(name, data, || passphrase(name))?;
+            if current.as_deref() == Some(name.as_str()) {
+                current_id = Some(key.id());
+            }
+            keyring = keyring.with_key(key, info);
+        }
+
+        // CURRENT holds a key ID, or the name of one of the keyfiles
+        match current {
+            Some(current) => {
+                let id = match current_id {
+                    Some(id) => id,
+                    None => KeyId::parse(&current)?,
+                };
+                keyring.with_current(id)
+            }
+            None => Ok(keyring),
+        }
+    }
+
+    /// Keyfiles of a keyring directory in name order, and its `CURRENT` entry
+    fn read_keyring_dir(keyring_path: &str) -> Result<KeyringEntries> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(keyring_path)? {
+            let path = entry?.path();
+            if path.is_file() && path.extension().is_some_and(|ext| ext == KEYRING_KEY_EXTENSION) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut entries = Vec::new();
+        for path in paths {
+            entries.push((path.display().to_string(), SecretBytes::from(fs::read(&path)?)));
+        }
+
+        let current_path = Path::new(keyring_path).join(KEYRING_CURRENT_FILE);
+        let current = match fs::read_to_string(&current_path) {
+            Ok(current) => {
+                let current = current.trim();
+                // A file name is resolved to the path it is listed under
+                let named = Path::new(keyring_path).join(current);
+                Some(if named.is_file() { named.display().to_string() } else { current.to_string() })
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        Ok((current, entries))
+    }
+
+    /// magic | version | current key ID | count | (length | keyfile)* | checksum.
+    /// Entries are named after their position for error messages.
+    fn parse_keyring_file(keyring_path: &str, data: &[u8]) -> Result<KeyringEntries> {
+        if !data.starts_with(KEYRING_MAGIC) {
+            return Err(SteganoError::InvalidKey(format!("{} is not a keyring", keyring_path)));
+        }
+        let body = Self::verify_checksum(keyring_path, data)?;
+        if body[4] != KEYRING_VERSION {
+            return Err(SteganoError::InvalidKey(format!("Unsupported keyring version {}", body[4])));
+        }
+        let truncated = || SteganoError::InvalidKey(format!("{} is truncated", keyring_path));
+
+        let header = body.get(..KEYRING_HEADER_LEN).ok_or_else(truncated)?;
+        let current = KeyId::from_bytes(header[5..13].try_into().expect("slice is 8 bytes"));
+        let count = u16::from_le_bytes([header[13], header[14]]);
+
+        let mut entries = Vec::new();
+        let mut pos = KEYRING_HEADER_LEN;
+        for index in 0..count {
+            let len_bytes = body.get(pos..pos + 2).ok_or_else(truncated)?;
+            let len = u16::from_le_bytes([len_bytes[0], len_bytes[1]]) as usize;
+            let entry = body.get(pos + 2..pos + 2 + len).ok_or_else(truncated)?;
+            entries.push((format!("{} key {}", keyring_path, index + 1), SecretBytes::from(entry)));
+            pos += 2 + len;
+        }
+        Ok((Some(current.to_string()), entries))
+    }
+
+    /// Parse any keyfile format, asking for a passphrase only if the key is
+    /// wrapped
+    fn read_keyfile(
+        keyfile_path: &str,
+        key_data: &[u8],
+        passphrase: impl FnOnce() -> Result<SecretBytes>,
+    ) -> Result<(SecretKey, KeyInfo)> {
+        if Self::is_wrapped(key_data) {
+            return Self::unwrap_key(keyfile_path, key_data, &passphrase()?);
+        }
+
+        if !key_data.starts_with(KEY_MAGIC) {
+            // Bare key from before keyfiles had a header
+            Self::check_size(key_data, 32)?;
+            let key = SecretKey::from_slice(key_data).expect("length checked above");
+            let info = KeyInfo { key_id: key.id(), created: None, purpose: None, protected: false, legacy: true };
+            return Ok((key, info));
+        }
+
+        let body = Self::verify_checksum(keyfile_path, key_data)?;
+        if body[4] != KEY_VERSION {
+            return Err(SteganoError::InvalidKey(format!("Unsupported keyfile version {}", body[4])));
+        }
+        let (metadata, len) = Metadata::from_bytes(&body[5..])?;
+        let key = SecretKey::from_slice(&body[5 + len..]).ok_or_else(|| SteganoError::InvalidKey(
+            format!("{} holds a key of the wrong size", keyfile_path)
+        ))?;
+
+        Self::check_key_id(keyfile_path, &key, &metadata)?;
+        Ok((key, metadata.info(false)))
+    }
+
+    fn check_size(key_data: &[u8], expected: usize) -> Result<()> {
+        if key_data.len() != expected {
+            return Err(SteganoError::InvalidKey(format!(
+                "Invalid keyfile size: expected {} bytes, got {}; the file may be truncated or not a keyfile",
+                expected,
+                key_data.len()
+            )));
+        }
+        Ok(())
+    }
+
+    fn checksum(data: &[u8]) -> [u8; CHECKSUM_LEN] {
+        let digest = Sha256::digest(data);
+        let mut checksum = [0u8; CHECKSUM_LEN];
+        checksum.copy_from_slice(&digest[..CHECKSUM_LEN]);
+        checksum
+    }
+
+    /// Check the trailing checksum, returning the keyfile without it
+    fn verify_checksum<'a>(keyfile_path: &str, key_data: &'a [u8]) -> Result<&'a [u8]> {
+        let corrupted = || SteganoError::InvalidKey(
+            format!("{} is corrupted or truncated: checksum mismatch", keyfile_path)
+        );
+
+        if key_data.len() < 5 + CHECKSUM_LEN {
+            return Err(corrupted());
+        }
+        let (body, checksum) = key_data.split_at(key_data.len() - CHECKSUM_LEN);
+        if Self::checksum(body) != checksum {
+            return Err(corrupted());
+        }
+        Ok(body)
+    }
+
+    fn check_key_id(keyfile_path: &str, key: &SecretKey, metadata: &Metadata) -> Result<()> {
+        if key.id() != metadata.key_id {
+            return Err(SteganoError::InvalidKey(format!(
+                "{} records key ID {} but holds key {}",
+                keyfile_path,
+                metadata.key_id,
+                key.id()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Write `key` with its metadata, wrapped under `passphrase` if one is given
+    fn write_keyfile(
+        keyfile_path: &str,
+        key: &SecretKey,
+        metadata: &Metadata,
+        passphrase: Option<&str>,
+    ) -> Result<()> {
+        let mut data = match passphrase {
+            Some(passphrase) => Self::wrap_key(key, metadata, passphrase.as_bytes(), &KdfParams::default())?,
+            None => {
+                let mut data = KEY_MAGIC.to_vec();
+                data.push(KEY_VERSION);
+                data.extend_from_slice(&metadata.to_bytes());
+                data.extend_from_slice(key.expose());
+                data
+            }
+        };
+        data.extend_from_slice(&Self::checksum(&data));
+        Self::write_private(keyfile_path, SecretBytes::from(data).expose())
+    }
+
+    /// Encrypt the key with AES-256-GCM under an Argon2id key. The header and
+    /// metadata are authenticated as associated data.
+    fn wrap_key(key: &SecretKey, metadata: &Metadata, passphrase: &[u8], params: &KdfParams) -> Result<Vec<u8>> {
+        if passphrase.is_empty() {
+            return Err(SteganoError::InvalidKey("Passphrase must not be empty".to_string()));
+        }
+
+        let mut salt = [0u8; SALT_LEN];
+        rng().fill_bytes(&mut salt);
+        let wrapping_key = kdf::derive_key(passphrase, &salt, params)?;
+
+        let mut result = KEYFILE_MAGIC.to_vec();
+        result.push(KEYFILE_VERSION);
+        result.extend_from_slice(&params.to_bytes());
+        result.extend_from_slice(&salt);
+        result.extend_from_slice(&metadata.to_bytes());
+
+        let mut nonce_bytes = [0u8; 12];
+        rng().fill_bytes(&mut nonce_bytes);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(wrapping_key.expose()));
+        let wrapped = cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: key.expose(), aad: &result })
+            .map_err(|e| SteganoError::EncryptionError(format!("Key wrapping failed: {}", e)))?;
+
+        result.extend_from_slice(&nonce_bytes);
+        result.extend_from_slice(&wrapped);
+        Ok(result)
+    }
+
+    /// Split a current protected keyfile into its metadata, its authenticated
+    /// header and the nonce with the wrapped key
+    fn parse_wrapped<'a>(keyfile_path: &str, key_data: &'a [u8]) -> Result<(Metadata, &'a [u8], &'a [u8])> {
+        let body = Self::verify_checksum(keyfile_path, key_data)?;
+        if body[4] != KEYFILE_VERSION {
+            return Err(SteganoError::InvalidKey(format!("Unsupported keyfile version {}", body[4])));
+        }
+
+        let metadata_bytes = body.get(KEYFILE_HEADER_LEN..).ok_or_else(|| SteganoError::InvalidKey(
+            format!("{} is truncated", keyfile_path)
+        ))?;
+        let (metadata, len) = Metadata::from_bytes(metadata_bytes)?;
+        let (header, wrapped) = body.split_at(KEYFILE_HEADER_LEN + len);
+        if wrapped.len() != 12 + 32 + 16 {
+            return Err(SteganoError::InvalidKey(format!("{} holds a key of the wrong size", keyfile_path)));
+        }
+        Ok((metadata, header, wrapped))
+    }
+
+    fn unwrap_key(keyfile_path: &str, key_data: &[u8], passphrase: &[u8]) -> Result<(SecretKey, KeyInfo)> {
+        let (metadata, header, body) = Self::parse_wrapped(keyfile_path, key_data)?;
+
+        let params = KdfParams::from_bytes(&header[5..5 + KDF_PARAMS_LEN])
+            .map_err(|e| SteganoError::InvalidKey(e.to_string()))?;
+        let salt = &header[5 + KDF_PARAMS_LEN..KEYFILE_HEADER_LEN];
+        let wrapping_key = kdf::derive_key(passphrase, salt, &params)?;
+
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(wrapping_key.expose()));
+        let key = cipher
+            .decrypt(Nonce::from_slice(&body[..12]), Payload { msg: &body[12..], aad: header })
+            .map(SecretBytes::from)
+            // The checksum already rules out corruption
+            .map_err(|_| SteganoError::InvalidKey("Could not unlock keyfile: wrong passphrase".to_string()))?;
+
+        let key = SecretKey::from_slice(&key)
+            .ok_or_else(|| SteganoError::InvalidKey("Wrapped key has the wrong size".to_string()))?;
+        Self::check_key_id(keyfile_path, &key, &metadata)?;
+        Ok((key, metadata.info(true)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{path_str, temp_dir};
+
+    #[test]
+    fn protected_keyfile_opens_only_with_its_passphrase() {
+        let dir = temp_dir("protected");
+        let path = dir.join("my.key");
+        let created = KeyManager::create_with_passphrase(path_str(&path), "correct horse").unwrap();
+
+        assert!(KeyManager::is_protected(path_str(&path)).unwrap());
+        let loaded = KeyManager::load_with_passphrase(path_str(&path), "correct horse").unwrap();
+        assert_eq!(loaded.key_id(), created.key_id());
+        assert!(KeyManager::load_with_passphrase(path_str(&path), "wrong horse").is_err());
+    }
+
+    #[test]
+    fn protecting_a_keyfile_keeps_its_key_and_metadata() {
+        let dir = temp_dir("protect");
+        let path = dir.join("my.key");
+        let created = KeyManager::create_with_purpose(path_str(&path), "archive", None).unwrap();
+        let before = KeyManager::inspect(path_str(&path)).unwrap();
+
+        KeyManager::protect(path_str(&path), "correct horse").unwrap();
+        let after = KeyManager::inspect(path_str(&path)).unwrap();
+        assert!(after.protected);
+        assert_eq!((after.key_id, after.created, after.purpose), (before.key_id, before.created, before.purpose));
+
+        let ciphertext = created.encrypt(b"secret").unwrap();
+        let loaded = KeyManager::load_with_passphrase(path_str(&path), "correct horse").unwrap();
+        assert_eq!(loaded.decrypt(&ciphertext).unwrap().expose(), b"secret");
+        assert!(KeyManager::protect(path_str(&path), "again").is_err());
+    }
+
+    #[test]
+    fn keyfile_records_its_key_id_and_purpose() {
+        let dir = temp_dir("metadata");
+        let path = dir.join("my.key");
+        let created = KeyManager::create_with_purpose(path_str(&path), "team archive", None).unwrap();
+
+        let info = KeyManager::inspect(path_str(&path)).unwrap();
+        assert_eq!(Some(info.key_id), created.key_id());
+        assert_eq!(info.purpose.as_deref(), Some("team archive"));
+        assert!(info.created.is_some() && !info.protected && !info.legacy);
+        assert_eq!(KeyManager::load(path_str(&path)).unwrap().key_id(), created.key_id());
+    }
+
+    #[test]
+    fn damaged_or_truncated_keyfiles_are_refused() {
+        let dir = temp_dir("damaged");
+        let path = dir.join("my.key");
+        KeyManager::create(path_str(&path)).unwrap();
+        let data = fs::read(&path).unwrap();
+
+        let mut damaged = data.clone();
+        damaged[10] ^= 1;
+        fs::write(&path, &damaged).unwrap();
+        assert!(KeyManager::load(path_str(&path)).is_err());
+
+        fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert!(KeyManager::load(path_str(&path)).is_err());
+    }
+
+    #[test]
+    fn bare_keyfiles_still_load() {
+        let dir = temp_dir("bare");
+        let path = dir.join("old.key");
+        let key = SecretKey::generate();
+        fs::write(&path, key.expose()).unwrap();
+
+        let info = KeyManager::inspect(path_str(&path)).unwrap();
+        assert!(info.legacy);
+        assert_eq!(info.key_id, key.id());
+        assert_eq!(KeyManager::load(path_str(&path)).unwrap().key_id(), Some(key.id()));
+    }
+
+    #[test]
+    fn keyring_encrypts_under_the_current_key_and_opens_older_payloads() {
+        for as_directory in [true, false] {
+            let dir = temp_dir(if as_directory { "keyring-dir" } else { "keyring-file" });
+            let keyring_path = match as_directory {
+                true => {
+                    let path = dir.join("keys");
+                    fs::create_dir(&path).unwrap();
+                    path
+                }
+                false => dir.join("keys.svkr"),
+            };
+            let (old_path, new_path) = (dir.join("old.key"), dir.join("new.key"));
+            let old = KeyManager::create(path_str(&old_path)).unwrap();
+            let new = KeyManager::create(path_str(&new_path)).unwrap();
+            KeyManager::add_to_keyring(path_str(&keyring_path), path_str(&old_path)).unwrap();
+            let current = KeyManager::add_to_keyring(path_str(&keyring_path), path_str(&new_path)).unwrap();
+            assert_eq!(Some(current), new.key_id());
+
+            let keyring = KeyManager::load_keyring_with_passphrase(path_str(&keyring_path), "").unwrap();
+            assert_eq!(keyring.len(), 2);
+            let manager = CryptoManager::with_keyring(&keyring).unwrap();
+            assert_eq!(manager.key_id(), new.key_id());
+
+            let old_payload = old.encrypt(b"before rotation").unwrap();
+            assert_eq!(manager.decrypt(&old_payload).unwrap().expose(), b"before rotation");
+            assert_eq!(new.decrypt(&manager.encrypt(b"after").unwrap()).unwrap().expose(), b"after");
+        }
+    }
+
+    #[test]
+    fn keyring_directory_current_file_may_name_a_keyfile() {
+        let dir = temp_dir("keyring-current");
+        let first = KeyManager::create(path_str(&dir.join("first.key"))).unwrap();
+        KeyManager::create(path_str(&dir.join("second.key"))).unwrap();
+        fs::write(dir.join(KEYRING_CURRENT_FILE), "first.key\n").unwrap();
+
+        let keyring = KeyManager::load_keyring_with_passphrase(path_str(&dir), "").unwrap();
+        assert_eq!(keyring.current().map(SecretKey::id), first.key_id());
+
+        fs::write(dir.join(KEYRING_CURRENT_FILE), "missing.key\n").unwrap();
+        assert!(KeyManager::load_keyring_with_passphrase(path_str(&dir), "").is_err());
+    }
 }
@@ -0,0 +1,103 @@
+use crate::error::{Result, SteganoError};
+use super::key_manager::KeyInfo;
+use super::secret::{KeyId, SecretKey};
+
+/// A set of symmetric keys with one designated current key. New payloads are
+/// encrypted under the current key; extraction picks whichever key a payload
+/// names, so carriers made before a rotation stay readable.
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    keys: Vec<(SecretKey, KeyInfo)>,
+    current: Option<KeyId>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a key, replacing any key with the same ID
+    pub fn with_key(mut self, key: SecretKey, info: KeyInfo) -> Self {
+        let id = key.id();
+        self.keys.retain(|(existing, _)| existing.id() != id);
+        self.keys.push((key, KeyInfo { key_id: id, ..info }));
+        self
+    }
+
+    /// Designate the key that new payloads are encrypted under
+    pub fn with_current(mut self, id: KeyId) -> Result<Self> {
+        if self.get(id).is_none() {
+            return Err(SteganoError::InvalidKey(format!("Key {} is not in the keyring", id)));
+        }
+        self.current = Some(id);
+        Ok(self)
+    }
+
+    /// The designated key or, failing that, the most recently created one
+    pub fn current(&self) -> Option<&SecretKey> {
+        match self.current {
+            Some(id) => self.get(id),
+            None => self
+                .keys
+                .iter()
+                .filter(|(_, info)| info.created.is_some())
+                .max_by_key(|(_, info)| info.created)
+                .or(self.keys.first().filter(|_| self.keys.len() == 1))
+                .map(|(key, _)| key),
+        }
+    }
+
+    pub fn get(&self, id: KeyId) -> Option<&SecretKey> {
+        self.keys.iter().map(|(key, _)| key).find(|key| key.id() == id)
+    }
+
+    /// Every key with what its keyfile records about it
+    pub fn keys(&self) -> impl Iterator<Item = (&SecretKey, &KeyInfo)> {
+        self.keys.iter().map(|(key, info)| (key, info))
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(created: Option<u64>) -> KeyInfo {
+        let key_id = SecretKey::generate().id();
+        KeyInfo { key_id, created, purpose: None, protected: false, legacy: created.is_none() }
+    }
+
+    #[test]
+    fn newest_key_is_current_unless_one_is_designated() {
+        let (old, new) = (SecretKey::generate(), SecretKey::generate());
+        let keyring = Keyring::new().with_key(new.clone(), info(Some(200))).with_key(old.clone(), info(Some(100)));
+        assert_eq!(keyring.current(), Some(&new));
+
+        let keyring = keyring.with_current(old.id()).unwrap();
+        assert_eq!(keyring.current(), Some(&old));
+        assert!(keyring.with_current(SecretKey::generate().id()).is_err());
+    }
+
+    #[test]
+    fn undated_keys_leave_no_current_key_to_guess() {
+        let keyring = Keyring::new().with_key(SecretKey::generate(), info(None));
+        assert!(keyring.current().is_some());
+        let keyring = keyring.with_key(SecretKey::generate(), info(None));
+        assert!(keyring.current().is_none());
+    }
+
+    #[test]
+    fn adding_a_key_again_replaces_it() {
+        let key = SecretKey::generate();
+        let keyring = Keyring::new().with_key(key.clone(), info(Some(1))).with_key(key.clone(), info(Some(2)));
+        assert_eq!(keyring.len(), 1);
+        assert_eq!(keyring.keys().next().unwrap().1.key_id, key.id());
+    }
+}
@@ -0,0 +1,108 @@
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::fmt;
+use super::secret::{KeyId, SecretKey};
+
+/// A root key that is never used directly. Every purpose gets its own subkey,
+/// derived with HKDF-SHA256 under a distinct label, so a key that leaks from
+/// one use (a carrier layout, say) reveals nothing about the others.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MasterKey {
+    root: SecretKey,
+}
+
+impl MasterKey {
+    pub fn new(root: SecretKey) -> Self {
+        Self { root }
+    }
+
+    pub fn generate() -> Self {
+        Self::new(SecretKey::generate())
+    }
+
+    /// Public identifier of the root key, as recorded in keyfiles and payloads
+    pub fn id(&self) -> KeyId {
+        self.root.id()
+    }
+
+    /// Key that payloads are encrypted under
+    pub fn encryption_key(&self) -> SecretKey {
+        self.derive(b"stegano-vault encryption")
+    }
+
+    /// Key that wraps content keys in recipient slots
+    pub fn wrapping_key(&self) -> SecretKey {
+        self.derive(b"stegano-vault key slot")
+    }
+
+    /// Key that orders the bits of a payload within a carrier
+    pub fn permutation_key(&self) -> SecretKey {
+        self.derive(b"stegano-vault slot permutation")
+    }
+
+    /// Key for the keystream masking data and headers hidden in a carrier
+    pub fn header_key(&self) -> SecretKey {
+        self.derive(b"stegano-vault header mask")
+    }
+
+    /// Key for the MACs and check values that find hidden data
+    pub fn mac_key(&self) -> SecretKey {
+        self.derive(b"stegano-vault mac")
+    }
+
+    /// The root key itself, for keyfiles and for SteganoVault 0.2 payloads,
+    /// which used it directly
+    pub(crate) fn root_key(&self) -> &SecretKey {
+        &self.root
+    }
+
+    fn derive(&self, label: &[u8]) -> SecretKey {
+        let mut key = SecretKey::new(&mut [0u8; 32]);
+        Hkdf::<Sha256>::new(None, self.root.expose())
+            .expand(label, key.expose_mut())
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        key
+    }
+}
+
+impl From<SecretKey> for MasterKey {
+    fn from(root: SecretKey) -> Self {
+        Self::new(root)
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MasterKey({}, [REDACTED])", self.id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_purpose_gets_a_distinct_subkey() {
+        let key = MasterKey::generate();
+        let subkeys = [
+            key.encryption_key(),
+            key.wrapping_key(),
+            key.permutation_key(),
+            key.header_key(),
+            key.mac_key(),
+            key.root_key().clone(),
+        ];
+        for (i, first) in subkeys.iter().enumerate() {
+            for second in &subkeys[i + 1..] {
+                assert_ne!(first, second);
+            }
+        }
+    }
+
+    #[test]
+    fn subkeys_depend_only_on_the_root_key() {
+        let root = SecretKey::generate();
+        assert_eq!(MasterKey::new(root.clone()).encryption_key(), MasterKey::new(root).encryption_key());
+        assert_ne!(MasterKey::generate().encryption_key(), MasterKey::generate().encryption_key());
+    }
+}
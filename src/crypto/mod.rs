@@ -1,5 +1,28 @@
+pub mod cipher;
+pub mod container;
+pub mod context;
 pub mod encryption;
+pub mod hybrid;
+pub mod kdf;
 pub mod key_manager;
+pub mod keyring;
+pub mod master;
+pub mod padding;
+pub mod recipient;
+pub mod secret;
+pub mod shamir;
+pub mod signature;
+pub mod stream;
 
+pub use cipher::CipherSuite;
+pub use context::PayloadContext;
 pub use encryption::CryptoManager;
-pub use key_manager::KeyManager;
+pub use hybrid::{HybridPublicKey, HybridSecretKey};
+pub use kdf::KdfParams;
+pub use key_manager::{KeyInfo, KeyManager};
+pub use keyring::Keyring;
+pub use master::MasterKey;
+pub use padding::Padding;
+pub use recipient::{Recipient, RecipientPublicKey, RecipientSecretKey};
+pub use secret::{KeyId, SecretBytes, SecretKey};
+pub use signature::{SignerKeypair, SignerPublicKey};
@@ -0,0 +1,97 @@
+use crate::error::{Result, SteganoError};
+use super::secret::SecretBytes;
+
+/// The true length, before the message and its padding
+const PADDED_HEADER_LEN: usize = 4;
+
+/// How far a plaintext is padded before encryption, so the payload length
+/// says little or nothing about the length of the data. The true length is
+/// encrypted with the data and recovered only after decryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Padding {
+    /// No padding: the payload grows and shrinks with the data
+    #[default]
+    None,
+    /// Round the padded plaintext up to a power of two, so payloads only
+    /// reveal which bucket the data falls in
+    PowerOfTwo,
+    /// Pad so the whole payload, headers included, is exactly this many
+    /// bytes, such as the capacity of a carrier
+    ToLength(usize),
+}
+
+/// Frame `message` with its length and pad it as `padding` asks, for a
+/// payload that adds `overhead` bytes of its own. `None` if no padding is set.
+pub(crate) fn pad(padding: Padding, message: &[u8], overhead: usize) -> Result<Option<SecretBytes>> {
+    let framed = PADDED_HEADER_LEN + message.len();
+    let len = match padding {
+        Padding::None => return Ok(None),
+        Padding::PowerOfTwo => framed.next_power_of_two(),
+        Padding::ToLength(len) => {
+            let available = len.saturating_sub(overhead);
+            if framed > available {
+                return Err(SteganoError::EncryptionError(format!(
+                    "Data needs a {} byte payload but padding allows {}",
+                    framed + overhead,
+                    len
+                )));
+            }
+            available
+        }
+    };
+    let true_len = u32::try_from(message.len())
+        .map_err(|_| SteganoError::EncryptionError("Data is too large to pad".to_string()))?;
+
+    // Sized up front, so the plaintext is never copied by a reallocation
+    let mut result = Vec::with_capacity(len);
+    result.extend_from_slice(&true_len.to_le_bytes());
+    result.extend_from_slice(message);
+    result.resize(len, 0);
+    Ok(Some(SecretBytes::from(result)))
+}
+
+/// Strip the padding from a plaintext made by [`pad`]
+pub(crate) fn unpad(plaintext: &[u8]) -> Result<SecretBytes> {
+    let too_long = || SteganoError::DecryptionError("Padded length exceeds the payload".to_string());
+    let len_bytes = plaintext.get(..PADDED_HEADER_LEN).ok_or_else(too_long)?;
+    let len = u32::from_le_bytes(len_bytes.try_into().expect("slice is 4 bytes")) as usize;
+    let message = plaintext[PADDED_HEADER_LEN..].get(..len).ok_or_else(too_long)?;
+    Ok(SecretBytes::from(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn power_of_two_round_trips() {
+        let padded = pad(Padding::PowerOfTwo, b"hello", 0).unwrap().unwrap();
+        assert_eq!(padded.len(), 16);
+        assert_eq!(unpad(&padded).unwrap().expose(), b"hello");
+    }
+
+    #[test]
+    fn to_length_fills_what_the_overhead_leaves() {
+        let padded = pad(Padding::ToLength(100), b"hello", 40).unwrap().unwrap();
+        assert_eq!(padded.len(), 60);
+        assert_eq!(unpad(&padded).unwrap().expose(), b"hello");
+    }
+
+    #[test]
+    fn to_length_refuses_data_that_does_not_fit() {
+        assert!(pad(Padding::ToLength(20), &[0u8; 16], 8).is_err());
+    }
+
+    #[test]
+    fn no_padding_leaves_the_message_alone() {
+        assert!(pad(Padding::None, b"hello", 0).unwrap().is_none());
+    }
+
+    #[test]
+    fn unpad_refuses_a_length_beyond_the_plaintext() {
+        let mut padded = pad(Padding::PowerOfTwo, b"hello", 0).unwrap().unwrap().expose().to_vec();
+        padded[..4].copy_from_slice(&100u32.to_le_bytes());
+        assert!(unpad(&padded).is_err());
+        assert!(unpad(&[1, 0]).is_err());
+    }
+}
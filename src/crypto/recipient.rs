@@ -0,0 +1,336 @@
+use hkdf::Hkdf;
+use rand::{rng, RngCore};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use x25519_dalek::{PublicKey, StaticSecret};
+use crate::error::{Result, SteganoError};
+use super::cipher::CipherSuite;
+use super::hybrid::{self, HybridPublicKey};
+use super::kdf::{self, KdfParams, KDF_PARAMS_LEN};
+use super::master::MasterKey;
+use super::secret::{KeyId, SecretBytes, SecretKey};
+
+/// Prefix of an exported public key
+const PUBLIC_KEY_PREFIX: &str = "svx25519:";
+
+/// Slot kinds in a multi-recipient payload header
+pub(crate) const SLOT_X25519: u8 = 1;
+pub(crate) const SLOT_PASSPHRASE: u8 = 3;
+/// A symmetric key slot that starts with the key's ID
+pub(crate) const SLOT_NAMED_KEY: u8 = 4;
+pub(crate) const SLOT_HYBRID: u8 = 5;
+
+const SALT_LEN: usize = 16;
+
+/// Someone who can open a payload: each gets its own wrapped copy of the
+/// payload's content key
+#[derive(Clone)]
+pub enum Recipient {
+    /// A 256-bit symmetric key, such as a keyfile's
+    Key(SecretKey),
+    /// A passphrase, stretched with Argon2id at the given costs
+    Passphrase(SecretBytes, KdfParams),
+    /// An X25519 public key
+    PublicKey(RecipientPublicKey),
+    /// A hybrid X25519 + ML-KEM-768 public key
+    Hybrid(HybridPublicKey),
+}
+
+impl Recipient {
+    /// Wrap `content_key` for this recipient, returning the slot kind and data
+    pub(crate) fn wrap(&self, cipher: CipherSuite, aad: &[u8], content_key: &SecretKey) -> Result<(u8, Vec<u8>)> {
+        match self {
+            Recipient::Key(key) => {
+                let mut slot = key.id().to_bytes().to_vec();
+                slot.extend_from_slice(&cipher.encrypt(slot_key(key).expose(), aad, content_key.expose())?);
+                Ok((SLOT_NAMED_KEY, slot))
+            }
+            Recipient::Passphrase(passphrase, params) => {
+                if passphrase.is_empty() {
+                    return Err(SteganoError::InvalidKey("Passphrase must not be empty".to_string()));
+                }
+                let mut salt = [0u8; SALT_LEN];
+                rng().fill_bytes(&mut salt);
+                let wrapping_key = kdf::derive_key(passphrase, &salt, params)?;
+
+                let mut slot = params.to_bytes().to_vec();
+                slot.extend_from_slice(&salt);
+                slot.extend_from_slice(&cipher.encrypt(wrapping_key.expose(), aad, content_key.expose())?);
+                Ok((SLOT_PASSPHRASE, slot))
+            }
+            Recipient::PublicKey(public_key) => Ok((SLOT_X25519, wrap_for(public_key, cipher, aad, content_key)?)),
+            Recipient::Hybrid(public_key) => Ok((SLOT_HYBRID, hybrid::wrap_for(public_key, cipher, aad, content_key)?)),
+        }
+    }
+
+    /// Fingerprint of a public key recipient
+    pub fn fingerprint(&self) -> Option<String> {
+        match self {
+            Recipient::PublicKey(public_key) => Some(public_key.fingerprint()),
+            Recipient::Hybrid(public_key) => Some(public_key.fingerprint()),
+            _ => None,
+        }
+    }
+}
+
+impl From<RecipientPublicKey> for Recipient {
+    fn from(public_key: RecipientPublicKey) -> Self {
+        Recipient::PublicKey(public_key)
+    }
+}
+
+impl From<HybridPublicKey> for Recipient {
+    fn from(public_key: HybridPublicKey) -> Self {
+        Recipient::Hybrid(public_key)
+    }
+}
+
+/// An X25519 public key that payloads can be encrypted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecipientPublicKey([u8; 32]);
+
+impl RecipientPublicKey {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Parse the `svx25519:<hex>` form produced by `Display`
+    pub fn parse(text: &str) -> Result<Self> {
+        let hex = text
+            .trim()
+            .strip_prefix(PUBLIC_KEY_PREFIX)
+            .ok_or_else(|| SteganoError::InvalidKey(format!(
+                "Public key must start with '{}'",
+                PUBLIC_KEY_PREFIX
+            )))?;
+
+        let bytes = decode_hex(hex)
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .ok_or_else(|| SteganoError::InvalidKey(
+                "Public key must be 64 hexadecimal digits".to_string()
+            ))?;
+        Ok(Self(bytes))
+    }
+
+    /// Short SHA-256 fingerprint for comparing keys by eye
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.0)
+    }
+}
+
+impl fmt::Debug for RecipientSecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RecipientSecretKey({}, [REDACTED])", self.public_key())
+    }
+}
+
+impl fmt::Display for RecipientPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", PUBLIC_KEY_PREFIX, encode_hex(&self.0))
+    }
+}
+
+/// An X25519 secret key that can open payloads encrypted to its public key.
+/// Wiped when dropped.
+#[derive(Clone)]
+pub struct RecipientSecretKey(StaticSecret);
+
+impl RecipientSecretKey {
+    pub fn generate() -> Self {
+        Self::from_bytes(&SecretKey::generate())
+    }
+
+    pub fn from_bytes(bytes: &SecretKey) -> Self {
+        Self(StaticSecret::from(*bytes.expose()))
+    }
+
+    pub fn to_bytes(&self) -> SecretKey {
+        SecretKey::new(&mut self.0.to_bytes())
+    }
+
+    pub fn public_key(&self) -> RecipientPublicKey {
+        RecipientPublicKey(PublicKey::from(&self.0).to_bytes())
+    }
+}
+
+/// Wrap `content_key` for `recipient` with a fresh ephemeral key.
+/// Returns the ephemeral public key followed by the wrapped key.
+pub(crate) fn wrap_for(
+    recipient: &RecipientPublicKey,
+    cipher: CipherSuite,
+    aad: &[u8],
+    content_key: &SecretKey,
+) -> Result<Vec<u8>> {
+    let ephemeral = RecipientSecretKey::generate();
+    let ephemeral_public = ephemeral.public_key();
+    let wrapping_key = shared_key(&ephemeral, recipient, &ephemeral_public, recipient)
+        .ok_or_else(|| SteganoError::InvalidKey(
+            format!("Public key {} is not a usable X25519 key", recipient.fingerprint())
+        ))?;
+
+    let mut slot = ephemeral_public.to_bytes().to_vec();
+    slot.extend_from_slice(&cipher.encrypt(wrapping_key.expose(), aad, content_key.expose())?);
+    Ok(slot)
+}
+
+/// Recover the content key from a slot made by [`wrap_for`], or `None` if the
+/// slot was made for another key
+pub(crate) fn unwrap_with_secret_key(
+    secret: &RecipientSecretKey,
+    cipher: CipherSuite,
+    aad: &[u8],
+    slot: &[u8],
+) -> Option<SecretKey> {
+    let ephemeral_public = RecipientPublicKey(slot.get(..32)?.try_into().ok()?);
+    let wrapping_key = shared_key(secret, &ephemeral_public, &ephemeral_public, &secret.public_key())?;
+
+    let content_key = SecretBytes::from(cipher.decrypt(wrapping_key.expose(), aad, &slot[32..]).ok()?);
+    SecretKey::from_slice(&content_key)
+}
+
+/// Key ID recorded in a [`SLOT_NAMED_KEY`] slot
+pub(crate) fn slot_key_id(slot: &[u8]) -> Option<KeyId> {
+    Some(KeyId::from_bytes(slot.get(..8)?.try_into().ok()?))
+}
+
+/// Recover the content key from a [`SLOT_NAMED_KEY`] slot, skipping the
+/// decryption when the slot names another key
+pub(crate) fn unwrap_with_named_key(key: &SecretKey, cipher: CipherSuite, aad: &[u8], slot: &[u8]) -> Option<SecretKey> {
+    if slot_key_id(slot)? != key.id() {
+        return None;
+    }
+    let content_key = SecretBytes::from(cipher.decrypt(slot_key(key).expose(), aad, &slot[8..]).ok()?);
+    SecretKey::from_slice(&content_key)
+}
+
+/// Recover the content key from a [`SLOT_PASSPHRASE`] slot
+pub(crate) fn unwrap_with_passphrase(
+    passphrase: &[u8],
+    cipher: CipherSuite,
+    aad: &[u8],
+    slot: &[u8],
+) -> Option<SecretKey> {
+    let params = KdfParams::from_bytes(slot).ok()?;
+    let salt = slot.get(KDF_PARAMS_LEN..KDF_PARAMS_LEN + SALT_LEN)?;
+    let wrapping_key = kdf::derive_key(passphrase, salt, &params).ok()?;
+
+    let content_key = SecretBytes::from(
+        cipher.decrypt(wrapping_key.expose(), aad, &slot[KDF_PARAMS_LEN + SALT_LEN..]).ok()?
+    );
+    SecretKey::from_slice(&content_key)
+}
+
+/// Key-wrapping key for a symmetric key, kept apart from the key itself
+fn slot_key(key: &SecretKey) -> SecretKey {
+    MasterKey::new(key.clone()).wrapping_key()
+}
+
+/// HKDF-SHA256 over the X25519 shared secret with `peer`, bound to both public
+/// keys. `None` for low-order points, which give no secret.
+fn shared_key(
+    secret: &RecipientSecretKey,
+    peer: &RecipientPublicKey,
+    ephemeral_public: &RecipientPublicKey,
+    recipient_public: &RecipientPublicKey,
+) -> Option<SecretKey> {
+    let shared = diffie_hellman(secret, peer)?;
+
+    let mut salt = ephemeral_public.0.to_vec();
+    salt.extend_from_slice(&recipient_public.0);
+
+    let mut key = SecretKey::new(&mut [0u8; 32]);
+    Hkdf::<Sha256>::new(Some(&salt), shared.expose())
+        .expand(b"stegano-vault x25519 key wrap", key.expose_mut())
+        .ok()?;
+    Some(key)
+}
+
+/// The raw X25519 shared secret with `peer`, or `None` for low-order points
+pub(crate) fn diffie_hellman(secret: &RecipientSecretKey, peer: &RecipientPublicKey) -> Option<SecretKey> {
+    let shared = secret.0.diffie_hellman(&PublicKey::from(peer.0));
+    if !shared.was_contributory() {
+        return None;
+    }
+    Some(SecretKey::new(&mut shared.to_bytes()))
+}
+
+pub(crate) fn fingerprint(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    digest[..8]
+        .chunks(2)
+        .map(encode_hex)
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(text.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::CryptoManager;
+
+    #[test]
+    fn public_key_text_round_trips() {
+        let public_key = RecipientSecretKey::generate().public_key();
+        assert_eq!(RecipientPublicKey::parse(&public_key.to_string()).unwrap(), public_key);
+        assert!(RecipientPublicKey::parse("svx25519:abcd").is_err());
+        assert!(RecipientPublicKey::parse(&encode_hex(&public_key.to_bytes())).is_err());
+    }
+
+    #[test]
+    fn payload_opens_only_with_the_recipients_secret_key() {
+        let secret_key = RecipientSecretKey::generate();
+        let ciphertext = CryptoManager::for_recipients(vec![secret_key.public_key()])
+            .unwrap()
+            .encrypt(b"for you")
+            .unwrap();
+
+        let opened = CryptoManager::with_secret_key(secret_key).decrypt(&ciphertext).unwrap();
+        assert_eq!(opened.expose(), b"for you");
+        assert!(CryptoManager::with_secret_key(RecipientSecretKey::generate()).decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn slots_use_a_fresh_ephemeral_key() {
+        let public_key = RecipientSecretKey::generate().public_key();
+        let content_key = SecretKey::generate();
+        let first = wrap_for(&public_key, CipherSuite::default(), b"aad", &content_key).unwrap();
+        let second = wrap_for(&public_key, CipherSuite::default(), b"aad", &content_key).unwrap();
+        assert_ne!(first[..32], second[..32]);
+    }
+
+    #[test]
+    fn slot_is_bound_to_its_associated_data() {
+        let secret_key = RecipientSecretKey::generate();
+        let content_key = SecretKey::generate();
+        let slot = wrap_for(&secret_key.public_key(), CipherSuite::default(), b"aad", &content_key).unwrap();
+
+        let unwrapped = unwrap_with_secret_key(&secret_key, CipherSuite::default(), b"aad", &slot).unwrap();
+        assert_eq!(unwrapped, content_key);
+        assert!(unwrap_with_secret_key(&secret_key, CipherSuite::default(), b"other", &slot).is_none());
+    }
+
+    #[test]
+    fn low_order_public_keys_are_refused() {
+        let content_key = SecretKey::generate();
+        let zero = RecipientPublicKey::from_bytes([0u8; 32]);
+        assert!(wrap_for(&zero, CipherSuite::default(), b"aad", &content_key).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use rand::{rng, RngCore};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::ops::Deref;
+use zeroize::Zeroize;
+use crate::error::{Result, SteganoError};
+use super::recipient::{decode_hex, encode_hex};
+
+/// A 256-bit key that is locked into RAM where the platform allows, wiped when
+/// dropped and never printed. Borrow it with [`SecretKey::expose`] instead of
+/// copying it around.
+pub struct SecretKey {
+    bytes: Box<[u8; 32]>,
+    locked: bool,
+}
+
+impl SecretKey {
+    /// Take ownership of `bytes`; the caller's copy is wiped
+    pub fn new(bytes: &mut [u8; 32]) -> Self {
+        // Box first so the key is locked and wiped at its final address
+        let mut key = Box::new([0u8; 32]);
+        key.copy_from_slice(bytes);
+        bytes.zeroize();
+
+        let locked = memory_lock(key.as_ptr(), key.len());
+        Self { bytes: key, locked }
+    }
+
+    /// Copy a key out of a buffer that must hold exactly 32 bytes
+    pub(crate) fn from_slice(bytes: &[u8]) -> Option<Self> {
+        let mut key = Self::new(&mut [0u8; 32]);
+        key.expose_mut().copy_from_slice(bytes.get(..32).filter(|_| bytes.len() == 32)?);
+        Some(key)
+    }
+
+    pub fn generate() -> Self {
+        let mut key = Self::new(&mut [0u8; 32]);
+        rng().fill_bytes(key.expose_mut());
+        key
+    }
+
+    pub fn expose(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+
+    /// Public identifier of this key, recorded in keyfiles and payloads
+    pub fn id(&self) -> KeyId {
+        let mut hasher = Sha256::new();
+        hasher.update(b"stegano-vault key id");
+        hasher.update(self.expose());
+        let digest = hasher.finalize();
+
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&digest[..8]);
+        KeyId(id)
+    }
+
+    /// Fill the key in place, for derivations that write their output
+    pub(crate) fn expose_mut(&mut self) -> &mut [u8; 32] {
+        &mut self.bytes
+    }
+}
+
+impl Clone for SecretKey {
+    fn clone(&self) -> Self {
+        let mut bytes = *self.expose();
+        Self::new(&mut bytes)
+    }
+}
+
+/// Compares in constant time
+impl PartialEq for SecretKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes
+            .iter()
+            .zip(other.bytes.iter())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+}
+
+impl Eq for SecretKey {}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SecretKey([REDACTED])")
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+        if self.locked {
+            memory_unlock(self.bytes.as_ptr(), self.bytes.len());
+        }
+    }
+}
+
+/// Short identifier of a symmetric key: a domain-separated hash that names the
+/// key without revealing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyId([u8; 8]);
+
+impl KeyId {
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 8] {
+        self.0
+    }
+
+    /// Parse the `ab12:cd34:ef56:7890` form produced by `Display`, with or
+    /// without the colons
+    pub fn parse(text: &str) -> Result<Self> {
+        decode_hex(&text.trim().replace(':', ""))
+            .and_then(|bytes| <[u8; 8]>::try_from(bytes).ok())
+            .map(Self)
+            .ok_or_else(|| SteganoError::InvalidKey(
+                format!("Invalid key ID '{}': expected 16 hexadecimal digits", text.trim())
+            ))
+    }
+}
+
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.chunks(2).map(encode_hex).collect::<Vec<_>>().join(":"))
+    }
+}
+
+/// Sensitive bytes such as decrypted plaintext or a passphrase, locked and
+/// wiped like [`SecretKey`]. The buffer never grows, so it is never left
+/// behind by a reallocation.
+pub struct SecretBytes {
+    bytes: Vec<u8>,
+    locked: bool,
+}
+
+impl SecretBytes {
+    pub fn expose(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Overwrite the bytes in place; the length stays fixed
+    pub(crate) fn expose_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        let locked = !bytes.is_empty() && memory_lock(bytes.as_ptr(), bytes.capacity());
+        Self { bytes, locked }
+    }
+}
+
+impl From<&[u8]> for SecretBytes {
+    fn from(bytes: &[u8]) -> Self {
+        Self::from(bytes.to_vec())
+    }
+}
+
+impl From<String> for SecretBytes {
+    fn from(text: String) -> Self {
+        Self::from(text.into_bytes())
+    }
+}
+
+impl Clone for SecretBytes {
+    fn clone(&self) -> Self {
+        Self::from(self.expose())
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {}])", self.bytes.len())
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        // Wipe the spare capacity too, in case it ever held data
+        self.bytes.zeroize();
+        if self.locked {
+            memory_unlock(self.bytes.as_ptr(), self.bytes.capacity());
+        }
+    }
+}
+
+/// Keep the pages holding a secret out of swap. Best effort: fails quietly
+/// when the memory lock limit is reached, and locks do not nest, so dropping
+/// one secret can unlock a page it shares with another.
+#[cfg(target_os = "linux")]
+fn memory_lock(ptr: *const u8, len: usize) -> bool {
+    // SAFETY: the range lies within a live allocation owned by the caller
+    unsafe { libc::mlock(ptr.cast(), len) == 0 }
+}
+
+#[cfg(target_os = "linux")]
+fn memory_unlock(ptr: *const u8, len: usize) {
+    // SAFETY: the range was locked by memory_lock and is still allocated
+    unsafe {
+        libc::munlock(ptr.cast(), len);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn memory_lock(_ptr: *const u8, _len: usize) -> bool {
+    false
+}
+
+#[cfg(not(target_os = "linux"))]
+fn memory_unlock(_ptr: *const u8, _len: usize) {}
@@ -0,0 +1,220 @@
+use rand::{rng, RngCore};
+use crate::error::{Result, SteganoError};
+use super::secret::SecretBytes;
+
+/// Marks one share of a split secret
+const SHARE_MAGIC: &[u8; 4] = b"SVSS";
+const SHARE_VERSION: u8 = 1;
+/// magic | version | set ID | threshold | index
+const SHARE_HEADER_LEN: usize = 4 + 1 + 8 + 1 + 1;
+
+/// One of the shares a secret is split into. Fewer than the threshold reveal
+/// nothing about the secret; any `threshold` of them rebuild it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    /// Random ID shared by every share of one split, so shares of different
+    /// secrets are not mixed
+    set_id: [u8; 8],
+    threshold: u8,
+    /// The share's x coordinate, never 0
+    index: u8,
+    data: Vec<u8>,
+}
+
+impl Share {
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// magic | version | set ID | threshold | index | data
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = SHARE_MAGIC.to_vec();
+        bytes.push(SHARE_VERSION);
+        bytes.extend_from_slice(&self.set_id);
+        bytes.push(self.threshold);
+        bytes.push(self.index);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(SHARE_MAGIC) || bytes.len() < SHARE_HEADER_LEN {
+            return Err(SteganoError::InvalidData("Data is not a secret share".to_string()));
+        }
+        if bytes[4] != SHARE_VERSION {
+            return Err(SteganoError::InvalidData(format!("Unsupported share version {}", bytes[4])));
+        }
+
+        let share = Self {
+            set_id: bytes[5..13].try_into().expect("slice is 8 bytes"),
+            threshold: bytes[13],
+            index: bytes[14],
+            data: bytes[SHARE_HEADER_LEN..].to_vec(),
+        };
+        if share.threshold == 0 || share.index == 0 {
+            return Err(SteganoError::InvalidData("Share header is corrupted".to_string()));
+        }
+        Ok(share)
+    }
+}
+
+/// Split `secret` into `count` shares, any `threshold` of which rebuild it
+/// (Shamir's scheme over GF(256), byte by byte)
+pub fn split(secret: &[u8], threshold: u8, count: u8) -> Result<Vec<Share>> {
+    if threshold == 0 || threshold > count {
+        return Err(SteganoError::InvalidData(format!(
+            "Threshold must be between 1 and the number of shares ({}), got {}",
+            count, threshold
+        )));
+    }
+
+    let mut set_id = [0u8; 8];
+    rng().fill_bytes(&mut set_id);
+
+    let mut shares: Vec<Share> = (1..=count)
+        .map(|index| Share { set_id, threshold, index, data: Vec::with_capacity(secret.len()) })
+        .collect();
+
+    // Each byte is the constant term of its own random polynomial of degree
+    // threshold - 1; the coefficients are wiped once used
+    let mut polynomial = SecretBytes::from(vec![0u8; threshold as usize]);
+    for &byte in secret {
+        let coefficients = polynomial.expose_mut();
+        coefficients[0] = byte;
+        rng().fill_bytes(&mut coefficients[1..]);
+
+        for share in &mut shares {
+            // Horner's rule from the highest coefficient down
+            let y = coefficients.iter().rev().fold(0, |acc, &c| gf_mul(acc, share.index) ^ c);
+            share.data.push(y);
+        }
+    }
+    Ok(shares)
+}
+
+/// Rebuild a secret from at least its threshold of shares
+pub fn combine(shares: &[Share]) -> Result<SecretBytes> {
+    let first = shares.first().ok_or_else(|| SteganoError::InvalidData("No shares given".to_string()))?;
+
+    for share in shares {
+        if share.set_id != first.set_id || share.threshold != first.threshold {
+            return Err(SteganoError::InvalidData("Shares belong to different secrets".to_string()));
+        }
+        if share.data.len() != first.data.len() {
+            return Err(SteganoError::InvalidData("Shares have different lengths".to_string()));
+        }
+    }
+
+    let mut distinct: Vec<&Share> = Vec::new();
+    for share in shares {
+        if !distinct.iter().any(|other| other.index == share.index) {
+            distinct.push(share);
+        }
+    }
+    let threshold = first.threshold as usize;
+    if distinct.len() < threshold {
+        return Err(SteganoError::InvalidData(format!(
+            "{} distinct shares given, but {} are needed",
+            distinct.len(),
+            threshold
+        )));
+    }
+    let shares = &distinct[..threshold];
+
+    // Lagrange basis polynomials evaluated at x = 0
+    let weights: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1, |acc, other| gf_mul(acc, gf_div(other.index, other.index ^ share.index)))
+        })
+        .collect();
+
+    let secret = (0..first.data.len())
+        .map(|pos| {
+            shares
+                .iter()
+                .zip(&weights)
+                .fold(0, |acc, (share, &weight)| acc ^ gf_mul(share.data[pos], weight))
+        })
+        .collect::<Vec<u8>>();
+    Ok(SecretBytes::from(secret))
+}
+
+/// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x + 1, without
+/// secret-dependent branches or table lookups
+fn gf_mul(a: u8, b: u8) -> u8 {
+    let (mut a, mut b, mut product) = (a, b, 0u8);
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Division in GF(2^8): `a` times the inverse of `b`, which is b^254
+fn gf_div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1;
+    let mut power = b;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            inverse = gf_mul(inverse, power);
+        }
+        power = gf_mul(power, power);
+        exponent >>= 1;
+    }
+    gf_mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_threshold_of_shares_rebuilds_the_secret() {
+        let shares = split(b"launch codes", 3, 5).unwrap();
+        for picked in [[0, 1, 2], [0, 2, 4], [4, 3, 1]] {
+            let subset: Vec<Share> = picked.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(combine(&subset).unwrap().expose(), b"launch codes");
+        }
+    }
+
+    #[test]
+    fn fewer_than_threshold_shares_are_refused() {
+        let shares = split(b"launch codes", 3, 5).unwrap();
+        assert!(combine(&shares[..2]).is_err());
+        // A repeated share does not count twice
+        assert!(combine(&[shares[0].clone(), shares[0].clone(), shares[1].clone()]).is_err());
+    }
+
+    #[test]
+    fn shares_of_different_secrets_are_refused() {
+        let first = split(b"launch codes", 2, 3).unwrap();
+        let second = split(b"launch codes", 2, 3).unwrap();
+        assert!(combine(&[first[0].clone(), second[1].clone()]).is_err());
+    }
+
+    #[test]
+    fn a_changed_share_gives_a_different_secret() {
+        let mut shares = split(b"launch codes", 2, 3).unwrap();
+        shares[0].data[0] ^= 1;
+        assert_ne!(combine(&shares[..2]).unwrap().expose(), b"launch codes");
+    }
+
+    #[test]
+    fn shares_survive_serialization() {
+        let shares = split(b"launch codes", 2, 2).unwrap();
+        let parsed: Vec<Share> = shares.iter().map(|share| Share::from_bytes(&share.to_bytes()).unwrap()).collect();
+        assert_eq!(parsed, shares);
+        assert!(Share::from_bytes(b"SVSS").is_err());
+    }
+}
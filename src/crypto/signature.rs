@@ -0,0 +1,151 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use std::fmt;
+use crate::error::{Result, SteganoError};
+use super::recipient::{decode_hex, encode_hex, fingerprint};
+use super::secret::{SecretBytes, SecretKey};
+
+/// Prefix of an exported verification key
+const PUBLIC_KEY_PREFIX: &str = "sved25519:";
+
+/// verification key | signature, before the signed message
+const SIGNED_HEADER_LEN: usize = 32 + 64;
+
+/// Domain separation, so payload signatures cannot be replayed elsewhere
+const SIGNATURE_CONTEXT: &[u8] = b"stegano-vault signed payload";
+
+/// An Ed25519 key that signs payloads. Wiped when dropped.
+#[derive(Clone)]
+pub struct SignerKeypair(SigningKey);
+
+impl SignerKeypair {
+    pub fn generate() -> Self {
+        Self::from_bytes(&SecretKey::generate())
+    }
+
+    pub fn from_bytes(seed: &SecretKey) -> Self {
+        Self(SigningKey::from_bytes(seed.expose()))
+    }
+
+    pub fn to_bytes(&self) -> SecretKey {
+        SecretKey::new(&mut self.0.to_bytes())
+    }
+
+    pub fn public_key(&self) -> SignerPublicKey {
+        SignerPublicKey(self.0.verifying_key().to_bytes())
+    }
+}
+
+impl fmt::Debug for SignerKeypair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SignerKeypair({}, [REDACTED])", self.public_key())
+    }
+}
+
+/// An Ed25519 verification key identifying a signer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignerPublicKey([u8; 32]);
+
+impl SignerPublicKey {
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Parse the `sved25519:<hex>` form produced by `Display`
+    pub fn parse(text: &str) -> Result<Self> {
+        let hex = text
+            .trim()
+            .strip_prefix(PUBLIC_KEY_PREFIX)
+            .ok_or_else(|| SteganoError::InvalidKey(format!(
+                "Signer key must start with '{}'",
+                PUBLIC_KEY_PREFIX
+            )))?;
+
+        let bytes = decode_hex(hex)
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .ok_or_else(|| SteganoError::InvalidKey(
+                "Signer key must be 64 hexadecimal digits".to_string()
+            ))?;
+        VerifyingKey::from_bytes(&bytes)
+            .map_err(|_| SteganoError::InvalidKey("Signer key is not a valid Ed25519 key".to_string()))?;
+        Ok(Self(bytes))
+    }
+
+    /// Short SHA-256 fingerprint for comparing keys by eye
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.0)
+    }
+}
+
+impl fmt::Display for SignerPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", PUBLIC_KEY_PREFIX, encode_hex(&self.0))
+    }
+}
+
+/// Prefix `message` with the signer's key and a signature over it
+pub(crate) fn sign(signer: &SignerKeypair, message: &[u8]) -> Vec<u8> {
+    let signature = signer.0.sign(&signed_bytes(message));
+
+    let mut result = signer.public_key().0.to_vec();
+    result.extend_from_slice(&signature.to_bytes());
+    result.extend_from_slice(message);
+    result
+}
+
+/// Split a plaintext made by [`sign`] into the message and its verified signer
+pub(crate) fn verify(plaintext: &[u8]) -> Result<(SecretBytes, SignerPublicKey)> {
+    let invalid = || SteganoError::DecryptionError("Payload signature is invalid".to_string());
+    if plaintext.len() < SIGNED_HEADER_LEN {
+        return Err(invalid());
+    }
+
+    let key_bytes: [u8; 32] = plaintext[..32].try_into().expect("slice is 32 bytes");
+    let signature_bytes: [u8; 64] = plaintext[32..SIGNED_HEADER_LEN].try_into().expect("slice is 64 bytes");
+    let message = &plaintext[SIGNED_HEADER_LEN..];
+
+    let verifying_key = VerifyingKey::from_bytes(&key_bytes).map_err(|_| invalid())?;
+    verifying_key
+        .verify_strict(&signed_bytes(message), &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| invalid())?;
+
+    Ok((SecretBytes::from(message), SignerPublicKey(key_bytes)))
+}
+
+fn signed_bytes(message: &[u8]) -> Vec<u8> {
+    let mut bytes = SIGNATURE_CONTEXT.to_vec();
+    bytes.extend_from_slice(message);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_returns_the_message_and_signer() {
+        let signer = SignerKeypair::generate();
+        let (message, public_key) = verify(&sign(&signer, b"report")).unwrap();
+        assert_eq!(message.expose(), b"report");
+        assert_eq!(public_key, signer.public_key());
+    }
+
+    #[test]
+    fn verify_refuses_a_changed_message() {
+        let mut signed = sign(&SignerKeypair::generate(), b"report");
+        *signed.last_mut().unwrap() ^= 1;
+        assert!(verify(&signed).is_err());
+    }
+
+    #[test]
+    fn verify_refuses_another_signers_key() {
+        let mut signed = sign(&SignerKeypair::generate(), b"report");
+        signed[..32].copy_from_slice(&SignerKeypair::generate().public_key().to_bytes());
+        assert!(verify(&signed).is_err());
+    }
+
+    #[test]
+    fn verify_refuses_a_truncated_signature() {
+        let signed = sign(&SignerKeypair::generate(), b"");
+        assert!(verify(&signed[..SIGNED_HEADER_LEN - 1]).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! STREAM segmented encryption (Hoang, Reyhanitabar, Rogaway and Vizár):
+//! the plaintext is cut into fixed-size chunks, each sealed under a nonce
+//! made of a random prefix, the chunk counter and a flag marking the final
+//! chunk. Reordered, dropped or appended chunks fail to decrypt, and only one
+//! chunk is held in memory at a time.
+
+use std::io::{self, Read, Write};
+use crate::error::{Result, SteganoError};
+use super::cipher::CipherSuite;
+use super::secret::{SecretBytes, SecretKey};
+
+/// Plaintext bytes per chunk unless a payload says otherwise
+pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+/// Largest chunk a payload may ask a reader to buffer
+pub const MAX_CHUNK_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Authentication tag added to every chunk
+const TAG_LEN: usize = 16;
+/// Counter (u32, big-endian) and final-chunk flag closing every nonce
+const NONCE_SUFFIX_LEN: usize = 4 + 1;
+
+/// Random part of the nonces, written once in the payload header
+pub(crate) fn nonce_prefix_len(cipher: CipherSuite) -> usize {
+    cipher.nonce_len() - NONCE_SUFFIX_LEN
+}
+
+/// Check a chunk size read from a header or given by a caller
+pub(crate) fn check_chunk_size(chunk_size: u32) -> Result<()> {
+    if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {
+        return Err(SteganoError::InvalidData(format!(
+            "Chunk size must be between 1 and {} bytes, got {}",
+            MAX_CHUNK_SIZE, chunk_size
+        )));
+    }
+    Ok(())
+}
+
+/// Encrypt everything `reader` yields into `writer` as chunks of
+/// `chunk_size` bytes, each authenticating `aad`. Returns the number of
+/// plaintext bytes.
+pub(crate) fn encrypt<R: Read, W: Write>(
+    cipher: CipherSuite,
+    key: &SecretKey,
+    nonce_prefix: &[u8],
+    chunk_size: u32,
+    aad: &[u8],
+    mut reader: R,
+    mut writer: W,
+) -> Result<u64> {
+    let chunk_size = chunk_size as usize;
+    let mut current = SecretBytes::from(vec![0u8; chunk_size]);
+    let mut next = SecretBytes::from(vec![0u8; chunk_size]);
+    let mut len = read_full(&mut reader, current.expose_mut())?;
+
+    let mut total = 0u64;
+    let mut counter = 0u32;
+    loop {
+        // A short chunk is the last one; a full chunk is the last one if
+        // nothing follows it
+        let next_len = if len == chunk_size { read_full(&mut reader, next.expose_mut())? } else { 0 };
+        let last = next_len == 0;
+
+        let nonce = nonce(nonce_prefix, counter, last);
+        writer.write_all(&cipher.seal(key.expose(), &nonce, aad, &current[..len])?)?;
+        total += len as u64;
+
+        if last {
+            break;
+        }
+        std::mem::swap(&mut current, &mut next);
+        len = next_len;
+        counter = counter.checked_add(1).ok_or_else(|| SteganoError::EncryptionError(
+            "Payload has too many chunks; use a larger chunk size".to_string()
+        ))?;
+    }
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Decrypt chunks written by [`encrypt`] from `reader` into `writer`,
+/// returning the number of plaintext bytes. On an error, whatever was
+/// already written must be discarded.
+pub(crate) fn decrypt<R: Read, W: Write>(
+    cipher: CipherSuite,
+    key: &SecretKey,
+    nonce_prefix: &[u8],
+    chunk_size: u32,
+    aad: &[u8],
+    mut reader: R,
+    mut writer: W,
+) -> Result<u64> {
+    let sealed_size = chunk_size as usize + TAG_LEN;
+    let mut current = vec![0u8; sealed_size];
+    let mut next = vec![0u8; sealed_size];
+    let mut len = read_full(&mut reader, &mut current)?;
+
+    let mut total = 0u64;
+    let mut counter = 0u32;
+    loop {
+        if len < TAG_LEN {
+            return Err(SteganoError::DecryptionError(format!("Chunk {} is truncated", counter)));
+        }
+        let next_len = if len == sealed_size { read_full(&mut reader, &mut next)? } else { 0 };
+        let last = next_len == 0;
+
+        let nonce = nonce(nonce_prefix, counter, last);
+        let plaintext = SecretBytes::from(cipher.open(key.expose(), &nonce, aad, &current[..len]).map_err(|_| {
+            SteganoError::DecryptionError(format!(
+                "Chunk {} failed to decrypt: wrong key, a different bound context, or data that was corrupted, truncated or extended",
+                counter
+            ))
+        })?);
+        writer.write_all(&plaintext)?;
+        total += plaintext.len() as u64;
+
+        if last {
+            break;
+        }
+        std::mem::swap(&mut current, &mut next);
+        len = next_len;
+        counter = counter.checked_add(1).ok_or_else(|| SteganoError::DecryptionError(
+            "Payload has too many chunks".to_string()
+        ))?;
+    }
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Prefix | counter | final-chunk flag
+fn nonce(prefix: &[u8], counter: u32, last: bool) -> Vec<u8> {
+    let mut nonce = prefix.to_vec();
+    nonce.extend_from_slice(&counter.to_be_bytes());
+    nonce.push(last as u8);
+    nonce
+}
+
+/// Fill `buffer` unless the reader ends first, returning the bytes read
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
@@ -6,9 +6,21 @@
 pub mod error;
 pub mod crypto;
 pub mod steganography;
+pub mod rotation;
+pub mod sharing;
 pub mod cli;
+#[cfg(test)]
+mod testing;
 
 // Re-export commonly used types
 pub use error::{Result, SteganoError};
-pub use crypto::{CryptoManager, KeyManager};
-pub use steganography::{Steganography, ImageSteganography, AudioSteganography};
+pub use crypto::{
+    CipherSuite, CryptoManager, HybridPublicKey, HybridSecretKey, KeyId, KeyInfo, KeyManager, Keyring, MasterKey,
+    Padding, PayloadContext, Recipient, RecipientPublicKey, RecipientSecretKey, SecretBytes, SecretKey, SignerKeypair,
+    SignerPublicKey,
+};
+pub use rotation::{KeyRotation, RotationReport};
+pub use sharing::SecretSharing;
+pub use steganography::{
+    Steganography, ImageSteganography, AudioSteganography, AiffSteganography, AuSteganography,
+};
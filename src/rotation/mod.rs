@@ -0,0 +1,330 @@
+//! Re-keying existing carriers: each payload is extracted with the old key,
+//! encrypted under the new one and embedded again in the same kind of carrier
+//! with the same parameters.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::crypto::{CryptoManager, MasterKey, PayloadContext};
+use crate::error::{Result, SteganoError};
+use crate::steganography::{path_str, Carrier, PdfMode};
+
+/// Moves carriers from one key to another
+pub struct KeyRotation {
+    old: CryptoManager,
+    new: CryptoManager,
+    output_dir: Option<PathBuf>,
+    bind_carrier: bool,
+    label: Option<String>,
+}
+
+impl KeyRotation {
+    /// Rotate from whatever `old` can open to `new`. Payloads come out
+    /// encrypted for `new` alone, whoever else they were made for.
+    pub fn new(old: CryptoManager, new: CryptoManager) -> Self {
+        Self { old, new, output_dir: None, bind_carrier: false, label: None }
+    }
+
+    /// Write rotated carriers to `dir` instead of replacing them in place
+    pub fn with_output_dir(mut self, dir: &Path) -> Self {
+        self.output_dir = Some(dir.to_path_buf());
+        self
+    }
+
+    /// Payloads are bound to their carriers, as with `--bind-carrier`
+    pub fn with_bound_carriers(mut self) -> Self {
+        self.bind_carrier = true;
+        self
+    }
+
+    /// Payloads are bound to `label`
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// Rotate every carrier in `paths`; directories contribute the carriers
+    /// directly inside them. Failures are recorded and the rest carry on.
+    pub fn rotate(&self, paths: &[PathBuf]) -> RotationReport {
+        let mut files = Vec::new();
+        for path in paths {
+            match Self::carriers_in(path) {
+                Ok(carriers) => files.extend(carriers),
+                Err(error) => files.push(RotatedFile {
+                    source: path.clone(),
+                    destination: path.clone(),
+                    result: Err(error),
+                }),
+            }
+        }
+
+        // Carriers with the same name in different directories would
+        // overwrite each other in the output directory
+        let mut targets: HashMap<PathBuf, usize> = HashMap::new();
+        for file in files.iter().filter(|file| file.result.is_ok()) {
+            *targets.entry(self.destination(&file.source)).or_default() += 1;
+        }
+
+        let files = files
+            .into_iter()
+            .map(|file| {
+                if file.result.is_err() {
+                    return file;
+                }
+                let destination = self.destination(&file.source);
+                let result = if targets[&destination] > 1 {
+                    Err(SteganoError::InvalidData(format!(
+                        "Another carrier would also be written to {}",
+                        destination.display()
+                    )))
+                } else {
+                    self.rotate_file(&file.source, &destination)
+                };
+                RotatedFile { source: file.source, destination, result }
+            })
+            .collect();
+        RotationReport { files }
+    }
+
+    /// Where a rotated copy of `source` goes
+    pub fn destination(&self, source: &Path) -> PathBuf {
+        match (&self.output_dir, source.file_name()) {
+            (Some(dir), Some(name)) => dir.join(name),
+            _ => source.to_path_buf(),
+        }
+    }
+
+    /// Rotate one carrier into `destination`, which may be `source` itself.
+    /// The new carrier is written next to the destination, checked to open
+    /// with the new key, then renamed over it, so a failure leaves the
+    /// destination untouched.
+    pub fn rotate_file(&self, source: &Path, destination: &Path) -> Result<()> {
+        let source_str = path_str(source)?;
+        let (carrier, old_key, payload) = self.extract(source_str)?;
+
+        if let Some(dir) = destination.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let temp = temp_path(destination)?;
+        let temp_str = path_str(&temp)?;
+
+        let result = (|| {
+            let context = self.context(carrier, source_str)?;
+            let new = self.new.clone().with_context(&context);
+            let payload = self.old.clone().with_context(&context).rekey(&payload, &new)?;
+
+            let steg = carrier.steganography(source_str, new.carrier_key());
+            steg.replace(source_str, &old_key, &payload, temp_str)?;
+            new.decrypt(&steg.extract(temp_str)?)?;
+
+            fs::rename(&temp, destination)?;
+            Ok(())
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// Find the carrier kind and extract the payload with the old key. Every
+    /// PDF mode and every old carrier key is tried, so the original
+    /// parameters need not be known; the carrier key that worked is returned.
+    fn extract(&self, path: &str) -> Result<(Carrier, MasterKey, Vec<u8>)> {
+        let unsupported = || SteganoError::InvalidData(format!("{} is not a supported carrier", path));
+        let carrier = Carrier::detect(path, PdfMode::default()).ok_or_else(unsupported)?;
+
+        let public_carrier_key = CryptoManager::public_carrier_key();
+
+        let mut first_error = None;
+        for carrier in carrier.variants() {
+            let context = match self.context(carrier, path) {
+                Ok(context) => context,
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                    continue;
+                }
+            };
+            let old = self.old.clone().with_context(&context);
+            for stealth_key in old.carrier_keys().chain([&public_carrier_key]) {
+                let payload = carrier.steganography(path, stealth_key).extract(path);
+                match payload.and_then(|payload| old.decrypt(&payload).map(|_| payload)) {
+                    Ok(payload) => return Ok((carrier, stealth_key.clone(), payload)),
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+        }
+        Err(first_error.unwrap_or_else(unsupported))
+    }
+
+    fn context(&self, carrier: Carrier, path: &str) -> Result<PayloadContext> {
+        let mut context = PayloadContext::new();
+        if let Some(label) = &self.label {
+            context = context.with_label(label);
+        }
+        if self.bind_carrier {
+            context = carrier.bound_context(path, context)?;
+        }
+        Ok(context)
+    }
+
+    /// `path` itself if it is a file, or the carriers directly inside it
+    fn carriers_in(path: &Path) -> Result<Vec<RotatedFile>> {
+        if !path.is_dir() {
+            return Ok(vec![RotatedFile::pending(path)]);
+        }
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let file = entry?.path();
+            let supported = file
+                .to_str()
+                .is_some_and(|name| Carrier::detect(name, PdfMode::default()).is_some());
+            if file.is_file() && supported {
+                files.push(file);
+            }
+        }
+        files.sort();
+        Ok(files.iter().map(|file| RotatedFile::pending(file)).collect())
+    }
+}
+
+/// Outcome of rotating one carrier
+#[derive(Debug)]
+pub struct RotatedFile {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub result: Result<()>,
+}
+
+impl RotatedFile {
+    fn pending(path: &Path) -> Self {
+        Self { source: path.to_path_buf(), destination: path.to_path_buf(), result: Ok(()) }
+    }
+}
+
+/// Every carrier a rotation touched, in order
+#[derive(Debug)]
+pub struct RotationReport {
+    pub files: Vec<RotatedFile>,
+}
+
+impl RotationReport {
+    pub fn succeeded(&self) -> impl Iterator<Item = &RotatedFile> {
+        self.files.iter().filter(|file| file.result.is_ok())
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &RotatedFile> {
+        self.files.iter().filter(|file| file.result.is_err())
+    }
+}
+
+impl fmt::Display for RotationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for file in &self.files {
+            match &file.result {
+                Ok(()) if file.source == file.destination => writeln!(f, "ok      {}", file.source.display())?,
+                Ok(()) => writeln!(f, "ok      {} -> {}", file.source.display(), file.destination.display())?,
+                Err(e) => writeln!(f, "FAILED  {}: {}", file.source.display(), e)?,
+            }
+        }
+        write!(
+            f,
+            "{} rotated, {} failed",
+            self.succeeded().count(),
+            self.failed().count()
+        )
+    }
+}
+
+/// Hidden sibling of `destination` that keeps its extension, which some
+/// carriers use to pick the output format
+fn temp_path(destination: &Path) -> Result<PathBuf> {
+    let name = destination
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| SteganoError::InvalidData(format!("{} is not a file path", destination.display())))?;
+    Ok(destination.with_file_name(format!(".rotating-{}-{}", std::process::id(), name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{temp_dir, write_png};
+
+    fn embed(crypto: &CryptoManager, path: &Path, secret: &[u8]) {
+        let path = path_str(path).unwrap();
+        let payload = crypto.encrypt(secret).unwrap();
+        let steg = Carrier::Image.steganography(path, crypto.carrier_key());
+        steg.embed(path, &payload, path).unwrap();
+    }
+
+    fn open(crypto: &CryptoManager, path: &Path) -> Result<Vec<u8>> {
+        let path = path_str(path)?;
+        let payload = Carrier::Image.steganography(path, crypto.carrier_key()).extract(path)?;
+        Ok(crypto.decrypt(&payload)?.expose().to_vec())
+    }
+
+    #[test]
+    fn report_lists_every_carrier_and_failures_leave_nothing_behind() {
+        let dir = temp_dir("rotation-report");
+        let (input, output) = (dir.join("in"), dir.join("out"));
+        fs::create_dir_all(&input).unwrap();
+        let (old, new, stranger) = (CryptoManager::new(), CryptoManager::new(), CryptoManager::new());
+        for (name, owner) in [("a.png", &old), ("b.png", &stranger)] {
+            write_png(&input.join(name), 64, 64);
+            embed(owner, &input.join(name), name.as_bytes());
+        }
+        fs::write(input.join("notes.txt"), b"not a carrier").unwrap();
+
+        let report = KeyRotation::new(old.clone(), new.clone())
+            .with_output_dir(&output)
+            .rotate(&[input.clone(), dir.join("missing.png")]);
+
+        let succeeded: Vec<_> = report.succeeded().map(|file| file.destination.clone()).collect();
+        let failed: Vec<_> = report.failed().map(|file| file.source.clone()).collect();
+        assert_eq!(succeeded, [output.join("a.png")]);
+        assert_eq!(failed, [input.join("b.png"), dir.join("missing.png")]);
+        assert!(report.to_string().ends_with("1 rotated, 2 failed"));
+
+        assert_eq!(open(&new, &output.join("a.png")).unwrap(), b"a.png");
+        assert!(open(&old, &output.join("a.png")).is_err());
+        // Sources are left alone, and nothing is written for a failure
+        assert_eq!(open(&old, &input.join("a.png")).unwrap(), b"a.png");
+        let written: Vec<_> = fs::read_dir(&output).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(written, ["a.png"]);
+    }
+
+    #[test]
+    fn in_place_rotation_replaces_the_carrier() {
+        let dir = temp_dir("rotation-in-place");
+        let carrier = dir.join("cover.png");
+        write_png(&carrier, 64, 64);
+        let (old, new) = (CryptoManager::new(), CryptoManager::new());
+        embed(&old, &carrier, b"secret");
+
+        KeyRotation::new(old.clone(), new.clone()).rotate_file(&carrier, &carrier).unwrap();
+        assert_eq!(open(&new, &carrier).unwrap(), b"secret");
+        assert!(open(&old, &carrier).is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn carriers_that_would_share_a_destination_are_refused() {
+        let dir = temp_dir("rotation-collision");
+        let old = CryptoManager::new();
+        for sub in ["one", "two"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+            write_png(&dir.join(sub).join("cover.png"), 64, 64);
+            embed(&old, &dir.join(sub).join("cover.png"), b"secret");
+        }
+
+        let report = KeyRotation::new(old, CryptoManager::new())
+            .with_output_dir(&dir.join("out"))
+            .rotate(&[dir.join("one"), dir.join("two")]);
+        assert_eq!(report.failed().count(), 2);
+        assert!(!dir.join("out").join("cover.png").exists());
+    }
+}
@@ -0,0 +1,169 @@
+//! Spreading one payload over several carriers: the encrypted payload is
+//! split into Shamir shares, one per carrier, and any `threshold` of the
+//! carriers together with the key bring it back.
+
+use std::path::{Path, PathBuf};
+use crate::crypto::shamir::{self, Share};
+use crate::crypto::{CryptoManager, SecretBytes, SignerPublicKey};
+use crate::error::{Result, SteganoError};
+use crate::steganography::{path_str, Carrier, PdfMode};
+
+/// Hides and recovers payloads split across carriers, which can be any mix
+/// of images, audio files and PDFs
+pub struct SecretSharing {
+    crypto_manager: CryptoManager,
+    pdf_mode: PdfMode,
+}
+
+impl SecretSharing {
+    pub fn new(crypto_manager: CryptoManager) -> Self {
+        Self { crypto_manager, pdf_mode: PdfMode::default() }
+    }
+
+    /// How shares are embedded in PDF carriers. Recovery tries every mode.
+    pub fn with_pdf_mode(mut self, pdf_mode: PdfMode) -> Self {
+        self.pdf_mode = pdf_mode;
+        self
+    }
+
+    /// Encrypt `data`, split it into one share per carrier and embed share
+    /// `i` of `carriers[i].0` into `carriers[i].1`. Every carrier is checked
+    /// for room before anything is written.
+    pub fn hide(&self, data: &[u8], threshold: u8, carriers: &[(PathBuf, PathBuf)]) -> Result<()> {
+        let count = u8::try_from(carriers.len()).map_err(|_| SteganoError::InvalidData(
+            format!("At most {} carriers can share a payload", u8::MAX)
+        ))?;
+
+        let encrypted = self.crypto_manager.encrypt(data)?;
+        let shares = shamir::split(&encrypted, threshold, count)?;
+
+        let mut plans = Vec::new();
+        for ((input, output), share) in carriers.iter().zip(&shares) {
+            let input = path_str(input)?;
+            let carrier = Carrier::detect(input, self.pdf_mode).ok_or_else(|| SteganoError::InvalidData(
+                format!("{} is not a supported carrier", input)
+            ))?;
+            let steg = carrier.steganography(input, self.crypto_manager.carrier_key());
+
+            let share = share.to_bytes();
+            if !steg.can_embed(input, share.len())? {
+                return Err(SteganoError::EmbedError(
+                    format!("{} is too small for a {}-byte share", input, share.len())
+                ));
+            }
+            plans.push((steg, input, path_str(output)?, share));
+        }
+
+        for (steg, input, output, share) in plans {
+            steg.embed(input, &share, output)?;
+        }
+        Ok(())
+    }
+
+    /// Extract the shares from `carriers`, rebuild the payload and decrypt
+    /// it. Carriers without a readable share are skipped as long as enough
+    /// of the others remain.
+    pub fn recover(&self, carriers: &[PathBuf]) -> Result<(SecretBytes, Option<SignerPublicKey>)> {
+        let mut shares = Vec::new();
+        let mut errors = Vec::new();
+        for path in carriers {
+            match self.extract_share(path) {
+                Ok(share) => shares.push(share),
+                Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+
+        let payload = shamir::combine(&shares).map_err(|e| match e {
+            SteganoError::InvalidData(message) if !errors.is_empty() => SteganoError::InvalidData(
+                format!("{}; unreadable carriers: {}", message, errors.join("; "))
+            ),
+            e => e,
+        })?;
+        self.crypto_manager.decrypt_signed(&payload)
+    }
+
+    /// The share in one carrier, trying every PDF mode and carrier key
+    fn extract_share(&self, path: &Path) -> Result<Share> {
+        let path = path_str(path)?;
+        let carrier = Carrier::detect(path, self.pdf_mode).ok_or_else(|| SteganoError::InvalidData(
+            format!("{} is not a supported carrier", path)
+        ))?;
+        let public_carrier_key = CryptoManager::public_carrier_key();
+
+        let mut first_error = None;
+        for carrier in carrier.variants() {
+            for stealth_key in self.crypto_manager.carrier_keys().chain([&public_carrier_key]) {
+                let share = carrier
+                    .steganography(path, stealth_key)
+                    .extract(path)
+                    .and_then(|data| Share::from_bytes(&data));
+                match share {
+                    Ok(share) => return Ok(share),
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+        }
+        Err(first_error.expect("every carrier has at least one variant"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{temp_dir, write_png, write_wav};
+
+    /// Three covers, and where each carrier with a share goes
+    fn carriers(dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+        write_png(&dir.join("a.png"), 64, 64);
+        write_wav(&dir.join("b.wav"), 20_000);
+        write_png(&dir.join("c.png"), 64, 64);
+        ["a.png", "b.wav", "c.png"]
+            .iter()
+            .map(|name| (dir.join(name), dir.join(format!("shared-{}", name))))
+            .collect()
+    }
+
+    #[test]
+    fn any_threshold_of_carriers_recovers_the_payload() {
+        let dir = temp_dir("sharing");
+        let carriers = carriers(&dir);
+        let manager = CryptoManager::new();
+        SecretSharing::new(manager.clone()).hide(b"split secret", 2, &carriers).unwrap();
+
+        let outputs: Vec<PathBuf> = carriers.iter().map(|(_, output)| output.clone()).collect();
+        for picked in [[0, 1], [1, 2], [0, 2]] {
+            let subset: Vec<PathBuf> = picked.iter().map(|&i| outputs[i].clone()).collect();
+            let (data, signer) = SecretSharing::new(manager.clone()).recover(&subset).unwrap();
+            assert_eq!(data.expose(), b"split secret");
+            assert!(signer.is_none());
+        }
+    }
+
+    #[test]
+    fn too_few_carriers_report_the_unreadable_ones() {
+        let dir = temp_dir("sharing-few");
+        let carriers = carriers(&dir);
+        let manager = CryptoManager::new();
+        SecretSharing::new(manager.clone()).hide(b"split secret", 2, &carriers).unwrap();
+
+        // A cover without a share counts as unreadable, not as a share
+        let error = SecretSharing::new(manager)
+            .recover(&[carriers[0].1.clone(), carriers[1].0.clone()])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("unreadable carriers") && error.contains("b.wav"), "{}", error);
+    }
+
+    #[test]
+    fn carrier_too_small_for_a_share_writes_nothing() {
+        let dir = temp_dir("sharing-small");
+        let mut carriers = carriers(&dir);
+        write_png(&dir.join("tiny.png"), 4, 4);
+        carriers.push((dir.join("tiny.png"), dir.join("shared-tiny.png")));
+
+        assert!(SecretSharing::new(CryptoManager::new()).hide(b"split secret", 2, &carriers).is_err());
+        assert!(carriers.iter().all(|(_, output)| !output.exists()));
+    }
+}
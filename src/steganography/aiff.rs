@@ -0,0 +1,290 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use crate::crypto::MasterKey;
+use crate::error::{Result, SteganoError};
+use crate::steganography::audio::PcmLayout;
+use crate::steganography::slots::SlotKeys;
+use crate::steganography::traits::{PayloadWriter, Steganography};
+
+/// LSB steganography for AIFF and uncompressed AIFF-C files
+pub struct AiffSteganography {
+    slots: SlotKeys,
+}
+
+impl AiffSteganography {
+    pub fn new() -> Self {
+        AiffSteganography { slots: SlotKeys::default() }
+    }
+
+    /// Hide data in one of two key-selected slots spread over every sample;
+    /// see `AudioSteganography::with_key`
+    pub fn with_key(mut self, key: &MasterKey) -> Self {
+        self.slots.set_key(key);
+        self
+    }
+
+    /// Fill the other slot with `data`, readable with `key` instead of the
+    /// real key
+    pub fn with_decoy(mut self, key: &MasterKey, data: &[u8]) -> Self {
+        self.slots.set_decoy(key, data);
+        self
+    }
+
+    /// Walk the FORM chunks to find the sample format (COMM) and sample data (SSND)
+    fn read_layout(carrier_path: &str) -> Result<PcmLayout> {
+        let mut file = BufReader::new(File::open(carrier_path)?);
+        let file_len = file.get_ref().metadata()?.len();
+
+        let mut header = [0u8; 12];
+        file.read_exact(&mut header)
+            .map_err(|_| Self::invalid("File too short to be an AIFF file"))?;
+        if &header[0..4] != b"FORM" {
+            return Err(Self::invalid("Missing FORM header"));
+        }
+        let is_aifc = match &header[8..12] {
+            b"AIFF" => false,
+            b"AIFC" => true,
+            _ => return Err(Self::invalid("FORM type is neither AIFF nor AIFC")),
+        };
+
+        // (channels, sample frames, bits per sample, little endian)
+        let mut format: Option<(u64, u64, usize, bool)> = None;
+        // (sample data offset, sample data length)
+        let mut sound: Option<(u64, u64)> = None;
+        let mut pos = 12u64;
+
+        while pos + 8 <= file_len {
+            file.seek(SeekFrom::Start(pos))?;
+            let mut chunk_header = [0u8; 8];
+            file.read_exact(&mut chunk_header)?;
+            let chunk_id = &chunk_header[0..4];
+            let chunk_len = u32::from_be_bytes([
+                chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7],
+            ]) as u64;
+            let body_start = pos + 8;
+
+            match chunk_id {
+                b"COMM" => {
+                    let mut comm = vec![0u8; chunk_len.min(64) as usize];
+                    file.read_exact(&mut comm)
+                        .map_err(|_| Self::invalid("Truncated COMM chunk"))?;
+                    format = Some(Self::parse_comm(&comm, is_aifc)?);
+                }
+                b"SSND" => {
+                    let mut ssnd = [0u8; 8];
+                    file.read_exact(&mut ssnd)
+                        .map_err(|_| Self::invalid("Truncated SSND chunk"))?;
+                    let offset = u32::from_be_bytes([ssnd[0], ssnd[1], ssnd[2], ssnd[3]]) as u64;
+                    let data_offset = body_start + 8 + offset;
+                    let chunk_end = (body_start + chunk_len).min(file_len);
+                    sound = Some((data_offset, chunk_end.saturating_sub(data_offset)));
+                }
+                _ => {}
+            }
+
+            // Chunks are padded to an even length
+            pos = body_start + chunk_len + (chunk_len & 1);
+        }
+
+        let (channels, frames, bits, little_endian) = format
+            .ok_or_else(|| Self::invalid("Missing COMM chunk"))?;
+        let (data_offset, available) = sound
+            .ok_or_else(|| Self::invalid("Missing SSND chunk"))?;
+
+        let sample_width = bits.div_ceil(8);
+        let declared = channels * frames * sample_width as u64;
+
+        Ok(PcmLayout {
+            data_offset,
+            data_len: declared.min(available),
+            sample_width,
+            big_endian: !little_endian,
+            lsb_bit: (sample_width * 8 - bits) as u8,
+        })
+    }
+
+    fn parse_comm(comm: &[u8], is_aifc: bool) -> Result<(u64, u64, usize, bool)> {
+        if comm.len() < 18 {
+            return Err(Self::invalid("Truncated COMM chunk"));
+        }
+
+        let channels = u16::from_be_bytes([comm[0], comm[1]]) as u64;
+        let frames = u32::from_be_bytes([comm[2], comm[3], comm[4], comm[5]]) as u64;
+        let bits = u16::from_be_bytes([comm[6], comm[7]]) as usize;
+
+        // Odd sizes such as 12 or 20 bits are left-justified in whole bytes
+        // over zero padding; the layout puts data in the lowest real bit
+        if bits == 0 || bits > 32 {
+            return Err(Self::invalid(&format!("Unsupported sample size: {} bits", bits)));
+        }
+
+        // AIFF-C appends a compression type after the 80-bit sample rate
+        let little_endian = if is_aifc {
+            if comm.len() < 22 {
+                return Err(Self::invalid("Truncated AIFF-C COMM chunk"));
+            }
+            match &comm[18..22] {
+                b"NONE" | b"twos" => false,
+                b"sowt" => true,
+                other => {
+                    return Err(SteganoError::InvalidData(format!(
+                        "Unsupported AIFF-C compression '{}': only uncompressed PCM can carry data",
+                        String::from_utf8_lossy(other)
+                    )))
+                }
+            }
+        } else {
+            false
+        };
+
+        Ok((channels, frames, bits, little_endian))
+    }
+
+    fn invalid(msg: &str) -> SteganoError {
+        SteganoError::InvalidData(format!("Invalid AIFF file: {}", msg))
+    }
+}
+
+impl Default for AiffSteganography {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Steganography for AiffSteganography {
+    fn embed(&self, carrier_path: &str, data: &[u8], output_path: &str) -> Result<()> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.embed(&self.slots, carrier_path, data, output_path)
+    }
+
+    fn embed_stream(&self, carrier_path: &str, write: PayloadWriter, output_path: &str) -> Result<()> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.embed_stream(&self.slots, carrier_path, write, output_path)
+    }
+
+    fn replace(&self, carrier_path: &str, old_key: &MasterKey, data: &[u8], output_path: &str) -> Result<()> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.replace(&self.slots, old_key, carrier_path, data, output_path)
+    }
+
+    fn extract(&self, carrier_path: &str) -> Result<Vec<u8>> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.extract(&self.slots, carrier_path)
+    }
+
+    fn can_embed(&self, carrier_path: &str, data_size: usize) -> Result<bool> {
+        let layout = Self::read_layout(carrier_path)?;
+        let capacity = self.slots.capacity(layout.sample_count());
+        Ok(data_size <= capacity)
+    }
+
+    fn capacity(&self, carrier_path: &str) -> Result<Option<usize>> {
+        let layout = Self::read_layout(carrier_path)?;
+        Ok(Some(self.slots.capacity(layout.sample_count())))
+    }
+
+    fn carrier_digest(&self, carrier_path: &str) -> Result<Option<[u8; 32]>> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.digest(carrier_path).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{path_str, temp_dir};
+    use rand::{rng, Rng};
+    use std::fs;
+    use std::path::Path;
+
+    /// A mono AIFF, or an AIFF-C with `compression`, of `frames` random
+    /// `bits`-bit samples, left-justified, and a chunk after the samples
+    fn write_aiff(path: &Path, compression: Option<&[u8; 4]>, bits: u16, frames: usize) {
+        let width = bits.div_ceil(8) as usize;
+        let mut comm = Vec::new();
+        comm.extend_from_slice(&1u16.to_be_bytes());
+        comm.extend_from_slice(&(frames as u32).to_be_bytes());
+        comm.extend_from_slice(&bits.to_be_bytes());
+        // 8000 Hz as an 80-bit extended float
+        comm.extend_from_slice(&[0x40, 0x0b, 0xfa, 0, 0, 0, 0, 0, 0, 0]);
+        if let Some(compression) = compression {
+            // Compression type, then an empty name padded to an even length
+            comm.extend_from_slice(compression);
+            comm.extend_from_slice(&[0, 0]);
+        }
+
+        let mut samples = vec![0u8; frames * width];
+        rng().fill(&mut samples[..]);
+        let padding = (width * 8) as u16 - bits;
+        let low_byte = if compression == Some(b"sowt") { 0 } else { width - 1 };
+        for sample in samples.chunks_exact_mut(width) {
+            sample[low_byte] &= !((1u16 << padding) - 1) as u8;
+        }
+
+        let mut chunks = Vec::new();
+        let mut chunk = |id: &[u8], body: &[u8]| {
+            chunks.extend_from_slice(id);
+            chunks.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            chunks.extend_from_slice(body);
+            if body.len() % 2 == 1 {
+                chunks.push(0);
+            }
+        };
+        if compression.is_some() {
+            chunk(b"FVER", &0xa280_5140u32.to_be_bytes());
+        }
+        chunk(b"COMM", &comm);
+        chunk(b"SSND", &[&[0u8; 8][..], &samples].concat());
+        chunk(b"ANNO", b"kept as is");
+
+        let form_type = if compression.is_some() { b"AIFC" } else { b"AIFF" };
+        let mut aiff = b"FORM".to_vec();
+        aiff.extend_from_slice(&(4 + chunks.len() as u32).to_be_bytes());
+        aiff.extend_from_slice(form_type);
+        aiff.extend_from_slice(&chunks);
+        fs::write(path, aiff).unwrap();
+    }
+
+    #[test]
+    fn aiff_and_aiff_c_change_only_the_lowest_real_bit() {
+        let dir = temp_dir("aiff");
+        // (compression, bits, byte of each sample holding the LSB, its mask)
+        let cases: [(Option<&[u8; 4]>, u16, usize, u8); 4] = [
+            (None, 16, 1, 0x01),
+            (None, 12, 1, 0x10),
+            (Some(b"NONE"), 16, 1, 0x01),
+            (Some(b"sowt"), 16, 0, 0x01),
+        ];
+        for (case, (compression, bits, lsb_byte, mask)) in cases.into_iter().enumerate() {
+            let (cover, stego) = (dir.join(format!("cover{}.aiff", case)), dir.join(format!("stego{}.aiff", case)));
+            write_aiff(&cover, compression, bits, 20_000);
+
+            let key = MasterKey::generate();
+            let steg = AiffSteganography::new().with_key(&key);
+            steg.embed(path_str(&cover), b"hidden in an aiff", path_str(&stego)).unwrap();
+            assert_eq!(steg.extract(path_str(&stego)).unwrap(), b"hidden in an aiff", "case {}", case);
+            assert!(AiffSteganography::new().extract(path_str(&stego)).is_err());
+
+            let layout = AiffSteganography::read_layout(path_str(&cover)).unwrap();
+            let (start, end) = (layout.data_offset as usize, (layout.data_offset + layout.data_len) as usize);
+            let (before, after) = (fs::read(&cover).unwrap(), fs::read(&stego).unwrap());
+            assert_eq!(before.len(), after.len());
+            assert_eq!(before[..start], after[..start]);
+            assert_eq!(before[end..], after[end..]);
+            for (offset, (a, b)) in before[start..end].iter().zip(&after[start..end]).enumerate() {
+                let allowed = if offset % 2 == lsb_byte { mask } else { 0 };
+                assert_eq!((a ^ b) & !allowed, 0, "case {}", case);
+            }
+            assert_ne!(before, after);
+        }
+    }
+
+    #[test]
+    fn compressed_aiff_c_is_refused() {
+        let dir = temp_dir("aiff-ulaw");
+        let (cover, stego) = (dir.join("cover.aifc"), dir.join("stego.aifc"));
+        write_aiff(&cover, Some(b"ulaw"), 16, 1000);
+        let error = AiffSteganography::new().embed(path_str(&cover), b"data", path_str(&stego)).unwrap_err();
+        assert!(error.to_string().contains("ulaw"), "{}", error);
+    }
+}
@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io::Read;
+use crate::crypto::MasterKey;
+use crate::error::{Result, SteganoError};
+use crate::steganography::audio::PcmLayout;
+use crate::steganography::slots::SlotKeys;
+use crate::steganography::traits::{PayloadWriter, Steganography};
+
+/// Data size value meaning "until end of file"
+const AU_UNKNOWN_SIZE: u32 = 0xFFFF_FFFF;
+
+/// LSB steganography for Sun/NeXT AU (.snd) files with linear PCM encoding
+pub struct AuSteganography {
+    slots: SlotKeys,
+}
+
+impl AuSteganography {
+    pub fn new() -> Self {
+        AuSteganography { slots: SlotKeys::default() }
+    }
+
+    /// Hide data in one of two key-selected slots spread over every sample;
+    /// see `AudioSteganography::with_key`
+    pub fn with_key(mut self, key: &MasterKey) -> Self {
+        self.slots.set_key(key);
+        self
+    }
+
+    /// Fill the other slot with `data`, readable with `key` instead of the
+    /// real key
+    pub fn with_decoy(mut self, key: &MasterKey, data: &[u8]) -> Self {
+        self.slots.set_decoy(key, data);
+        self
+    }
+
+    /// Parse the fixed 24-byte AU header
+    fn read_layout(carrier_path: &str) -> Result<PcmLayout> {
+        let mut file = File::open(carrier_path)?;
+        let file_len = file.metadata()?.len();
+
+        let mut header = [0u8; 24];
+        file.read_exact(&mut header)
+            .map_err(|_| Self::invalid("File too short to be an AU file"))?;
+        if &header[0..4] != b".snd" {
+            return Err(Self::invalid("Missing .snd magic"));
+        }
+
+        let field = |idx: usize| {
+            u32::from_be_bytes([header[idx], header[idx + 1], header[idx + 2], header[idx + 3]])
+        };
+        let data_offset = field(4) as u64;
+        let data_size = field(8);
+        let encoding = field(12);
+
+        // Encodings 2-5 are 8, 16, 24 and 32-bit linear PCM
+        let sample_width = match encoding {
+            2..=5 => (encoding - 1) as usize,
+            other => {
+                return Err(SteganoError::InvalidData(format!(
+                    "Unsupported AU encoding {}: only linear PCM can carry data",
+                    other
+                )))
+            }
+        };
+
+        if data_offset < 24 || data_offset > file_len {
+            return Err(Self::invalid("Data offset lies outside the file"));
+        }
+
+        let available = file_len - data_offset;
+        let data_len = if data_size == AU_UNKNOWN_SIZE {
+            available
+        } else {
+            (data_size as u64).min(available)
+        };
+
+        Ok(PcmLayout {
+            data_offset,
+            data_len,
+            sample_width,
+            big_endian: true,
+            lsb_bit: 0,
+        })
+    }
+
+    fn invalid(msg: &str) -> SteganoError {
+        SteganoError::InvalidData(format!("Invalid AU file: {}", msg))
+    }
+}
+
+impl Default for AuSteganography {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Steganography for AuSteganography {
+    fn embed(&self, carrier_path: &str, data: &[u8], output_path: &str) -> Result<()> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.embed(&self.slots, carrier_path, data, output_path)
+    }
+
+    fn embed_stream(&self, carrier_path: &str, write: PayloadWriter, output_path: &str) -> Result<()> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.embed_stream(&self.slots, carrier_path, write, output_path)
+    }
+
+    fn replace(&self, carrier_path: &str, old_key: &MasterKey, data: &[u8], output_path: &str) -> Result<()> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.replace(&self.slots, old_key, carrier_path, data, output_path)
+    }
+
+    fn extract(&self, carrier_path: &str) -> Result<Vec<u8>> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.extract(&self.slots, carrier_path)
+    }
+
+    fn can_embed(&self, carrier_path: &str, data_size: usize) -> Result<bool> {
+        let layout = Self::read_layout(carrier_path)?;
+        let capacity = self.slots.capacity(layout.sample_count());
+        Ok(data_size <= capacity)
+    }
+
+    fn capacity(&self, carrier_path: &str) -> Result<Option<usize>> {
+        let layout = Self::read_layout(carrier_path)?;
+        Ok(Some(self.slots.capacity(layout.sample_count())))
+    }
+
+    fn carrier_digest(&self, carrier_path: &str) -> Result<Option<[u8; 32]>> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.digest(carrier_path).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{path_str, temp_dir};
+    use rand::{rng, Rng};
+    use std::fs;
+    use std::path::Path;
+
+    /// An AU file of `samples` random mono samples in `encoding`, with an
+    /// annotation after the header and bytes after the declared data
+    fn write_au(path: &Path, encoding: u32, samples: usize) {
+        let width = match encoding {
+            2..=5 => (encoding - 1) as usize,
+            _ => 1,
+        };
+        let annotation = b"note\0\0\0\0";
+        let mut data = vec![0u8; samples * width];
+        rng().fill(&mut data[..]);
+
+        let mut au = b".snd".to_vec();
+        for field in [24 + annotation.len() as u32, data.len() as u32, encoding, 8000, 1] {
+            au.extend_from_slice(&field.to_be_bytes());
+        }
+        au.extend_from_slice(annotation);
+        au.extend_from_slice(&data);
+        au.extend_from_slice(b"past the data");
+        fs::write(path, au).unwrap();
+    }
+
+    #[test]
+    fn linear_pcm_round_trips_and_keeps_everything_but_the_lsbs() {
+        let dir = temp_dir("au");
+        for encoding in 2..=5 {
+            let (cover, stego) = (dir.join(format!("cover{}.au", encoding)), dir.join(format!("stego{}.au", encoding)));
+            write_au(&cover, encoding, 20_000);
+
+            let key = MasterKey::generate();
+            let steg = AuSteganography::new().with_key(&key);
+            steg.embed(path_str(&cover), b"hidden in an au", path_str(&stego)).unwrap();
+            assert_eq!(steg.extract(path_str(&stego)).unwrap(), b"hidden in an au", "encoding {}", encoding);
+            assert!(AuSteganography::new().extract(path_str(&stego)).is_err());
+
+            // Samples are big-endian, so the LSB is in the last byte of each
+            let layout = AuSteganography::read_layout(path_str(&cover)).unwrap();
+            let width = layout.sample_width;
+            let (start, end) = (layout.data_offset as usize, (layout.data_offset + layout.data_len) as usize);
+            let (before, after) = (fs::read(&cover).unwrap(), fs::read(&stego).unwrap());
+            assert_eq!(before.len(), after.len());
+            assert_eq!(before[..start], after[..start]);
+            assert_eq!(before[end..], after[end..]);
+            for (offset, (a, b)) in before[start..end].iter().zip(&after[start..end]).enumerate() {
+                let allowed = if offset % width == width - 1 { 1 } else { 0 };
+                assert_eq!((a ^ b) & !allowed, 0, "encoding {}", encoding);
+            }
+            assert_ne!(before, after);
+        }
+    }
+
+    #[test]
+    fn non_linear_encodings_are_refused() {
+        let dir = temp_dir("au-ulaw");
+        let (cover, stego) = (dir.join("cover.au"), dir.join("stego.au"));
+        // Encoding 1 is 8-bit mu-law
+        write_au(&cover, 1, 1000);
+        assert!(AuSteganography::new().embed(path_str(&cover), b"data", path_str(&stego)).is_err());
+    }
+}
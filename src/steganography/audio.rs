@@ -1,137 +1,478 @@
-use hound::{WavReader, WavWriter};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use hound::WavReader;
+use sha2::{Digest, Sha256};
+use crate::crypto::MasterKey;
 use crate::error::{Result, SteganoError};
-use crate::steganography::traits::Steganography;
+use crate::steganography::slots::{LsbPlane, LsbSource, SlotKeys};
+use crate::steganography::traits::{PayloadWriter, Steganography};
 
-pub struct AudioSteganography;
+/// Size of the buffer used when streaming raw PCM sample data
+const PCM_CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct AudioSteganography {
+    slots: SlotKeys,
+}
 
 impl AudioSteganography {
     pub fn new() -> Self {
-        AudioSteganography
+        AudioSteganography { slots: SlotKeys::default() }
     }
 
-    fn calculate_capacity(sample_count: usize) -> usize {
-        // Each sample can hold 1 bit
-        // Subtract 32 bits for length prefix
-        if sample_count < 32 {
-            0
-        } else {
-            (sample_count - 32) / 8
+    /// Hide data in one of two slots spread over every sample, selected and
+    /// masked by `key` rather than the public carrier key
+    pub fn with_key(mut self, key: &MasterKey) -> Self {
+        self.slots.set_key(key);
+        self
+    }
+
+    /// Fill the other slot with `data`, readable with `key` instead of the
+    /// real key
+    pub fn with_decoy(mut self, key: &MasterKey, data: &[u8]) -> Self {
+        self.slots.set_decoy(key, data);
+        self
+    }
+
+    /// Where hound finds the samples of a WAV file, so they can be rewritten
+    /// in place and extraction can seek straight to the ones it needs
+    fn read_layout(carrier_path: &str) -> Result<PcmLayout> {
+        let reader = WavReader::open(carrier_path)?;
+        let spec = reader.spec();
+        let sample_count = reader.len() as u64;
+        let data_offset = reader.into_inner().stream_position()?;
+
+        // hound reports the bits a sample uses; the block alignment gives the
+        // whole bytes it is stored in, with the used bits left-justified
+        let bits = spec.bits_per_sample as usize;
+        let sample_width = Self::block_align(carrier_path)? as usize / spec.channels.max(1) as usize;
+        if bits == 0 || bits > sample_width * 8 {
+            return Err(SteganoError::InvalidData(format!(
+                "Invalid WAV file: {} bits per sample do not fit in {} bytes",
+                bits, sample_width
+            )));
+        }
+
+        Ok(PcmLayout {
+            data_offset,
+            data_len: sample_count * sample_width as u64,
+            sample_width,
+            big_endian: false,
+            lsb_bit: (sample_width * 8 - bits) as u8,
+        })
+    }
+
+    /// Block alignment (bytes per frame) from the fmt chunk, which hound has
+    /// already found before the data chunk
+    fn block_align(carrier_path: &str) -> Result<u16> {
+        let mut file = BufReader::new(File::open(carrier_path)?);
+        let mut pos = 12u64;
+        loop {
+            file.seek(SeekFrom::Start(pos))?;
+            let mut chunk_header = [0u8; 8];
+            file.read_exact(&mut chunk_header)?;
+            let chunk_len = u32::from_le_bytes([
+                chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7],
+            ]) as u64;
+
+            if &chunk_header[0..4] == b"fmt " {
+                let mut fmt = [0u8; 14];
+                file.read_exact(&mut fmt)?;
+                return Ok(u16::from_le_bytes([fmt[12], fmt[13]]));
+            }
+
+            // Chunks are padded to an even length
+            pos += 8 + chunk_len + (chunk_len & 1);
         }
     }
 }
 
-impl Steganography for AudioSteganography {
-    fn embed(&self, carrier_path: &str, data: &[u8], output_path: &str) -> Result<()> {
-        let mut reader = WavReader::open(carrier_path)?;
-        let spec = reader.spec();
-        let samples: Vec<i16> = reader
-            .samples::<i16>()
-            .collect::<std::result::Result<Vec<_>, _>>()?;
+impl Default for AudioSteganography {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        // Check capacity
-        if samples.len() < 32 {
-            return Err(SteganoError::EmbedError(
-                "Audio file too short to embed data".to_string()
-            ));
+/// Location and sample encoding of uncompressed PCM data inside a container file
+/// whose headers are copied through untouched (WAV, AIFF, AU)
+pub(crate) struct PcmLayout {
+    /// Byte offset of the first sample
+    pub data_offset: u64,
+    /// Length of the sample data in bytes
+    pub data_len: u64,
+    /// Bytes per sample (1 to 4)
+    pub sample_width: usize,
+    /// Whether multi-byte samples are stored most significant byte first
+    pub big_endian: bool,
+    /// Bit of the least significant byte that holds the sample's LSB: samples
+    /// narrower than their container (12 or 20 bits, say) are left-justified
+    /// over zero padding
+    pub lsb_bit: u8,
+}
+
+impl PcmLayout {
+    pub(crate) fn sample_count(&self) -> usize {
+        (self.data_len / self.sample_width as u64) as usize
+    }
+
+    /// Offset of the byte holding the least significant bit within a sample
+    fn lsb_offset(&self) -> usize {
+        if self.big_endian {
+            self.sample_width - 1
+        } else {
+            0
         }
+    }
+
+    fn lsb_mask(&self) -> u8 {
+        1 << self.lsb_bit
+    }
 
-        let capacity = Self::calculate_capacity(samples.len());
-        if data.len() > capacity {
+    /// Copy `carrier_path` to `output_path`, hiding `data` in the slots of
+    /// the sample LSBs
+    pub(crate) fn embed(&self, slots: &SlotKeys, carrier_path: &str, data: &[u8], output_path: &str) -> Result<()> {
+        if data.len() > slots.capacity(self.sample_count()) {
             return Err(SteganoError::EmbedError(
                 format!("Audio file too small to embed {} bytes of data", data.len())
             ));
         }
+        self.embed_stream(slots, carrier_path, &mut |writer| Ok(writer.write_all(data)?), output_path)
+    }
+
+    /// Copy `carrier_path` to `output_path`, hiding the payload `write`
+    /// produces in the slots of the sample LSBs
+    pub(crate) fn embed_stream(
+        &self,
+        slots: &SlotKeys,
+        carrier_path: &str,
+        write: PayloadWriter,
+        output_path: &str,
+    ) -> Result<()> {
+        self.write(slots.writer(self.sample_count(), write)?, carrier_path, output_path)
+    }
 
-        let mut modified_samples = samples.clone();
-        
-        // Embed length (4 bytes = 32 bits)
-        let len_bytes = (data.len() as u32).to_le_bytes();
-        let mut bit_count = 0;
-        
-        for byte_idx in 0..4 {
-            for bit_idx in 0..8 {
-                let bit = (len_bytes[byte_idx] >> bit_idx) & 1;
-                modified_samples[bit_count] = 
-                    (modified_samples[bit_count] & !1) | (bit as i16);
-                bit_count += 1;
+    /// Copy `carrier_path` to `output_path`, hiding `data` in place of the
+    /// payload under `old_key` and keeping whatever the other slot holds
+    pub(crate) fn replace(
+        &self,
+        slots: &SlotKeys,
+        old_key: &MasterKey,
+        carrier_path: &str,
+        data: &[u8],
+        output_path: &str,
+    ) -> Result<()> {
+        let plane = slots.replacer(&mut self.lsbs(carrier_path)?, old_key, &mut |writer| Ok(writer.write_all(data)?))?;
+        self.write(plane, carrier_path, output_path)
+    }
+
+    /// Copy `carrier_path` to `output_path` with every sample LSB set from `plane`
+    fn write(&self, plane: LsbPlane, carrier_path: &str, output_path: &str) -> Result<()> {
+        let mut reader = BufReader::new(File::open(carrier_path)?);
+        let mut writer = BufWriter::new(File::create(output_path)?);
+
+        // Headers and any chunks before the samples are preserved byte for byte
+        io::copy(&mut (&mut reader).take(self.data_offset), &mut writer)?;
+
+        let (lsb_offset, lsb_mask) = (self.lsb_offset(), self.lsb_mask());
+        let chunk_len = PCM_CHUNK_SIZE - PCM_CHUNK_SIZE % self.sample_width;
+        let mut buffer = vec![0u8; chunk_len];
+        let mut remaining = self.sample_count() as u64 * self.sample_width as u64;
+        let mut position = 0;
+
+        while remaining > 0 {
+            let len = remaining.min(chunk_len as u64) as usize;
+            reader.read_exact(&mut buffer[..len])?;
+
+            for sample in buffer[..len].chunks_exact_mut(self.sample_width) {
+                let byte = &mut sample[lsb_offset];
+                *byte = (*byte & !lsb_mask) | (plane.bit(position) << self.lsb_bit);
+                position += 1;
             }
+
+            writer.write_all(&buffer[..len])?;
+            remaining -= len as u64;
         }
-        
-        // Embed the actual data
-        for byte_idx in 0..data.len() {
-            for bit_idx in 0..8 {
-                let bit = (data[byte_idx] >> bit_idx) & 1;
-                modified_samples[bit_count] = 
-                    (modified_samples[bit_count] & !1) | (bit as i16);
-                bit_count += 1;
+
+        // Trailing chunks and padding follow the samples unchanged
+        io::copy(&mut reader, &mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Recover data hidden in the sample LSBs of `carrier_path`, reading
+    /// only the samples that the slot headers and the payload occupy
+    pub(crate) fn extract(&self, slots: &SlotKeys, carrier_path: &str) -> Result<Vec<u8>> {
+        slots.extract(&mut self.lsbs(carrier_path)?)
+    }
+
+    fn lsbs(&self, carrier_path: &str) -> Result<PcmLsbs<'_>> {
+        let mut reader = BufReader::new(File::open(carrier_path)?);
+        reader.seek(SeekFrom::Start(self.data_offset))?;
+        Ok(PcmLsbs { layout: self, reader, offset: self.data_offset })
+    }
+
+    /// Hash the whole file with the sample LSBs cleared; everything else is
+    /// copied through unchanged by [`PcmLayout::embed`]
+    pub(crate) fn digest(&self, carrier_path: &str) -> Result<[u8; 32]> {
+        let mut reader = BufReader::new(File::open(carrier_path)?);
+        let mut hasher = Sha256::new();
+
+        let mut header = Vec::new();
+        (&mut reader).take(self.data_offset).read_to_end(&mut header)?;
+        hasher.update(&header);
+
+        let (lsb_offset, lsb_mask) = (self.lsb_offset(), self.lsb_mask());
+        let chunk_len = PCM_CHUNK_SIZE - PCM_CHUNK_SIZE % self.sample_width;
+        let mut buffer = vec![0u8; chunk_len];
+        let mut remaining = self.sample_count() as u64 * self.sample_width as u64;
+
+        while remaining > 0 {
+            let len = remaining.min(chunk_len as u64) as usize;
+            reader.read_exact(&mut buffer[..len])?;
+            for sample in buffer[..len].chunks_exact_mut(self.sample_width) {
+                sample[lsb_offset] &= !lsb_mask;
             }
+            hasher.update(&buffer[..len]);
+            remaining -= len as u64;
         }
 
-        let mut writer = WavWriter::create(output_path, spec)?;
-        for sample in modified_samples {
-            writer.write_sample(sample)?;
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest)?;
+        hasher.update(&rest);
+
+        Ok(hasher.finalize().into())
+    }
+}
+
+/// The sample LSBs of a PCM file, read by seeking forward to each sample
+struct PcmLsbs<'a> {
+    layout: &'a PcmLayout,
+    reader: BufReader<File>,
+    /// Current offset of `reader` in the file
+    offset: u64,
+}
+
+impl LsbSource for PcmLsbs<'_> {
+    fn bits(&self) -> usize {
+        self.layout.sample_count()
+    }
+
+    fn read(&mut self, positions: &[usize]) -> Result<Vec<u8>> {
+        let (lsb_offset, lsb_mask) = (self.layout.lsb_offset(), self.layout.lsb_mask());
+        let mut bits = Vec::with_capacity(positions.len());
+        for &position in positions {
+            let target = self.layout.data_offset
+                + position as u64 * self.layout.sample_width as u64
+                + lsb_offset as u64;
+            // Positions ascend within a read, so this is a short skip inside
+            // the buffer whenever they are close together
+            self.reader.seek_relative(target as i64 - self.offset as i64)?;
+            let mut byte = [0u8];
+            self.reader.read_exact(&mut byte)?;
+            self.offset = target + 1;
+            bits.push((byte[0] & lsb_mask) >> self.layout.lsb_bit);
         }
-        writer.finalize()?;
-        
-        Ok(())
+        Ok(bits)
+    }
+}
+
+impl Steganography for AudioSteganography {
+    fn embed(&self, carrier_path: &str, data: &[u8], output_path: &str) -> Result<()> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.embed(&self.slots, carrier_path, data, output_path)
+    }
+
+    fn embed_stream(&self, carrier_path: &str, write: PayloadWriter, output_path: &str) -> Result<()> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.embed_stream(&self.slots, carrier_path, write, output_path)
+    }
+
+    /// Reuse the old payload's slot, keeping whatever the other slot holds
+    fn replace(&self, carrier_path: &str, old_key: &MasterKey, data: &[u8], output_path: &str) -> Result<()> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.replace(&self.slots, old_key, carrier_path, data, output_path)
     }
 
     fn extract(&self, carrier_path: &str) -> Result<Vec<u8>> {
-        let mut reader = WavReader::open(carrier_path)?;
-        let samples: Vec<i16> = reader
-            .samples::<i16>()
-            .collect::<std::result::Result<Vec<_>, _>>()?;
-
-        if samples.len() < 32 {
-            return Err(SteganoError::ExtractError(
-                "Audio file too short to extract data".to_string()
-            ));
-        }
-        
-        // Extract length (4 bytes = 32 bits)
-        let mut len_bytes = [0u8; 4];
-        let mut bit_count = 0;
-        
-        for byte_idx in 0..4 {
-            let mut byte = 0u8;
-            for bit_idx in 0..8 {
-                let bit = (samples[bit_count] & 1) as u8;
-                byte |= bit << bit_idx;
-                bit_count += 1;
+        let layout = Self::read_layout(carrier_path)?;
+        layout.extract(&self.slots, carrier_path)
+    }
+
+    fn can_embed(&self, carrier_path: &str, data_size: usize) -> Result<bool> {
+        let layout = Self::read_layout(carrier_path)?;
+        let capacity = self.slots.capacity(layout.sample_count());
+        Ok(data_size <= capacity)
+    }
+
+    fn capacity(&self, carrier_path: &str) -> Result<Option<usize>> {
+        let layout = Self::read_layout(carrier_path)?;
+        Ok(Some(self.slots.capacity(layout.sample_count())))
+    }
+
+    /// The header is copied through unchanged, so it is hashed as it is
+    fn carrier_digest(&self, carrier_path: &str) -> Result<Option<[u8; 32]>> {
+        let layout = Self::read_layout(carrier_path)?;
+        layout.digest(carrier_path).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{path_str, temp_dir, write_wav};
+    use hound::{SampleFormat, WavSpec, WavWriter};
+    use rand::{rng, Rng};
+    use std::fs;
+    use std::path::Path;
+
+    /// A mono WAV of random samples of a whole number of bytes
+    fn write_wav_bits(path: &Path, bits: u16, samples: usize) {
+        let spec = WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: bits, sample_format: SampleFormat::Int };
+        let mut writer = WavWriter::create(path, spec).unwrap();
+        let mut random = rng();
+        let range = 1i64 << (bits - 1);
+        for _ in 0..samples {
+            let sample = random.random_range(-range..range) as i32;
+            if bits == 8 {
+                writer.write_sample(sample as i8).unwrap();
+            } else {
+                writer.write_sample(sample).unwrap();
             }
-            len_bytes[byte_idx] = byte;
         }
-        
-        let data_len = u32::from_le_bytes(len_bytes) as usize;
-        
-        // Validate data length
-        let capacity = Self::calculate_capacity(samples.len());
-        if data_len > capacity {
-            return Err(SteganoError::ExtractError(
-                "Corrupted data: claimed length exceeds audio file capacity".to_string()
-            ));
+        writer.finalize().unwrap();
+    }
+
+    /// A mono WAVE_FORMAT_EXTENSIBLE file of 20-bit samples, left-justified
+    /// in 3 bytes over four zero bits
+    fn write_wav_20_bit(path: &Path, samples: usize) {
+        let data_len = samples as u32 * 3;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(4 + 8 + 40 + 8 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&40u32.to_le_bytes());
+        wav.extend_from_slice(&0xfffeu16.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&8000u32.to_le_bytes());
+        wav.extend_from_slice(&(8000u32 * 3).to_le_bytes());
+        wav.extend_from_slice(&3u16.to_le_bytes());
+        wav.extend_from_slice(&24u16.to_le_bytes());
+        wav.extend_from_slice(&22u16.to_le_bytes());
+        wav.extend_from_slice(&20u16.to_le_bytes());
+        wav.extend_from_slice(&4u32.to_le_bytes());
+        // KSDATAFORMAT_SUBTYPE_PCM
+        wav.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0x10, 0, 0x80, 0, 0, 0xaa, 0, 0x38, 0x9b, 0x71]);
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        let mut random = rng();
+        for _ in 0..samples {
+            let sample = random.random::<u32>() << 4;
+            wav.extend_from_slice(&sample.to_le_bytes()[..3]);
         }
-        
-        // Extract the actual data
-        let mut extracted_data = vec![0u8; data_len];
-        
-        for byte_idx in 0..data_len {
-            let mut byte = 0u8;
-            for bit_idx in 0..8 {
-                let bit = (samples[bit_count] & 1) as u8;
-                byte |= bit << bit_idx;
-                bit_count += 1;
-            }
-            extracted_data[byte_idx] = byte;
+        fs::write(path, wav).unwrap();
+    }
+
+    /// Counts the LSB positions a layout reads
+    struct Counting<'a> {
+        inner: PcmLsbs<'a>,
+        read: usize,
+    }
+
+    impl LsbSource for Counting<'_> {
+        fn bits(&self) -> usize {
+            self.inner.bits()
         }
 
-        Ok(extracted_data)
+        fn read(&mut self, positions: &[usize]) -> Result<Vec<u8>> {
+            self.read += positions.len();
+            self.inner.read(positions)
+        }
     }
 
-    fn can_embed(&self, carrier_path: &str, data_size: usize) -> Result<bool> {
-        let reader = WavReader::open(carrier_path)?;
-        let sample_count = reader.len() as usize;
-        let capacity = Self::calculate_capacity(sample_count);
-        Ok(data_size <= capacity)
+    fn samples(path: &Path) -> Vec<i32> {
+        WavReader::open(path).unwrap().samples::<i32>().map(|sample| sample.unwrap()).collect()
+    }
+
+    #[test]
+    fn wav_round_trips_and_keeps_its_header() {
+        let dir = temp_dir("wav-round-trip");
+        let (cover, stego) = (dir.join("cover.wav"), dir.join("stego.wav"));
+        write_wav(&cover, 20_000);
+
+        let key = MasterKey::generate();
+        let steg = AudioSteganography::new().with_key(&key);
+        steg.embed(path_str(&cover), b"hidden in a wav", path_str(&stego)).unwrap();
+        assert_eq!(steg.extract(path_str(&stego)).unwrap(), b"hidden in a wav");
+        assert!(AudioSteganography::new().extract(path_str(&stego)).is_err());
+
+        let (cover_bytes, stego_bytes) = (fs::read(&cover).unwrap(), fs::read(&stego).unwrap());
+        let data_offset = AudioSteganography::read_layout(path_str(&cover)).unwrap().data_offset as usize;
+        assert_eq!(cover_bytes.len(), stego_bytes.len());
+        assert_eq!(cover_bytes[..data_offset], stego_bytes[..data_offset]);
+        assert_eq!(
+            steg.carrier_digest(path_str(&cover)).unwrap(),
+            steg.carrier_digest(path_str(&stego)).unwrap()
+        );
+    }
+
+    #[test]
+    fn whole_byte_sample_sizes_change_only_the_lowest_bit() {
+        let dir = temp_dir("wav-sample-sizes");
+        for bits in [8, 16, 24, 32] {
+            let (cover, stego) = (dir.join(format!("cover{}.wav", bits)), dir.join(format!("stego{}.wav", bits)));
+            write_wav_bits(&cover, bits, 20_000);
+
+            let steg = AudioSteganography::new();
+            steg.embed(path_str(&cover), b"sample sizes", path_str(&stego)).unwrap();
+            assert_eq!(steg.extract(path_str(&stego)).unwrap(), b"sample sizes", "{} bits", bits);
+
+            let (before, after) = (samples(&cover), samples(&stego));
+            assert_eq!(before.len(), after.len());
+            assert!(before.iter().zip(&after).all(|(a, b)| (a ^ b) & !1 == 0), "{} bits", bits);
+            assert_ne!(before, after);
+        }
+    }
+
+    #[test]
+    fn left_justified_samples_change_their_lowest_real_bit() {
+        let dir = temp_dir("wav-20-bit");
+        let (cover, stego) = (dir.join("cover.wav"), dir.join("stego.wav"));
+        write_wav_20_bit(&cover, 20_000);
+
+        let steg = AudioSteganography::new();
+        steg.embed(path_str(&cover), b"twenty bits", path_str(&stego)).unwrap();
+        assert_eq!(steg.extract(path_str(&stego)).unwrap(), b"twenty bits");
+
+        let layout = AudioSteganography::read_layout(path_str(&cover)).unwrap();
+        assert_eq!((layout.sample_width, layout.lsb_bit), (3, 4));
+        let data_offset = layout.data_offset as usize;
+        let (before, after) = (fs::read(&cover).unwrap(), fs::read(&stego).unwrap());
+        let lowest_bytes = |wav: &[u8]| wav[data_offset..].iter().step_by(3).copied().collect::<Vec<u8>>();
+        let (before, after) = (lowest_bytes(&before), lowest_bytes(&after));
+        assert!(after.iter().all(|byte| byte & 0x0f == 0));
+        assert!(before.iter().zip(&after).all(|(a, b)| (a ^ b) & !0x10 == 0));
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn long_recordings_stream_through_and_extraction_reads_only_the_payload() {
+        let dir = temp_dir("wav-long");
+        let (cover, stego) = (dir.join("cover.wav"), dir.join("stego.wav"));
+        // Several chunks of 16-bit samples
+        let sample_count = 3 * PCM_CHUNK_SIZE + 123;
+        write_wav(&cover, sample_count);
+
+        let data = vec![0x5a; 100];
+        let steg = AudioSteganography::new();
+        steg.embed(path_str(&cover), &data, path_str(&stego)).unwrap();
+        let (before, after) = (samples(&cover), samples(&stego));
+        assert_eq!(after.len(), sample_count);
+        assert!(before.iter().zip(&after).all(|(a, b)| (a ^ b) & !1 == 0));
+
+        // Both headers and the payload, not the whole recording
+        let layout = AudioSteganography::read_layout(path_str(&stego)).unwrap();
+        let mut lsbs = Counting { inner: layout.lsbs(path_str(&stego)).unwrap(), read: 0 };
+        assert_eq!(steg.slots.extract(&mut lsbs).unwrap(), data);
+        assert!(lsbs.read < 2 * 30 * 8 + data.len() * 8 + 64, "read {} LSBs", lsbs.read);
     }
 }
@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+use crate::crypto::{MasterKey, PayloadContext, SecretKey};
+use crate::error::{Result, SteganoError};
+use super::aiff::AiffSteganography;
+use super::au::AuSteganography;
+use super::audio::AudioSteganography;
+use super::image::ImageSteganography;
+use super::pdf::{PdfMode, PdfSteganography};
+use super::traits::Steganography;
+
+/// The kind of file a payload is hidden in, picked by file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Carrier {
+    Image,
+    Audio,
+    Pdf(PdfMode),
+}
+
+impl Carrier {
+    /// Carrier for `path`, or `None` for extensions no method handles
+    pub fn detect(path: &str, pdf_mode: PdfMode) -> Option<Self> {
+        match extension(path).as_deref() {
+            Some("png") | Some("bmp") | Some("tif") | Some("tiff") | Some("jpg") | Some("jpeg") => {
+                Some(Carrier::Image)
+            }
+            Some("wav") | Some("aif") | Some("aiff") | Some("aifc") | Some("au") | Some("snd") => {
+                Some(Carrier::Audio)
+            }
+            Some("pdf") => Some(Carrier::Pdf(pdf_mode)),
+            _ => None,
+        }
+    }
+
+    /// This carrier followed by the same kind with other parameters, to try
+    /// when extracting without knowing how the payload was embedded
+    pub fn variants(&self) -> Vec<Carrier> {
+        match self {
+            Carrier::Pdf(mode) => std::iter::once(*mode)
+                .chain(PdfMode::ALL.into_iter().filter(|other| other != mode))
+                .map(Carrier::Pdf)
+                .collect(),
+            carrier => vec![*carrier],
+        }
+    }
+
+    /// Path for a copy of the carrier at `path` that holds hidden data. JPEG
+    /// would discard the LSBs on saving, so JPEG covers are written as PNG.
+    pub fn output_path(path: &Path) -> PathBuf {
+        match path.to_str().and_then(extension).as_deref() {
+            Some("jpg") | Some("jpeg") => path.with_extension("png"),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// Name recorded in a bound context
+    pub fn name(&self) -> &'static str {
+        match self {
+            Carrier::Image => "image",
+            Carrier::Audio => "audio",
+            Carrier::Pdf(_) => "pdf",
+        }
+    }
+
+    /// The method for this carrier. `carrier_key` masks PDF payloads and
+    /// picks the slot of image and audio payloads.
+    pub fn steganography(&self, path: &str, carrier_key: &MasterKey) -> Box<dyn Steganography> {
+        match self {
+            Carrier::Image => Box::new(ImageSteganography::new().with_key(carrier_key)),
+            Carrier::Audio => audio_steganography(path, carrier_key, None),
+            Carrier::Pdf(mode) => Box::new(PdfSteganography::with_mode(*mode).with_stealth_key(carrier_key)),
+        }
+    }
+
+    /// The method for this carrier with `decoy_data` hidden next to the real
+    /// payload, readable with `decoy_key`. Only image and audio carriers have
+    /// a second slot.
+    pub fn steganography_with_decoy(
+        &self,
+        path: &str,
+        carrier_key: &MasterKey,
+        decoy_key: &MasterKey,
+        decoy_data: &[u8],
+    ) -> Result<Box<dyn Steganography>> {
+        match self {
+            Carrier::Image => Ok(Box::new(
+                ImageSteganography::new().with_key(carrier_key).with_decoy(decoy_key, decoy_data)
+            )),
+            Carrier::Audio => Ok(audio_steganography(path, carrier_key, Some((decoy_key, decoy_data)))),
+            Carrier::Pdf(_) => Err(SteganoError::InvalidData(
+                "Decoy payloads can only be hidden in image and audio carriers".to_string()
+            )),
+        }
+    }
+
+    /// Context binding a payload to the carrier at `path`: its kind, the PDF
+    /// mode and the digest of everything embedding leaves unchanged
+    pub fn bound_context(&self, path: &str, context: PayloadContext) -> Result<PayloadContext> {
+        let mut context = context;
+        if let Carrier::Pdf(mode) = self {
+            context = context.with_parameters(&format!("mode={}", mode.name()));
+        }
+
+        // The stealth key plays no part in the digest
+        let steg = self.steganography(path, &MasterKey::new(SecretKey::new(&mut [0u8; 32])));
+        let digest = steg.carrier_digest(path)?.ok_or_else(|| SteganoError::InvalidData(
+            format!("Cannot bind a payload to this {} carrier", self.name())
+        ))?;
+
+        Ok(context.with_carrier(self.name()).with_carrier_digest(digest))
+    }
+}
+
+/// WAV unless the extension names AIFF or AU, keyed with `carrier_key` and
+/// holding the decoy if one is given
+pub fn audio_steganography(
+    path: &str,
+    carrier_key: &MasterKey,
+    decoy: Option<(&MasterKey, &[u8])>,
+) -> Box<dyn Steganography> {
+    match extension(path).as_deref() {
+        Some("aif") | Some("aiff") | Some("aifc") => {
+            let steg = AiffSteganography::new().with_key(carrier_key);
+            Box::new(match decoy {
+                Some((key, data)) => steg.with_decoy(key, data),
+                None => steg,
+            })
+        }
+        Some("au") | Some("snd") => {
+            let steg = AuSteganography::new().with_key(carrier_key);
+            Box::new(match decoy {
+                Some((key, data)) => steg.with_decoy(key, data),
+                None => steg,
+            })
+        }
+        _ => {
+            let steg = AudioSteganography::new().with_key(carrier_key);
+            Box::new(match decoy {
+                Some((key, data)) => steg.with_decoy(key, data),
+                None => steg,
+            })
+        }
+    }
+}
+
+/// `path` as a string, which the steganography methods take
+pub(crate) fn path_str(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| SteganoError::InvalidData(
+        format!("{} is not a valid UTF-8 path", path.display())
+    ))
+}
+
+fn extension(path: &str) -> Option<String> {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+}
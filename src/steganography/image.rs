@@ -1,142 +1,135 @@
-use image::{GenericImage, GenericImageView};
+use image::{DynamicImage, GenericImage, GenericImageView};
+use sha2::{Digest, Sha256};
+use crate::crypto::MasterKey;
 use crate::error::{Result, SteganoError};
-use crate::steganography::traits::Steganography;
+use crate::steganography::slots::{LsbPlane, LsbSource, SlotKeys};
+use crate::steganography::traits::{PayloadWriter, Steganography};
 
-pub struct ImageSteganography;
+pub struct ImageSteganography {
+    slots: SlotKeys,
+}
 
 impl ImageSteganography {
     pub fn new() -> Self {
-        ImageSteganography
+        ImageSteganography { slots: SlotKeys::default() }
+    }
+
+    /// Hide data in one of two slots spread over every pixel, selected and
+    /// masked by `key` rather than the public carrier key
+    pub fn with_key(mut self, key: &MasterKey) -> Self {
+        self.slots.set_key(key);
+        self
     }
 
-    fn calculate_capacity(width: u32, height: u32) -> usize {
-        // Each pixel has 3 color channels (RGB), each can hold 1 bit
-        // Subtract 32 bits for length prefix
-        let total_bits = (width as usize) * (height as usize) * 3;
-        (total_bits - 32) / 8
+    /// Fill the other slot with `data`, readable with `key` instead of the
+    /// real key
+    pub fn with_decoy(mut self, key: &MasterKey, data: &[u8]) -> Self {
+        self.slots.set_decoy(key, data);
+        self
+    }
+
+    /// Set the LSBs of `img` from `plane` and save it. Each pixel holds a bit
+    /// of one slot or the other, in one of its color channels in turn.
+    fn write(mut img: DynamicImage, plane: LsbPlane, output_path: &str) -> Result<()> {
+        let width = img.width();
+        for position in 0..plane.bits() {
+            let (x, y) = pixel_at(position, width);
+            let color_idx = position % 3;
+
+            let mut pixel = img.get_pixel(x, y);
+            pixel[color_idx] = (pixel[color_idx] & 0xFE) | plane.bit(position);
+            img.put_pixel(x, y, pixel);
+        }
+
+        img.save(output_path)?;
+        Ok(())
+    }
+}
+
+impl Default for ImageSteganography {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Steganography for ImageSteganography {
     fn embed(&self, carrier_path: &str, data: &[u8], output_path: &str) -> Result<()> {
-        let mut img = image::open(carrier_path)?;
-        let (width, _height) = img.dimensions();
-        
         // Check capacity
         if !self.can_embed(carrier_path, data.len())? {
             return Err(SteganoError::EmbedError(
                 format!("Image too small to embed {} bytes of data", data.len())
             ));
         }
-        
-        // Embed length (4 bytes = 32 bits)
-        let len_bytes = (data.len() as u32).to_le_bytes();
-        let mut bit_count = 0;
-        
-        // Embed length bits
-        for byte_idx in 0..4 {
-            for bit_idx in 0..8 {
-                let x = bit_count % width;
-                let y = bit_count / width;
-                let color_idx = (bit_count % 3) as usize;
-                
-                let bit = (len_bytes[byte_idx] >> bit_idx) & 1;
-                
-                let mut pixel = img.get_pixel(x, y);
-                pixel[color_idx] = (pixel[color_idx] & 0xFE) | bit;
-                img.put_pixel(x, y, pixel);
-                
-                bit_count += 1;
-            }
-        }
-        
-        // Embed the actual data
-        for byte_idx in 0..data.len() {
-            for bit_idx in 0..8 {
-                let x = bit_count % width;
-                let y = bit_count / width;
-                let color_idx = (bit_count % 3) as usize;
-                
-                let bit = (data[byte_idx] >> bit_idx) & 1;
-                
-                let mut pixel = img.get_pixel(x, y);
-                pixel[color_idx] = (pixel[color_idx] & 0xFE) | bit;
-                img.put_pixel(x, y, pixel);
-                
-                bit_count += 1;
-            }
-        }
 
-        img.save(output_path)?;
-        Ok(())
+        self.embed_stream(carrier_path, &mut |writer| Ok(writer.write_all(data)?), output_path)
     }
 
-    fn extract(&self, carrier_path: &str) -> Result<Vec<u8>> {
+    fn embed_stream(&self, carrier_path: &str, write: PayloadWriter, output_path: &str) -> Result<()> {
         let img = image::open(carrier_path)?;
         let (width, height) = img.dimensions();
-        
-        // Extract the data length (4 bytes = 32 bits)
-        let mut len_bytes = [0u8; 4];
-        let mut bit_count = 0;
-        
-        for byte_idx in 0..4 {
-            let mut byte = 0u8;
-            for bit_idx in 0..8 {
-                let x = bit_count % width;
-                let y = bit_count / width;
-                let color_idx = (bit_count % 3) as usize;
-                
-                let pixel = img.get_pixel(x, y);
-                let bit = pixel[color_idx] & 1;
-                
-                byte |= bit << bit_idx;
-                bit_count += 1;
-            }
-            len_bytes[byte_idx] = byte;
-        }
-        
-        let data_len = u32::from_le_bytes(len_bytes) as usize;
+        let changes = self.slots.writer(width as usize * height as usize, write)?;
+        Self::write(img, changes, output_path)
+    }
 
-        // Validate data length
-        if data_len < 16 {
-            return Err(SteganoError::ExtractError(
-                "Invalid data length: too small for encrypted content".to_string()
-            ));
-        }
-        
-        let capacity = Self::calculate_capacity(width, height);
-        if data_len > capacity {
-            return Err(SteganoError::ExtractError(
-                "Corrupted data: claimed length exceeds image capacity".to_string()
-            ));
-        }
-        
-        // Extract the actual data
-        let mut extracted_data = vec![0u8; data_len];
-        
-        for byte_idx in 0..data_len {
-            let mut byte = 0u8;
-            for bit_idx in 0..8 {
-                let x = bit_count % width;
-                let y = bit_count / width;
-                let color_idx = (bit_count % 3) as usize;
-                
-                let pixel = img.get_pixel(x, y);
-                let bit = pixel[color_idx] & 1;
-                
-                byte |= bit << bit_idx;
-                bit_count += 1;
-            }
-            extracted_data[byte_idx] = byte;
-        }
+    /// Reuse the old payload's slot, keeping whatever the other slot holds
+    fn replace(&self, carrier_path: &str, old_key: &MasterKey, data: &[u8], output_path: &str) -> Result<()> {
+        let img = image::open(carrier_path)?;
+        let changes = self.slots.replacer(&mut ImageLsbs(&img), old_key, &mut |writer| Ok(writer.write_all(data)?))?;
+        Self::write(img, changes, output_path)
+    }
 
-        Ok(extracted_data)
+    fn extract(&self, carrier_path: &str) -> Result<Vec<u8>> {
+        let img = image::open(carrier_path)?;
+        self.slots.extract(&mut ImageLsbs(&img))
     }
 
     fn can_embed(&self, carrier_path: &str, data_size: usize) -> Result<bool> {
         let img = image::open(carrier_path)?;
         let (width, height) = img.dimensions();
-        let capacity = Self::calculate_capacity(width, height);
+        let capacity = self.slots.capacity(width as usize * height as usize);
         Ok(data_size <= capacity)
     }
+
+    fn capacity(&self, carrier_path: &str) -> Result<Option<usize>> {
+        let (width, height) = image::image_dimensions(carrier_path)?;
+        Ok(Some(self.slots.capacity(width as usize * height as usize)))
+    }
+
+    fn carrier_digest(&self, carrier_path: &str) -> Result<Option<[u8; 32]>> {
+        let img = image::open(carrier_path)?;
+        let (width, height) = img.dimensions();
+
+        // Dimensions and every pixel, with the color LSBs that carry data cleared
+        let mut hasher = Sha256::new();
+        hasher.update(width.to_le_bytes());
+        hasher.update(height.to_le_bytes());
+        for (_, _, pixel) in img.pixels() {
+            let [r, g, b, a] = pixel.0;
+            hasher.update([r & 0xFE, g & 0xFE, b & 0xFE, a]);
+        }
+        Ok(Some(hasher.finalize().into()))
+    }
+}
+
+/// The color LSBs of a decoded image, one per pixel
+struct ImageLsbs<'a>(&'a DynamicImage);
+
+impl LsbSource for ImageLsbs<'_> {
+    fn bits(&self) -> usize {
+        let (width, height) = self.0.dimensions();
+        width as usize * height as usize
+    }
+
+    fn read(&mut self, positions: &[usize]) -> Result<Vec<u8>> {
+        let width = self.0.width();
+        Ok(positions.iter().map(|&position| {
+            let (x, y) = pixel_at(position, width);
+            self.0.get_pixel(x, y)[position % 3] & 1
+        }).collect())
+    }
+}
+
+/// Coordinates of the pixel at `position` in row-major order
+fn pixel_at(position: usize, width: u32) -> (u32, u32) {
+    ((position % width as usize) as u32, (position / width as usize) as u32)
 }
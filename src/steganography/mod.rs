@@ -1,9 +1,17 @@
 pub mod traits;
 pub mod image;
 pub mod audio;
+pub mod aiff;
+pub mod au;
 pub mod pdf;
+pub mod carrier;
+mod slots;
 
-pub use traits::Steganography;
+pub use traits::{PayloadWriter, Steganography};
 pub use image::ImageSteganography;
 pub use audio::AudioSteganography;
-pub use pdf::PdfSteganography;
+pub use aiff::AiffSteganography;
+pub use au::AuSteganography;
+pub use pdf::{PdfMode, PdfSteganography};
+pub use carrier::Carrier;
+pub(crate) use carrier::path_str;
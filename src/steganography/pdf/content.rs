@@ -0,0 +1,330 @@
+//! Hides bits in page content streams without changing what they draw.
+//!
+//! Two kinds of slot are used, in stream order:
+//!
+//! - the whitespace after an operator: a line feed for 0, a single space for 1
+//! - kerning numbers inside a `TJ` array: the shortest spelling of the value
+//!   for 0, the same value with one extra trailing zero (`-250.0`, `12.50`) for 1
+//!
+//! Neither changes the operators or their operand values, so rendering is
+//! identical.
+
+use crate::error::{Result, SteganoError};
+use super::syntax::{is_regular, is_whitespace};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Space,
+    Comment,
+    Number,
+    Operator,
+    ArrayOpen,
+    ArrayClose,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+/// A tokenized content stream and the tokens that can carry a bit
+pub(crate) struct ContentStream<'a> {
+    data: &'a [u8],
+    tokens: Vec<Token>,
+    slots: Vec<usize>,
+}
+
+impl<'a> ContentStream<'a> {
+    pub(crate) fn parse(data: &'a [u8]) -> Result<Self> {
+        let tokens = tokenize(data)?;
+        let slots = find_slots(data, &tokens);
+        Ok(Self { data, tokens, slots })
+    }
+
+    pub(crate) fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub(crate) fn read_bits(&self) -> Vec<bool> {
+        self.slots
+            .iter()
+            .map(|&idx| {
+                let token = self.tokens[idx];
+                let text = &self.data[token.start..token.end];
+                match token.kind {
+                    TokenKind::Space => text == b" ",
+                    _ => text.contains(&b'.') && text.ends_with(b"0"),
+                }
+            })
+            .collect()
+    }
+
+    /// Rewrite the stream with one bit from `bits` in each slot
+    pub(crate) fn write_bits(&self, bits: &mut impl Iterator<Item = bool>) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.data.len());
+        let mut slots = self.slots.iter().peekable();
+
+        for (idx, token) in self.tokens.iter().enumerate() {
+            let text = &self.data[token.start..token.end];
+            if slots.peek() != Some(&&idx) {
+                output.extend_from_slice(text);
+                continue;
+            }
+            slots.next();
+
+            let bit = bits.next().unwrap_or(false);
+            match token.kind {
+                TokenKind::Space => output.push(if bit { b' ' } else { b'\n' }),
+                _ => {
+                    let canonical = canonical_number(text);
+                    output.extend_from_slice(&canonical);
+                    if bit {
+                        output.extend_from_slice(if canonical.contains(&b'.') { b"0" } else { b".0" });
+                    }
+                }
+            }
+        }
+
+        output
+    }
+}
+
+fn tokenize(data: &[u8]) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let start = pos;
+        let byte = data[pos];
+
+        let kind = if is_whitespace(byte) {
+            while pos < data.len() && is_whitespace(data[pos]) {
+                pos += 1;
+            }
+            TokenKind::Space
+        } else {
+            match byte {
+                b'%' => {
+                    while pos < data.len() && data[pos] != b'\n' && data[pos] != b'\r' {
+                        pos += 1;
+                    }
+                    TokenKind::Comment
+                }
+                b'(' => {
+                    pos = skip_literal_string(data, pos)?;
+                    TokenKind::Other
+                }
+                b'<' if data.get(pos + 1) == Some(&b'<') => {
+                    pos += 2;
+                    TokenKind::Other
+                }
+                b'>' if data.get(pos + 1) == Some(&b'>') => {
+                    pos += 2;
+                    TokenKind::Other
+                }
+                b'<' => {
+                    pos += data[pos..]
+                        .iter()
+                        .position(|&b| b == b'>')
+                        .ok_or_else(|| malformed("unterminated hex string"))?
+                        + 1;
+                    TokenKind::Other
+                }
+                b'[' => {
+                    pos += 1;
+                    TokenKind::ArrayOpen
+                }
+                b']' => {
+                    pos += 1;
+                    TokenKind::ArrayClose
+                }
+                b'/' => {
+                    pos += 1;
+                    while pos < data.len() && is_regular(data[pos]) {
+                        pos += 1;
+                    }
+                    TokenKind::Other
+                }
+                _ if is_regular(byte) => {
+                    while pos < data.len() && is_regular(data[pos]) {
+                        pos += 1;
+                    }
+                    if is_number(&data[start..pos]) {
+                        TokenKind::Number
+                    } else {
+                        TokenKind::Operator
+                    }
+                }
+                _ => {
+                    pos += 1;
+                    TokenKind::Other
+                }
+            }
+        };
+
+        tokens.push(Token { kind, start, end: pos });
+
+        // Inline image data is binary and runs until the EI operator
+        if kind == TokenKind::Operator && &data[start..pos] == b"ID" {
+            let data_start = pos;
+            pos = find_inline_image_end(data, pos + 1)?;
+            tokens.push(Token { kind: TokenKind::Other, start: data_start, end: pos });
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Whitespace after operators and numbers inside `[...] TJ`, in stream order
+fn find_slots(data: &[u8], tokens: &[Token]) -> Vec<usize> {
+    let mut slots = Vec::new();
+    let mut array_start: Option<usize> = None;
+    let mut depth = 0;
+
+    for (idx, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::Space => {
+                let after_operator = idx > 0 && tokens[idx - 1].kind == TokenKind::Operator;
+                let has_next = idx + 1 < tokens.len();
+                if after_operator && has_next {
+                    slots.push(idx);
+                }
+            }
+            TokenKind::ArrayOpen => {
+                if depth == 0 {
+                    array_start = Some(idx);
+                }
+                depth += 1;
+            }
+            TokenKind::ArrayClose if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    let next = tokens[idx + 1..].iter().find(|t| t.kind != TokenKind::Space);
+                    let is_tj = next.is_some_and(|t| {
+                        t.kind == TokenKind::Operator && &data[t.start..t.end] == b"TJ"
+                    });
+                    if let (true, Some(open)) = (is_tj, array_start) {
+                        slots.extend(
+                            (open + 1..idx).filter(|&i| tokens[i].kind == TokenKind::Number),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    slots.sort_unstable();
+    slots
+}
+
+fn skip_literal_string(data: &[u8], mut pos: usize) -> Result<usize> {
+    let mut depth = 0;
+    while pos < data.len() {
+        match data[pos] {
+            b'\\' => pos += 1,
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(pos + 1);
+                }
+            }
+            _ => {}
+        }
+        pos += 1;
+    }
+    Err(malformed("unterminated string"))
+}
+
+/// Find the whitespace before the `EI` that ends inline image data
+fn find_inline_image_end(data: &[u8], from: usize) -> Result<usize> {
+    let mut pos = from;
+    while pos + 2 <= data.len() {
+        if &data[pos..pos + 2] == b"EI"
+            && pos > 0
+            && is_whitespace(data[pos - 1])
+            && data.get(pos + 2).is_none_or(|&b| !is_regular(b))
+        {
+            return Ok(pos);
+        }
+        pos += 1;
+    }
+    Err(malformed("inline image without EI"))
+}
+
+fn is_number(text: &[u8]) -> bool {
+    let digits = text.strip_prefix(b"-").or_else(|| text.strip_prefix(b"+")).unwrap_or(text);
+    digits.iter().any(u8::is_ascii_digit)
+        && digits.iter().all(|&b| b.is_ascii_digit() || b == b'.')
+        && digits.iter().filter(|&&b| b == b'.').count() <= 1
+}
+
+/// Shortest spelling of a number: no trailing fractional zeros or bare point
+fn canonical_number(text: &[u8]) -> Vec<u8> {
+    let mut canonical = text.to_vec();
+    if canonical.contains(&b'.') {
+        while canonical.last() == Some(&b'0') {
+            canonical.pop();
+        }
+        if canonical.last() == Some(&b'.') {
+            canonical.pop();
+        }
+        if canonical.is_empty() || canonical == b"-" || canonical == b"+" {
+            canonical.push(b'0');
+        }
+    }
+    canonical
+}
+
+fn malformed(msg: &str) -> SteganoError {
+    SteganoError::InvalidData(format!("Malformed PDF content stream: {}", msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STREAM: &[u8] =
+        b"BT\n/F1 24 Tf\n72 720 Td\n[(Hel) -250 (lo) 12.50 (W) -3.] TJ\n(a  b) Tj ET\nq 1 0 0 1 0 0 cm BI /W 1 /H 1 /BPC 8 /CS /G ID \x20 EI Q\n";
+
+    /// Every token but whitespace, with numbers spelled their shortest way
+    fn values(data: &[u8]) -> Vec<Vec<u8>> {
+        let content = ContentStream::parse(data).unwrap();
+        content
+            .tokens
+            .iter()
+            .filter(|token| token.kind != TokenKind::Space)
+            .map(|token| {
+                let text = &data[token.start..token.end];
+                match token.kind {
+                    TokenKind::Number => canonical_number(text),
+                    _ => text.to_vec(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bits_round_trip_without_changing_operators_or_values() {
+        let content = ContentStream::parse(STREAM).unwrap();
+        let bits: Vec<bool> = (0..content.slot_count()).map(|idx| idx % 3 != 1).collect();
+        let rewritten = content.write_bits(&mut bits.iter().copied());
+
+        assert_eq!(ContentStream::parse(&rewritten).unwrap().read_bits(), bits);
+        assert_eq!(values(STREAM), values(&rewritten));
+        assert!(rewritten.windows(6).any(|window| window == b"(a  b)"));
+    }
+
+    #[test]
+    fn kerning_values_and_operator_gaps_carry_bits() {
+        let content = ContentStream::parse(b"[(A) -250.0 (B) 12.5] TJ ET\nQ").unwrap();
+        assert_eq!(content.read_bits(), [true, false, true, false]);
+
+        let rewritten = content.write_bits(&mut [false, true, false, true].into_iter());
+        assert_eq!(rewritten, b"[(A) -250 (B) 12.50] TJ\nET Q");
+    }
+}
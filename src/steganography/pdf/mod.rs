@@ -0,0 +1,1018 @@
+mod content;
+mod stealth;
+mod structure;
+mod syntax;
+mod xmp;
+
+use crate::crypto::{CryptoManager, MasterKey};
+use crate::error::{Result, SteganoError};
+use crate::steganography::traits::{PayloadWriter, Steganography};
+use rand::{rng, RngCore};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Write};
+use content::ContentStream;
+use structure::{IncrementalUpdate, PdfDocument, PdfStream, PdfStructure};
+use syntax::{Dictionary, PdfObject};
+
+/// Where hidden data is placed inside the PDF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PdfMode {
+    /// Raw bytes after the final %%EOF marker (SteganoVault 0.2 format)
+    Trailing,
+    /// A compressed image XObject added to the first page's resources through
+    /// an incremental update, with its own cross-reference section and trailer
+    #[default]
+    Object,
+    /// Whitespace padding of the document's XMP metadata packet
+    Xmp,
+    /// Whitespace after operators and TJ kerning values in page content streams
+    ContentStream,
+}
+
+impl PdfMode {
+    pub const ALL: [PdfMode; 4] = [PdfMode::Object, PdfMode::Trailing, PdfMode::Xmp, PdfMode::ContentStream];
+
+    /// Name used on the command line and in bound contexts
+    pub fn name(&self) -> &'static str {
+        match self {
+            PdfMode::Trailing => "trailing",
+            PdfMode::Object => "object",
+            PdfMode::Xmp => "xmp",
+            PdfMode::ContentStream => "content",
+        }
+    }
+}
+
+/// Marker that ends the unkeyed trailing format
+const TRAILING_MARKER: &[u8] = b"\n%%STEGANO%%\n";
+
+/// Room kept free for the cross-reference section and trailer an update adds
+const UPDATE_RESERVE: usize = 64 * 1024;
+
+/// The document's XMP packet and the padding bytes that can carry a bit
+struct XmpPacket {
+    number: u32,
+    gen: u16,
+    stream: PdfStream,
+    packet: Vec<u8>,
+    slots: Vec<usize>,
+}
+
+/// A decoded page content stream and the object it came from
+struct PageContent {
+    number: u32,
+    gen: u16,
+    stream: PdfStream,
+    decoded: Vec<u8>,
+}
+
+pub struct PdfSteganography {
+    mode: PdfMode,
+    stealth_key: Option<MasterKey>,
+}
+
+impl PdfSteganography {
+    pub fn new() -> Self {
+        Self::with_mode(PdfMode::default())
+    }
+
+    /// Create an embedder that uses the given placement mode
+    pub fn with_mode(mode: PdfMode) -> Self {
+        PdfSteganography { mode, stealth_key: None }
+    }
+
+    /// Mask hidden data with a key so it is indistinguishable from random bytes
+    /// and carries no plaintext length or marker
+    pub fn with_stealth_key(mut self, key: &MasterKey) -> Self {
+        self.stealth_key = Some(key.clone());
+        self
+    }
+
+    /// Largest payload, in bytes, that the chosen mode can hide in `doc`
+    fn doc_capacity(&self, doc: &PdfDocument) -> Result<usize> {
+        // Unkeyed payloads carry a 4-byte length prefix, keyed ones and every
+        // payload stream the stealth trailer
+        let overhead = match (self.mode, &self.stealth_key) {
+            (PdfMode::Object, _) | (_, Some(_)) => stealth::OVERHEAD,
+            _ => 4,
+        };
+
+        match self.mode {
+            // Lengths are stored as 32-bit values
+            PdfMode::Trailing => Ok(u32::MAX as usize - overhead),
+            // Cross-reference streams hold 4-byte offsets, so the updated file has to
+            // stay under 4 GiB; Flate adds 5 bytes per 64 KiB of incompressible data
+            PdfMode::Object => {
+                let room = (u32::MAX as usize)
+                    .saturating_sub(doc.content().len() + UPDATE_RESERVE);
+                Ok((room - room / 8192).saturating_sub(overhead))
+            }
+            PdfMode::Xmp => {
+                let slots = Self::xmp_packet(doc)?.slots.len();
+                Ok((slots / 8).saturating_sub(overhead))
+            }
+            PdfMode::ContentStream => {
+                let slots: usize = Self::content_streams(doc)?
+                    .iter()
+                    .map(|page| ContentStream::parse(&page.decoded).map(|c| c.slot_count()))
+                    .sum::<Result<usize>>()?;
+                Ok((slots / 8).saturating_sub(overhead))
+            }
+        }
+    }
+
+    /// Whether anything other than whitespace follows the newest revision
+    fn has_trailing_data(pdf_content: &[u8]) -> bool {
+        structure::revision_end(pdf_content).is_some_and(|end| {
+            pdf_content[end..]
+                .iter()
+                .any(|&b| !matches!(b, b'\n' | b'\r' | b' '))
+        })
+    }
+
+    fn embed_trailing(&self, doc: &PdfDocument, data: &[u8]) -> Result<Vec<u8>> {
+        // Find the end of the newest revision
+        let eof_pos = structure::revision_end(doc.content())
+            .ok_or_else(|| SteganoError::EmbedError("Could not find PDF %%EOF marker".to_string()))?;
+
+        // Drop any existing hidden data
+        let mut pdf_content = doc.content()[..eof_pos].to_vec();
+
+        if let Some(key) = &self.stealth_key {
+            if !pdf_content.ends_with(b"\n") && !pdf_content.ends_with(b"\r") {
+                pdf_content.push(b'\n');
+            }
+            pdf_content.extend_from_slice(&stealth::seal(key, data));
+            return Ok(pdf_content);
+        }
+
+        // Create the hidden data section
+        let mut hidden_section = Vec::new();
+
+        // Add a newline for separation
+        hidden_section.push(b'\n');
+
+        // Add length prefix (4 bytes)
+        hidden_section.extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+        // Add the actual data
+        hidden_section.extend_from_slice(data);
+
+        // Add marker to identify our hidden data
+        hidden_section.extend_from_slice(TRAILING_MARKER);
+
+        // Append hidden section to PDF
+        pdf_content.extend_from_slice(&hidden_section);
+
+        Ok(pdf_content)
+    }
+
+    fn embed_object(&self, doc: &PdfDocument, data: &[u8]) -> Result<Vec<u8>> {
+        // Data after %%EOF would hide the update from readers, so drop it
+        let content = doc.content();
+        let eof_pos = structure::revision_end(content).unwrap_or(content.len());
+        let mut pdf_content = content[..eof_pos].to_vec();
+        let structure = &doc.structure;
+
+        // The sealed bytes look random, as the samples of a one-row grey image would
+        let payload = stealth::seal(&self.object_key(), data);
+        let mut dict = Dictionary::new();
+        dict.set(b"Type", PdfObject::Name(b"XObject".to_vec()));
+        dict.set(b"Subtype", PdfObject::Name(b"Image".to_vec()));
+        dict.set(b"Width", PdfObject::Integer(payload.len() as i64));
+        dict.set(b"Height", PdfObject::Integer(1));
+        dict.set(b"ColorSpace", PdfObject::Name(b"DeviceGray".to_vec()));
+        dict.set(b"BitsPerComponent", PdfObject::Integer(8));
+        dict.set(b"Filter", PdfObject::Name(b"FlateDecode".to_vec()));
+
+        let mut update = IncrementalUpdate::new(structure);
+        let number = update.add_stream(dict, &structure::deflate(&payload)?);
+
+        // List the image among the first page's resources, which no content stream draws
+        let (page_number, page_gen, mut page) = doc.first_page()?;
+        let mut resources = match doc.inherited(&page, b"Resources")? {
+            Some(resources) => doc.get_dictionary(&resources)?,
+            None => Dictionary::new(),
+        };
+        let mut xobjects = match resources.get(b"XObject") {
+            Some(xobjects) => doc.get_dictionary(xobjects)?,
+            None => Dictionary::new(),
+        };
+        let name = (number..)
+            .map(|n| format!("Im{}", n).into_bytes())
+            .find(|name| xobjects.get(name).is_none())
+            .unwrap_or_default();
+        xobjects.set(&name, PdfObject::Reference(number, 0));
+        resources.set(b"XObject", PdfObject::Dictionary(xobjects));
+        page.set(b"Resources", PdfObject::Dictionary(resources));
+        update.replace_object(page_number, page_gen, &PdfObject::Dictionary(page));
+
+        update.write(&mut pdf_content, structure);
+        Ok(pdf_content)
+    }
+
+    /// Key that seals payload streams: the stealth key, or without one the
+    /// public carrier key, so a payload stream never holds a bare container
+    fn object_key(&self) -> MasterKey {
+        self.stealth_key.clone().unwrap_or_else(CryptoManager::public_carrier_key)
+    }
+
+    fn extract_trailing(pdf_content: &[u8]) -> Result<Vec<u8>> {
+        // Find the end of the newest revision
+        let eof_pos = structure::revision_end(pdf_content)
+            .ok_or_else(|| SteganoError::ExtractError("Could not find PDF %%EOF marker".to_string()))?;
+
+        // Check if there's data after EOF
+        if !Self::has_trailing_data(pdf_content) {
+            return Err(SteganoError::ExtractError(
+                "No hidden data found in PDF".to_string()
+            ));
+        }
+
+        let tail = &pdf_content[eof_pos..];
+        let is_padding = |b: u8| matches!(b, b'\n' | b'\r' | b' ');
+
+        // The data ends right before the marker, so the length prefix is the one
+        // that reaches it exactly, even when its own bytes look like whitespace
+        if let Some(body) = tail.strip_suffix(TRAILING_MARKER) {
+            let found = (0..body.len().saturating_sub(3))
+                .take_while(|&start| start == 0 || is_padding(body[start - 1]))
+                .find(|&start| {
+                    let len_bytes = [body[start], body[start + 1], body[start + 2], body[start + 3]];
+                    u32::from_le_bytes(len_bytes) as usize == body.len() - start - 4
+                });
+            return match found {
+                Some(start) if start + 4 < body.len() => Ok(body[start + 4..].to_vec()),
+                _ => Err(SteganoError::ExtractError(
+                    "Corrupted hidden data: length prefix does not match".to_string()
+                )),
+            };
+        }
+
+        // Without the marker, skip leading whitespace and trust the length prefix
+        let data_start = tail.iter().position(|&b| !is_padding(b)).unwrap_or(tail.len());
+        if data_start + 4 > tail.len() {
+            return Err(SteganoError::ExtractError(
+                "No valid hidden data found in PDF".to_string()
+            ));
+        }
+
+        let len_bytes: [u8; 4] = tail[data_start..data_start + 4]
+            .try_into()
+            .map_err(|_| SteganoError::ExtractError("Failed to read length prefix".to_string()))?;
+
+        let data_len = u32::from_le_bytes(len_bytes) as usize;
+
+        // Validate length
+        if data_len == 0 {
+            return Err(SteganoError::ExtractError(
+                "Invalid data length: zero".to_string()
+            ));
+        }
+
+        let data_start_pos = data_start + 4;
+        let data_end_pos = data_start_pos + data_len;
+
+        if data_end_pos > tail.len() {
+            return Err(SteganoError::ExtractError(
+                "Corrupted hidden data: length exceeds file size".to_string()
+            ));
+        }
+
+        // Extract the hidden data
+        Ok(tail[data_start_pos..data_end_pos].to_vec())
+    }
+
+    /// Lay `data` out over `slot_count` carrier bits. Without a key the bits are a
+    /// length prefix, the data and zero filler; with one, every slot is used and
+    /// the sealed data sits behind random filler.
+    fn pack_bits(&self, data: &[u8], slot_count: usize) -> Result<Vec<bool>> {
+        let bytes = match &self.stealth_key {
+            Some(key) => {
+                let sealed = stealth::seal(key, data);
+                let mut bytes = vec![0u8; (slot_count / 8).saturating_sub(sealed.len())];
+                rng().fill_bytes(&mut bytes);
+                bytes.extend_from_slice(&sealed);
+                bytes
+            }
+            None => {
+                let mut bytes = (data.len() as u32).to_le_bytes().to_vec();
+                bytes.extend_from_slice(data);
+                bytes
+            }
+        };
+
+        if bytes.len() * 8 > slot_count {
+            return Err(SteganoError::EmbedError(format!(
+                "PDF too small to embed {} bytes of data in this mode (room for {} bits)",
+                data.len(),
+                slot_count
+            )));
+        }
+
+        let mut bits: Vec<bool> = bytes
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |bit_idx| (byte >> bit_idx) & 1 == 1))
+            .collect();
+        while bits.len() < slot_count {
+            bits.push(self.stealth_key.is_some() && rng().next_u32() & 1 == 1);
+        }
+        Ok(bits)
+    }
+
+    fn unpack_bits(&self, bits: &[bool]) -> Result<Vec<u8>> {
+        let bytes: Vec<u8> = bits
+            .chunks_exact(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u8, |byte, (bit_idx, &bit)| byte | ((bit as u8) << bit_idx))
+            })
+            .collect();
+
+        if let Some(key) = &self.stealth_key {
+            return stealth::open(key, &bytes)
+                .map(|(_, data)| data)
+                .ok_or_else(|| SteganoError::ExtractError("No hidden data found in PDF".to_string()));
+        }
+
+        if bytes.len() < 4 {
+            return Err(SteganoError::ExtractError("No hidden data found in PDF".to_string()));
+        }
+        let data_len = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        if data_len == 0 || data_len > bytes.len() - 4 {
+            return Err(SteganoError::ExtractError(
+                "Corrupted data: claimed length exceeds PDF capacity".to_string()
+            ));
+        }
+        Ok(bytes[4..4 + data_len].to_vec())
+    }
+
+    /// The catalog's /Metadata packet and its usable padding
+    fn xmp_packet(doc: &PdfDocument) -> Result<XmpPacket> {
+        let number = doc
+            .catalog()?
+            .get(b"Metadata")
+            .and_then(PdfObject::as_reference)
+            .map(|(number, _)| number)
+            .ok_or_else(|| SteganoError::InvalidData("PDF has no XMP metadata stream".to_string()))?;
+        let (_, gen) = doc
+            .location(number)
+            .ok_or_else(|| SteganoError::InvalidData("PDF metadata stream is missing".to_string()))?;
+        let stream = doc.get_stream(number)?;
+        let packet = stream.decode()?;
+        let slots = xmp::padding_slots(&packet)
+            .ok_or_else(|| SteganoError::InvalidData("PDF metadata is not an XMP packet".to_string()))?;
+        Ok(XmpPacket { number, gen, stream, packet, slots })
+    }
+
+    fn embed_xmp(&self, doc: &PdfDocument, data: &[u8]) -> Result<Vec<u8>> {
+        let XmpPacket { number, gen, stream, mut packet, slots } = Self::xmp_packet(doc)?;
+
+        let bits = self.pack_bits(data, slots.len())?;
+        xmp::write_bits(&mut packet, &slots, &bits);
+
+        // An unfiltered packet is rewritten in place, leaving the file structure alone
+        let mut pdf_content = doc.content().to_vec();
+        if stream.dict.get(b"Filter").is_none() && packet.len() == stream.data.len() {
+            let start = stream.data_offset;
+            pdf_content[start..start + packet.len()].copy_from_slice(&packet);
+            return Ok(pdf_content);
+        }
+
+        // Otherwise store an uncompressed revision, as XMP is meant to be
+        let mut dict = stream.dict.clone();
+        dict.remove(b"Filter");
+        dict.remove(b"DecodeParms");
+
+        let mut update = IncrementalUpdate::new(&doc.structure);
+        update.replace_stream(number, gen, dict, &packet);
+        update.write(&mut pdf_content, &doc.structure);
+        Ok(pdf_content)
+    }
+
+    fn extract_xmp(&self, pdf_content: &[u8]) -> Result<Vec<u8>> {
+        let doc = PdfDocument::parse(pdf_content)?;
+        let xmp = Self::xmp_packet(&doc).map_err(|e| SteganoError::ExtractError(e.to_string()))?;
+        self.unpack_bits(&xmp::read_bits(&xmp.packet, &xmp.slots))
+    }
+
+    /// Decoded content streams that can carry bits, with their object numbers
+    /// and generations. Streams that cannot be decoded are skipped both when
+    /// embedding and extracting, so the slot order stays the same.
+    fn content_streams(doc: &PdfDocument) -> Result<Vec<PageContent>> {
+        let mut streams = Vec::new();
+        for number in doc.content_streams()? {
+            let (Some((_, gen)), Ok(stream)) = (doc.location(number), doc.get_stream(number)) else {
+                continue;
+            };
+            let Ok(decoded) = stream.decode() else {
+                continue;
+            };
+            if ContentStream::parse(&decoded).is_ok() {
+                streams.push(PageContent { number, gen, stream, decoded });
+            }
+        }
+        Ok(streams)
+    }
+
+    fn embed_content(&self, doc: &PdfDocument, data: &[u8]) -> Result<Vec<u8>> {
+        let streams = Self::content_streams(doc)?;
+
+        let parsed = streams
+            .iter()
+            .map(|page| ContentStream::parse(&page.decoded))
+            .collect::<Result<Vec<_>>>()?;
+        let slot_count = parsed.iter().map(ContentStream::slot_count).sum();
+        let mut bits = self.pack_bits(data, slot_count)?.into_iter();
+
+        let mut update = IncrementalUpdate::new(&doc.structure);
+        for (page, content) in streams.iter().zip(&parsed) {
+            let rewritten = content.write_bits(&mut bits);
+
+            let mut dict = page.stream.dict.clone();
+            dict.remove(b"DecodeParms");
+            let encoded = if dict.get(b"Filter").is_some() {
+                dict.set(b"Filter", PdfObject::Name(b"FlateDecode".to_vec()));
+                structure::deflate(&rewritten)?
+            } else {
+                rewritten
+            };
+            update.replace_stream(page.number, page.gen, dict, &encoded);
+        }
+
+        let mut pdf_content = doc.content().to_vec();
+        update.write(&mut pdf_content, &doc.structure);
+        Ok(pdf_content)
+    }
+
+    fn extract_content(&self, pdf_content: &[u8]) -> Result<Vec<u8>> {
+        let doc = PdfDocument::parse(pdf_content)?;
+        let mut bits = Vec::new();
+        for page in Self::content_streams(&doc)? {
+            bits.extend(ContentStream::parse(&page.decoded)?.read_bits());
+        }
+        self.unpack_bits(&bits)
+    }
+
+    /// Payload streams are one-row grey images with nothing else in their dictionary
+    fn is_payload_stream(stream: &PdfStream) -> bool {
+        stream.dict.get(b"Subtype").and_then(PdfObject::as_name) == Some(b"Image")
+            && stream.dict.get(b"Height").and_then(PdfObject::as_integer) == Some(1)
+            && stream.dict.keys().all(|key| {
+                matches!(
+                    key,
+                    b"Type" | b"Subtype" | b"Width" | b"Height" | b"ColorSpace" | b"BitsPerComponent"
+                        | b"Length" | b"Filter"
+                )
+            })
+    }
+
+    /// Whether the newest revision only holds what this mode writes: a payload
+    /// stream and the first page listing it, the metadata stream or page
+    /// content streams
+    fn is_own_revision(&self, content: &[u8], structure: &PdfStructure) -> Result<bool> {
+        if self.mode == PdfMode::Trailing || structure.trailer.get(b"Prev").is_none() {
+            return Ok(false);
+        }
+
+        // A cross-reference stream lists itself; it is not part of the update
+        let objects: Vec<(u32, usize)> = structure
+            .latest_objects(content)?
+            .into_iter()
+            .filter(|&(_, offset)| offset != structure.startxref)
+            .collect();
+        if objects.is_empty() {
+            return Ok(false);
+        }
+
+        let doc = PdfDocument::parse(content)?;
+        Ok(match self.mode {
+            PdfMode::Object => {
+                let page = doc.first_page()?.0;
+                let payloads = objects
+                    .iter()
+                    .filter(|&&(_, offset)| {
+                        structure::read_stream_object(content, offset)
+                            .is_ok_and(|(_, stream)| Self::is_payload_stream(&stream))
+                    })
+                    .count();
+                objects.len() == 2 && payloads == 1 && objects.iter().any(|&(number, _)| number == page)
+            }
+            PdfMode::Xmp => {
+                let metadata = doc
+                    .catalog()?
+                    .get(b"Metadata")
+                    .and_then(PdfObject::as_reference)
+                    .map(|(number, _)| number);
+                objects.iter().all(|&(number, _)| Some(number) == metadata)
+            }
+            PdfMode::ContentStream => {
+                let streams = doc.content_streams()?;
+                objects.iter().all(|(number, _)| streams.contains(number))
+            }
+            PdfMode::Trailing => false,
+        })
+    }
+
+    /// The revision hidden data is added to: newer revisions this mode could
+    /// have written are peeled off, along with anything after the final %%EOF
+    /// and the line break before it, which embedding may add or drop
+    fn base_revision<'a>(&self, content: &'a [u8]) -> Result<&'a [u8]> {
+        let mut base = content;
+        loop {
+            let structure = PdfStructure::parse(base)?;
+            if !self.is_own_revision(base, &structure)? {
+                break;
+            }
+            let end = structure::revision_end(&base[..structure.startxref])
+                .ok_or_else(|| structure::invalid("previous revision has no %%EOF marker"))?;
+            base = &base[..end];
+        }
+
+        let end = structure::revision_end(base).unwrap_or(base.len());
+        let trimmed = base[..end]
+            .iter()
+            .rposition(|&b| !matches!(b, b'\r' | b'\n'))
+            .map_or(0, |pos| pos + 1);
+        Ok(&base[..trimmed])
+    }
+
+    /// `pdf_content` with `data` hidden in it by this mode
+    fn embed_in(&self, pdf_content: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+        // Check the header, cross-reference chain and trailer before changing anything
+        let doc = PdfDocument::open(pdf_content)?;
+
+        // Check capacity
+        let capacity = self.doc_capacity(&doc)?;
+        if data.len() > capacity {
+            return Err(SteganoError::EmbedError(format!(
+                "PDF too small to embed {} bytes of data in {:?} mode (capacity: {} bytes)",
+                data.len(),
+                self.mode,
+                capacity
+            )));
+        }
+
+        match self.mode {
+            PdfMode::Trailing => self.embed_trailing(&doc, data),
+            PdfMode::Object => self.embed_object(&doc, data),
+            PdfMode::Xmp => self.embed_xmp(&doc, data),
+            PdfMode::ContentStream => self.embed_content(&doc, data),
+        }
+    }
+
+    /// Open the payload stream added by the newest incremental update
+    fn extract_object(&self, pdf_content: &[u8]) -> Result<Vec<u8>> {
+        let structure = PdfStructure::parse(pdf_content)?;
+        if structure.trailer.get(b"Prev").is_none() {
+            return Err(SteganoError::ExtractError(
+                "No hidden data found in PDF: it has no incremental update".to_string()
+            ));
+        }
+
+        for (_, offset) in structure.latest_objects(pdf_content)? {
+            let Ok((_, stream)) = structure::read_stream_object(pdf_content, offset) else {
+                continue;
+            };
+
+            if !Self::is_payload_stream(&stream) {
+                continue;
+            }
+            let decoded = stream.decode().map_err(|e| {
+                SteganoError::ExtractError(format!("Corrupted hidden data: {}", e))
+            })?;
+            if let Some((0, data)) = stealth::open(&self.object_key(), &decoded) {
+                return Ok(data);
+            }
+        }
+
+        Err(SteganoError::ExtractError(
+            "No hidden data found in PDF".to_string()
+        ))
+    }
+}
+
+/// Collects a payload, refusing bytes past `capacity`
+struct BoundedBuffer {
+    data: Vec<u8>,
+    capacity: usize,
+    /// Set once a write would pass the capacity
+    full: bool,
+}
+
+impl Write for BoundedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() > self.capacity - self.data.len() {
+            self.full = true;
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "PDF is full"));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Default for PdfSteganography {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Steganography for PdfSteganography {
+    fn embed(&self, carrier_path: &str, data: &[u8], output_path: &str) -> Result<()> {
+        // Read the PDF file
+        let pdf_content = fs::read(carrier_path)?;
+        let pdf_content = self.embed_in(&pdf_content, data)?;
+
+        // Write the modified PDF
+        fs::write(output_path, pdf_content)?;
+
+        Ok(())
+    }
+
+    /// PDFs are rebuilt in memory, so the payload is collected first, but
+    /// no further than the document can hold
+    fn embed_stream(&self, carrier_path: &str, write: PayloadWriter, output_path: &str) -> Result<()> {
+        let pdf_content = fs::read(carrier_path)?;
+        let capacity = self.doc_capacity(&PdfDocument::open(&pdf_content)?)?;
+        let mut buffer = BoundedBuffer { data: Vec::new(), capacity, full: false };
+        let written = write(&mut buffer);
+        if buffer.full {
+            return Err(SteganoError::EmbedError(format!(
+                "PDF too small to embed more than {} bytes of data in {:?} mode",
+                capacity, self.mode
+            )));
+        }
+        written?;
+        fs::write(output_path, self.embed_in(&pdf_content, &buffer.data)?)?;
+        Ok(())
+    }
+
+    /// Embed on the revision before the old payload, which would otherwise
+    /// stay readable in the earlier revision of the incremental update
+    fn replace(&self, carrier_path: &str, _old_key: &MasterKey, data: &[u8], output_path: &str) -> Result<()> {
+        let pdf_content = fs::read(carrier_path)?;
+        let pdf_content = self.embed_in(self.base_revision(&pdf_content)?, data)?;
+        fs::write(output_path, pdf_content)?;
+        Ok(())
+    }
+
+    fn extract(&self, carrier_path: &str) -> Result<Vec<u8>> {
+        // Read the PDF file
+        let pdf_content = fs::read(carrier_path)?;
+
+        // Verify it's a PDF
+        structure::check_header(&pdf_content)?;
+
+        match self.mode {
+            PdfMode::Xmp => return self.extract_xmp(&pdf_content),
+            PdfMode::ContentStream => return self.extract_content(&pdf_content),
+            PdfMode::Trailing | PdfMode::Object => {}
+        }
+
+        // Bytes after the final %%EOF mean the trailing format, whichever mode
+        // was asked for, so older carriers keep working
+        if self.mode == PdfMode::Object && !Self::has_trailing_data(&pdf_content) {
+            return self.extract_object(&pdf_content);
+        }
+        if let Some(key) = &self.stealth_key {
+            if let Some((_, data)) = stealth::open(key, &pdf_content) {
+                return Ok(data);
+            }
+        }
+        Self::extract_trailing(&pdf_content)
+    }
+
+    fn can_embed(&self, carrier_path: &str, data_size: usize) -> Result<bool> {
+        let pdf_content = fs::read(carrier_path)?;
+        let doc = PdfDocument::open(&pdf_content)?;
+        Ok(data_size <= self.doc_capacity(&doc)?)
+    }
+
+    fn capacity(&self, carrier_path: &str) -> Result<Option<usize>> {
+        // Appended data and new objects grow the file instead
+        if matches!(self.mode, PdfMode::Trailing | PdfMode::Object) {
+            return Ok(None);
+        }
+        let pdf_content = fs::read(carrier_path)?;
+        let doc = PdfDocument::open(&pdf_content)?;
+        self.doc_capacity(&doc).map(Some)
+    }
+
+    fn carrier_digest(&self, carrier_path: &str) -> Result<Option<[u8; 32]>> {
+        let pdf_content = fs::read(carrier_path)?;
+        structure::check_header(&pdf_content)?;
+        let base = self.base_revision(&pdf_content)?;
+
+        // XMP padding can be rewritten in place, so the packet itself is left out
+        let mut hasher = Sha256::new();
+        match self.mode {
+            PdfMode::Xmp => {
+                let doc = PdfDocument::parse(base)?;
+                let stream = Self::xmp_packet(&doc)?.stream;
+                let start = stream.data_offset;
+                hasher.update(&base[..start]);
+                hasher.update(&base[start + stream.data.len()..]);
+            }
+            _ => hasher.update(base),
+        }
+        Ok(Some(hasher.finalize().into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{path_str, temp_dir, write_pdf};
+    use std::path::Path;
+
+    /// One page whose font resources it inherits from the page tree
+    const PAGE: [&[u8]; 5] = [
+        b"<< /Type /Catalog /Pages 2 0 R >>",
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 /Resources << /Font << /F1 5 0 R >> >> >>",
+        b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents 4 0 R >>",
+        b"<< /Length 43 >>\nstream\nBT /F1 24 Tf 72 720 Td (Hello World) Tj ET\n\nendstream",
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>",
+    ];
+
+    /// `PAGE` with a cross-reference stream in place of the table; returns
+    /// the stream's offset
+    fn write_pdf_with_xref_stream(path: &Path) -> usize {
+        let mut pdf = b"%PDF-1.5\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::new();
+        for (idx, object) in PAGE.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n", idx + 1).as_bytes());
+            pdf.extend_from_slice(object);
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+
+        let xref = pdf.len();
+        offsets.push(xref);
+        let mut entries = vec![0, 0, 0, 0, 0, 0xff, 0xff];
+        for offset in offsets {
+            entries.push(1);
+            entries.extend_from_slice(&(offset as u32).to_be_bytes());
+            entries.extend_from_slice(&[0, 0]);
+        }
+        pdf.extend_from_slice(
+            format!("6 0 obj\n<< /Type /XRef /Size 7 /W [1 4 2] /Root 1 0 R /Length {} >>\nstream\n", entries.len())
+                .as_bytes(),
+        );
+        pdf.extend_from_slice(&entries);
+        pdf.extend_from_slice(format!("\nendstream\nendobj\nstartxref\n{}\n%%EOF\n", xref).as_bytes());
+        fs::write(path, pdf).unwrap();
+        xref
+    }
+
+    /// A one-page PDF with an XMP packet and a content stream long enough
+    /// for a short payload in either
+    fn write_pdf_with_metadata(path: &Path) {
+        let content = b"BT /F1 12 Tf 72 700 Td [(A) -250 (B) -120 (C)] TJ ET\n".repeat(200);
+        let padding = format!("{}\n", " ".repeat(99)).repeat(20);
+        let xmp = format!(
+            "<?xpacket begin=\"\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\"></x:xmpmeta>\n{}<?xpacket end=\"w\"?>",
+            padding
+        );
+        let stream = |dict: &str, data: &[u8]| {
+            [format!("<< {} /Length {} >>\nstream\n", dict, data.len()).as_bytes(), data, b"\nendstream"].concat()
+        };
+        write_pdf(path, &[
+            b"<< /Type /Catalog /Pages 2 0 R /Metadata 6 0 R >>",
+            PAGE[1],
+            PAGE[2],
+            &stream("", &content),
+            PAGE[4],
+            &stream("/Type /Metadata /Subtype /XML", xmp.as_bytes()),
+        ]);
+    }
+
+    #[test]
+    fn object_mode_hides_a_sealed_image_listed_by_the_first_page() {
+        let dir = temp_dir("pdf-object");
+        let (cover, stego) = (dir.join("cover.pdf"), dir.join("stego.pdf"));
+        write_pdf(&cover, &PAGE);
+
+        let data = b"SVLT container bytes".to_vec();
+        let steg = PdfSteganography::new();
+        steg.embed(path_str(&cover), &data, path_str(&stego)).unwrap();
+        assert_eq!(steg.extract(path_str(&stego)).unwrap(), data);
+
+        let content = fs::read(&stego).unwrap();
+        let doc = PdfDocument::open(&content).unwrap();
+        let (number, _, page) = doc.first_page().unwrap();
+        assert_eq!(number, 3);
+        let resources = page.get(b"Resources").and_then(PdfObject::as_dictionary).unwrap();
+        assert!(resources.get(b"Font").is_some());
+        let xobjects = resources.get(b"XObject").and_then(PdfObject::as_dictionary).unwrap();
+        let (image, _) = xobjects.get(b"Im6").and_then(PdfObject::as_reference).unwrap();
+
+        let stream = doc.get_stream(image).unwrap();
+        assert!(PdfSteganography::is_payload_stream(&stream));
+        let decoded = stream.decode().unwrap();
+        assert!(!decoded.windows(4).any(|window| window == b"SVLT"));
+        assert_eq!(stealth::open(&CryptoManager::public_carrier_key(), &decoded).unwrap().1, data);
+    }
+
+    #[test]
+    fn object_mode_cross_reference_entries_ascend() {
+        let dir = temp_dir("pdf-xref");
+        let (cover, stego) = (dir.join("cover.pdf"), dir.join("stego.pdf"));
+        write_pdf(&cover, &PAGE);
+        PdfSteganography::new().embed(path_str(&cover), b"data", path_str(&stego)).unwrap();
+
+        let content = fs::read(&stego).unwrap();
+        let structure = PdfStructure::parse(&content).unwrap();
+        let section = String::from_utf8_lossy(&content[structure.startxref..]).into_owned();
+        let numbers: Vec<u32> = section
+            .lines()
+            .take_while(|line| *line != "trailer")
+            .filter_map(|line| line.strip_suffix(" 1")?.parse().ok())
+            .collect();
+        assert_eq!(numbers, [3, 6]);
+    }
+
+    #[test]
+    fn replacing_an_object_payload_drops_the_old_revision() {
+        let dir = temp_dir("pdf-replace");
+        let (cover, first, second) = (dir.join("cover.pdf"), dir.join("first.pdf"), dir.join("second.pdf"));
+        write_pdf(&cover, &PAGE);
+        let key = MasterKey::generate();
+        let steg = PdfSteganography::new().with_stealth_key(&key);
+
+        steg.embed(path_str(&cover), b"old", path_str(&first)).unwrap();
+        steg.replace(path_str(&first), &key, b"new", path_str(&second)).unwrap();
+
+        assert_eq!(steg.extract(path_str(&second)).unwrap(), b"new");
+        let (cover, second) = (fs::read(&cover).unwrap(), fs::read(&second).unwrap());
+        assert_eq!(second.windows(5).filter(|window| window == b"%%EOF").count(), 2);
+        assert!(second.starts_with(&cover[..cover.len() - 1]));
+        assert!(PdfSteganography::new().extract(path_str(&dir.join("second.pdf"))).is_err());
+    }
+
+    #[test]
+    fn object_mode_answers_a_cross_reference_stream_with_one() {
+        let dir = temp_dir("pdf-xref-stream");
+        let (cover, stego) = (dir.join("cover.pdf"), dir.join("stego.pdf"));
+        let xref = write_pdf_with_xref_stream(&cover);
+        let steg = PdfSteganography::new();
+        steg.embed(path_str(&cover), b"data", path_str(&stego)).unwrap();
+        assert_eq!(steg.extract(path_str(&stego)).unwrap(), b"data");
+
+        let (cover, content) = (fs::read(&cover).unwrap(), fs::read(&stego).unwrap());
+        assert!(content.starts_with(&cover));
+        assert!(!content[cover.len()..].windows(6).any(|window| window == b"\nxref\n"));
+        let structure = PdfStructure::parse(&content).unwrap();
+        assert_eq!(structure.trailer.get(b"Prev").and_then(PdfObject::as_integer), Some(xref as i64));
+        let (_, stream) = structure::read_stream_object(&content, structure.startxref).unwrap();
+        assert_eq!(stream.dict.get(b"Type").and_then(PdfObject::as_name), Some(&b"XRef"[..]));
+        PdfDocument::open(&content).unwrap().first_page().unwrap();
+    }
+
+    #[test]
+    fn keyed_trailing_data_has_no_marker_or_length() {
+        let dir = temp_dir("pdf-stealth");
+        let (cover, stego) = (dir.join("cover.pdf"), dir.join("stego.pdf"));
+        write_pdf(&cover, &PAGE);
+        let key = MasterKey::generate();
+        let steg = PdfSteganography::with_mode(PdfMode::Trailing).with_stealth_key(&key);
+        steg.embed(path_str(&cover), &[0x41; 300], path_str(&stego)).unwrap();
+        assert_eq!(steg.extract(path_str(&stego)).unwrap(), [0x41; 300]);
+
+        let content = fs::read(&stego).unwrap();
+        let tail = &content[structure::revision_end(&content).unwrap()..];
+        assert_eq!(tail.len(), 300 + stealth::OVERHEAD);
+        assert!(!tail.windows(3).any(|window| window == b"%%S" || window == [0x41; 3]));
+        assert!(!tail.windows(4).any(|window| window == 300u32.to_le_bytes()));
+        let other = PdfSteganography::with_mode(PdfMode::Trailing).with_stealth_key(&MasterKey::generate());
+        assert!(other.extract(path_str(&stego)).is_err());
+    }
+
+    #[test]
+    fn marker_format_still_extracts() {
+        let dir = temp_dir("pdf-marker");
+        let (cover, stego) = (dir.join("cover.pdf"), dir.join("stego.pdf"));
+        write_pdf(&cover, &PAGE);
+        let mut content = fs::read(&cover).unwrap();
+        content.extend_from_slice(b"\n");
+        content.extend_from_slice(&4u32.to_le_bytes());
+        content.extend_from_slice(b"0.2!");
+        content.extend_from_slice(TRAILING_MARKER);
+        fs::write(&stego, content).unwrap();
+
+        let key = MasterKey::generate();
+        for mode in [PdfMode::Trailing, PdfMode::Object] {
+            assert_eq!(PdfSteganography::with_mode(mode).extract(path_str(&stego)).unwrap(), b"0.2!");
+            let keyed = PdfSteganography::with_mode(mode).with_stealth_key(&key);
+            assert_eq!(keyed.extract(path_str(&stego)).unwrap(), b"0.2!");
+        }
+    }
+
+    #[test]
+    fn xmp_and_content_modes_round_trip_with_and_without_a_key() {
+        let dir = temp_dir("pdf-xmp-content");
+        let (cover, stego) = (dir.join("cover.pdf"), dir.join("stego.pdf"));
+        write_pdf_with_metadata(&cover);
+        let key = MasterKey::generate();
+
+        for mode in [PdfMode::Xmp, PdfMode::ContentStream] {
+            for steg in [PdfSteganography::with_mode(mode), PdfSteganography::with_mode(mode).with_stealth_key(&key)] {
+                steg.embed(path_str(&cover), b"metadata and whitespace", path_str(&stego)).unwrap();
+                assert_eq!(steg.extract(path_str(&stego)).unwrap(), b"metadata and whitespace", "{:?}", mode);
+            }
+        }
+
+        // An unfiltered packet is rewritten in place, the same length
+        PdfSteganography::with_mode(PdfMode::Xmp).embed(path_str(&cover), b"in place", path_str(&stego)).unwrap();
+        let (before, after) = (fs::read(&cover).unwrap(), fs::read(&stego).unwrap());
+        assert_eq!(before.len(), after.len());
+        let changed: Vec<(u8, u8)> = before.iter().zip(&after).filter(|(a, b)| a != b).map(|(&a, &b)| (a, b)).collect();
+        assert!(!changed.is_empty());
+        assert!(changed.iter().all(|&(a, b)| matches!((a, b), (b' ', b'\t') | (b'\t', b' '))));
+    }
+
+    #[test]
+    fn capacity_follows_the_mode() {
+        let dir = temp_dir("pdf-capacity");
+        let (cover, stego) = (dir.join("cover.pdf"), dir.join("stego.pdf"));
+        write_pdf_with_metadata(&cover);
+        let cover = path_str(&cover);
+        let key = MasterKey::generate();
+
+        // 20 lines of 99 padding bytes, less the length prefix or stealth trailer
+        assert_eq!(PdfSteganography::with_mode(PdfMode::Xmp).capacity(cover).unwrap(), Some(1980 / 8 - 4));
+        let keyed = PdfSteganography::with_mode(PdfMode::Xmp).with_stealth_key(&key);
+        assert_eq!(keyed.capacity(cover).unwrap(), Some(1980 / 8 - stealth::OVERHEAD));
+
+        for steg in [PdfSteganography::with_mode(PdfMode::Xmp), PdfSteganography::with_mode(PdfMode::ContentStream)] {
+            let capacity = steg.capacity(cover).unwrap().unwrap();
+            assert!(steg.can_embed(cover, capacity).unwrap());
+            assert!(!steg.can_embed(cover, capacity + 1).unwrap());
+            steg.embed(cover, &vec![7; capacity], path_str(&stego)).unwrap();
+            assert_eq!(steg.extract(path_str(&stego)).unwrap(), vec![7; capacity]);
+            assert!(steg.embed(cover, &vec![7; capacity + 1], path_str(&stego)).is_err());
+        }
+
+        // Appending modes grow the file, up to the 32-bit limits
+        for mode in [PdfMode::Trailing, PdfMode::Object] {
+            let steg = PdfSteganography::with_mode(mode);
+            assert_eq!(steg.capacity(cover).unwrap(), None);
+            assert!(steg.can_embed(cover, 100_000_000).unwrap());
+            assert!(!steg.can_embed(cover, u32::MAX as usize).unwrap());
+        }
+    }
+
+    #[test]
+    fn broken_and_encrypted_pdfs_are_refused_before_anything_is_written() {
+        let dir = temp_dir("pdf-invalid");
+        let (cover, stego) = (dir.join("cover.pdf"), dir.join("stego.pdf"));
+        write_pdf(&cover, &PAGE);
+        let valid = fs::read(&cover).unwrap();
+        // Edit the raw bytes: the binary comment line is not UTF-8
+        let find = |needle: &[u8]| valid.windows(needle.len()).rposition(|window| window == needle).unwrap();
+        let splice = |at: usize, cut: usize, insert: &[u8]| [&valid[..at], insert, &valid[at + cut..]].concat();
+        let startxref = find(b"startxref\n") + "startxref\n".len();
+
+        let cases: [(&str, Vec<u8>); 4] = [
+            ("header", valid[1..].to_vec()),
+            ("cross-reference", [&valid[..startxref], b"12\n%%EOF\n"].concat()),
+            ("encrypted", splice(find(b"/Root 1 0 R"), 0, b"/Encrypt 9 0 R ")),
+            ("catalog", splice(find(b"/Catalog"), "/Catalog".len(), b"/Pages")),
+        ];
+        for (case, content) in cases {
+            fs::write(&cover, content).unwrap();
+            for mode in PdfMode::ALL {
+                let result = PdfSteganography::with_mode(mode).embed(path_str(&cover), b"data", path_str(&stego));
+                assert!(result.is_err(), "{} in {:?} mode", case, mode);
+                assert!(!stego.exists());
+            }
+        }
+    }
+
+    #[test]
+    fn updates_stack_on_an_incrementally_updated_pdf() {
+        let dir = temp_dir("pdf-incremental");
+        let (cover, updated, stego) = (dir.join("cover.pdf"), dir.join("updated.pdf"), dir.join("stego.pdf"));
+        write_pdf_with_metadata(&cover);
+        // Content mode leaves an incremental update behind
+        let content_mode = PdfSteganography::with_mode(PdfMode::ContentStream);
+        content_mode.embed(path_str(&cover), b"first", path_str(&updated)).unwrap();
+
+        let steg = PdfSteganography::new();
+        steg.embed(path_str(&updated), b"second", path_str(&stego)).unwrap();
+        assert_eq!(steg.extract(path_str(&stego)).unwrap(), b"second");
+        assert_eq!(content_mode.extract(path_str(&stego)).unwrap(), b"first");
+
+        let (updated, content) = (fs::read(&updated).unwrap(), fs::read(&stego).unwrap());
+        assert!(content.starts_with(&updated));
+        assert_eq!(content.windows(5).filter(|window| window == b"%%EOF").count(), 3);
+        PdfDocument::open(&content).unwrap().first_page().unwrap();
+    }
+}
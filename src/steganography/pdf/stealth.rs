@@ -0,0 +1,142 @@
+//! Keyed encoding that makes hidden PDF data indistinguishable from random bytes.
+//!
+//! Layout (anchored at the end of the blob so no separator is needed):
+//!
+//! ```text
+//! masked data | nonce (16) | masked length (4) | tag (16)
+//! ```
+//!
+//! The data and its little-endian length are XORed with an HMAC-SHA256
+//! keystream under the header subkey, and the tag, under the MAC subkey,
+//! authenticates everything before it, so neither the length nor the presence
+//! of a payload can be recovered without the key.
+
+use hmac::{Hmac, Mac};
+use rand::{rng, RngCore};
+use sha2::Sha256;
+use crate::crypto::MasterKey;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NONCE_LEN: usize = 16;
+const TAG_LEN: usize = 16;
+const TRAILER_LEN: usize = NONCE_LEN + 4 + TAG_LEN;
+
+/// Bytes added to the data by [`seal`]
+pub(crate) const OVERHEAD: usize = TRAILER_LEN;
+
+/// Encode `data` so that every output byte looks uniformly random
+pub(crate) fn seal(key: &MasterKey, data: &[u8]) -> Vec<u8> {
+    let mask_key = key.header_key();
+    let mac_key = key.mac_key();
+
+    let mut nonce = [0u8; NONCE_LEN];
+    rng().fill_bytes(&mut nonce);
+
+    let mut len_bytes = (data.len() as u32).to_le_bytes();
+    let mut masked = data.to_vec();
+    apply_keystream(mask_key.expose(), &nonce, &mut len_bytes, &mut masked);
+
+    masked.extend_from_slice(&nonce);
+    masked.extend_from_slice(&len_bytes);
+    let tag = tag_mac(mac_key.expose(), &nonce, &len_bytes, &masked[..data.len()]).finalize().into_bytes();
+    masked.extend_from_slice(&tag[..TAG_LEN]);
+    masked
+}
+
+/// Decode a sealed payload that ends exactly at the end of `content`.
+/// Returns the offset where the sealed bytes start and the recovered data.
+pub(crate) fn open(key: &MasterKey, content: &[u8]) -> Option<(usize, Vec<u8>)> {
+    if content.len() < TRAILER_LEN {
+        return None;
+    }
+
+    let trailer_start = content.len() - TRAILER_LEN;
+    let nonce: [u8; NONCE_LEN] = content[trailer_start..trailer_start + NONCE_LEN].try_into().ok()?;
+    let masked_len: [u8; 4] = content[trailer_start + NONCE_LEN..content.len() - TAG_LEN]
+        .try_into()
+        .ok()?;
+    let tag = &content[content.len() - TAG_LEN..];
+    let mask_key = key.header_key();
+    let mac_key = key.mac_key();
+
+    // Unmask the length alone first to learn where the data starts
+    let mut len_bytes = masked_len;
+    apply_keystream(mask_key.expose(), &nonce, &mut len_bytes, &mut []);
+    let data_len = u32::from_le_bytes(len_bytes) as usize;
+    let data_start = trailer_start.checked_sub(data_len)?;
+    let masked_data = &content[data_start..trailer_start];
+
+    tag_mac(mac_key.expose(), &nonce, &masked_len, masked_data)
+        .verify_truncated_left(tag)
+        .ok()?;
+
+    let mut data = masked_data.to_vec();
+    apply_keystream(mask_key.expose(), &nonce, &mut masked_len.clone(), &mut data);
+    Some((data_start, data))
+}
+
+/// XOR the length field and then the data with HMAC-SHA256(key, label | nonce | counter)
+fn apply_keystream(key: &[u8; 32], nonce: &[u8; NONCE_LEN], len_bytes: &mut [u8; 4], data: &mut [u8]) {
+    let mut counter = 0u64;
+    let mut block = [0u8; 32];
+    let mut used = block.len();
+
+    let mut next = || {
+        if used == block.len() {
+            let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+            mac.update(b"stegano-vault pdf stealth mask");
+            mac.update(nonce);
+            mac.update(&counter.to_le_bytes());
+            block.copy_from_slice(&mac.finalize().into_bytes());
+            counter += 1;
+            used = 0;
+        }
+        used += 1;
+        block[used - 1]
+    };
+
+    for byte in len_bytes.iter_mut().chain(data.iter_mut()) {
+        *byte ^= next();
+    }
+}
+
+fn tag_mac(key: &[u8; 32], nonce: &[u8], masked_len: &[u8], masked_data: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(b"stegano-vault pdf stealth tag");
+    mac.update(nonce);
+    mac.update(masked_len);
+    mac.update(masked_data);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_data_opens_only_under_its_key() {
+        let key = MasterKey::generate();
+        let sealed = seal(&key, b"hidden bytes");
+        assert_eq!(sealed.len(), b"hidden bytes".len() + OVERHEAD);
+        assert!(!sealed.windows(6).any(|window| window == b"hidden"));
+
+        let content = [&b"%PDF-1.4 ... %%EOF\n"[..], &sealed].concat();
+        assert_eq!(open(&key, &content), Some((content.len() - sealed.len(), b"hidden bytes".to_vec())));
+        assert_eq!(open(&MasterKey::generate(), &content), None);
+    }
+
+    #[test]
+    fn sealing_twice_shares_no_bytes_and_tampering_is_caught() {
+        let key = MasterKey::generate();
+        let (first, second) = (seal(&key, &[0u8; 64]), seal(&key, &[0u8; 64]));
+        let equal = first.iter().zip(&second).filter(|(a, b)| a == b).count();
+        assert!(equal < 8, "{} equal bytes", equal);
+
+        for idx in [0, 63, 64, 80, first.len() - 1] {
+            let mut tampered = first.clone();
+            tampered[idx] ^= 1;
+            assert_eq!(open(&key, &tampered), None, "byte {}", idx);
+        }
+    }
+}
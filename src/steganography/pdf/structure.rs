@@ -0,0 +1,819 @@
+//! Cross-reference, trailer and incremental-update handling for PDF files.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use crate::error::{Result, SteganoError};
+use super::syntax::{Dictionary, Parser, PdfObject};
+
+/// How the newest cross-reference section is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum XrefKind {
+    /// Classic `xref` table followed by a `trailer` dictionary
+    Table,
+    /// PDF 1.5 cross-reference stream
+    Stream,
+}
+
+/// Where a cross-reference section says an object lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum XrefEntry {
+    /// Deleted object
+    Free,
+    /// Stored directly at a byte offset, with its generation number
+    Offset(usize, u16),
+    /// Stored inside an object stream at the given index
+    Compressed(u32, u32),
+}
+
+/// A stream object read from the file
+pub(crate) struct PdfStream {
+    pub dict: Dictionary,
+    pub data: Vec<u8>,
+    /// File offset of the first raw data byte
+    pub data_offset: usize,
+}
+
+impl PdfStream {
+    /// Apply the stream's filters and return the decoded bytes
+    pub(crate) fn decode(&self) -> Result<Vec<u8>> {
+        let filters: Vec<&[u8]> = match self.dict.get(b"Filter") {
+            None => Vec::new(),
+            Some(PdfObject::Name(name)) => vec![name.as_slice()],
+            Some(PdfObject::Array(items)) => items.iter().filter_map(PdfObject::as_name).collect(),
+            Some(_) => return Err(invalid("stream has an invalid /Filter")),
+        };
+
+        let mut data = self.data.clone();
+        for filter in filters {
+            data = match filter {
+                b"FlateDecode" | b"Fl" => inflate(&data)?,
+                other => {
+                    return Err(SteganoError::InvalidData(format!(
+                        "Unsupported PDF stream filter /{}",
+                        String::from_utf8_lossy(other)
+                    )))
+                }
+            };
+        }
+
+        match self.dict.get(b"DecodeParms").and_then(PdfObject::as_dictionary) {
+            Some(parms) => apply_predictor(&data, parms),
+            None => Ok(data),
+        }
+    }
+}
+
+/// The newest revision of a PDF: where its cross-reference data lives and what
+/// its trailer says
+pub(crate) struct PdfStructure {
+    /// Offset recorded by the final `startxref`
+    pub startxref: usize,
+    pub kind: XrefKind,
+    pub trailer: Dictionary,
+    /// One greater than the highest object number in use
+    pub size: u32,
+}
+
+impl PdfStructure {
+    pub(crate) fn parse(content: &[u8]) -> Result<Self> {
+        let startxref = find_startxref(content)?;
+        let (kind, trailer) = read_xref_section(content, startxref)?;
+
+        let size = trailer
+            .get(b"Size")
+            .and_then(PdfObject::as_integer)
+            .filter(|&size| size > 0 && size <= u32::MAX as i64)
+            .ok_or_else(|| invalid("trailer has no valid /Size"))? as u32;
+
+        if trailer.get(b"Root").and_then(PdfObject::as_reference).is_none() {
+            return Err(invalid("trailer has no /Root reference"));
+        }
+
+        Ok(Self { startxref, kind, trailer, size })
+    }
+
+    /// Objects introduced or replaced by the newest revision, as (number, offset)
+    pub(crate) fn latest_objects(&self, content: &[u8]) -> Result<Vec<(u32, usize)>> {
+        let entries = read_xref_entries(content, self.startxref)?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|(number, entry)| match entry {
+                XrefEntry::Offset(offset, _) => Some((number, offset)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Merge every cross-reference section reachable through /Prev (and the
+    /// /XRefStm of hybrid files), letting newer sections win
+    pub(crate) fn object_index(&self, content: &[u8]) -> Result<HashMap<u32, XrefEntry>> {
+        let mut index = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![self.startxref];
+
+        while let Some(offset) = pending.pop() {
+            if !visited.insert(offset) {
+                return Err(invalid("cross-reference sections form a loop"));
+            }
+            if offset >= content.len() {
+                return Err(invalid("/Prev points past the end of the file"));
+            }
+
+            let (_, trailer) = read_xref_section(content, offset)?;
+            for (number, entry) in read_xref_entries(content, offset)? {
+                index.entry(number).or_insert(entry);
+            }
+
+            // Older sections are merged last so they never override newer ones
+            if let Some(prev) = trailer.get(b"Prev").and_then(PdfObject::as_integer) {
+                pending.insert(0, prev as usize);
+            }
+            if let Some(stm) = trailer.get(b"XRefStm").and_then(PdfObject::as_integer) {
+                pending.push(stm as usize);
+            }
+        }
+
+        Ok(index)
+    }
+}
+
+/// Random access to the objects of a parsed PDF
+pub(crate) struct PdfDocument<'a> {
+    content: &'a [u8],
+    pub structure: PdfStructure,
+    index: HashMap<u32, XrefEntry>,
+}
+
+impl<'a> PdfDocument<'a> {
+    pub(crate) fn parse(content: &'a [u8]) -> Result<Self> {
+        let mut structure = PdfStructure::parse(content)?;
+        let index = structure.object_index(content)?;
+
+        // Never reuse an object number, even if /Size undercounts
+        if let Some(highest) = index.keys().max() {
+            structure.size = structure.size.max(highest + 1);
+        }
+
+        Ok(Self { content, structure, index })
+    }
+
+    /// Parse the document and check that it can be modified safely: a valid
+    /// header, a readable cross-reference chain and trailer, a catalog, and no
+    /// encryption
+    pub(crate) fn open(content: &'a [u8]) -> Result<Self> {
+        check_header(content)?;
+        let doc = Self::parse(content)?;
+
+        if doc.structure.trailer.get(b"Encrypt").is_some() {
+            return Err(SteganoError::InvalidData(
+                "PDF is encrypted; remove its password protection before hiding data in it".to_string()
+            ));
+        }
+
+        let catalog = doc.catalog()?;
+        if catalog.get(b"Type").is_some_and(|kind| kind.as_name() != Some(&b"Catalog"[..])) {
+            return Err(invalid("/Root is not a document catalog"));
+        }
+
+        Ok(doc)
+    }
+
+    /// The raw bytes the document was parsed from
+    pub(crate) fn content(&self) -> &'a [u8] {
+        self.content
+    }
+
+    /// Object number and generation recorded for `number`, if it is stored directly
+    pub(crate) fn location(&self, number: u32) -> Option<(usize, u16)> {
+        match self.index.get(&number) {
+            Some(XrefEntry::Offset(offset, gen)) => Some((*offset, *gen)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get(&self, number: u32) -> Result<PdfObject> {
+        match self.index.get(&number) {
+            Some(XrefEntry::Offset(offset, _)) => {
+                let mut parser = Parser::new(self.content, *offset);
+                parser.parse_object_header()?;
+                parser.parse_object()
+            }
+            Some(XrefEntry::Compressed(stream_number, idx)) => {
+                self.get_compressed(*stream_number, *idx)
+            }
+            _ => Ok(PdfObject::Null),
+        }
+    }
+
+    /// Follow indirect references until a direct object is reached
+    pub(crate) fn resolve(&self, object: &PdfObject) -> Result<PdfObject> {
+        let mut current = object.clone();
+        for _ in 0..32 {
+            match current.as_reference() {
+                Some((number, _)) => current = self.get(number)?,
+                None => return Ok(current),
+            }
+        }
+        Err(invalid("reference chain is too long"))
+    }
+
+    pub(crate) fn get_dictionary(&self, object: &PdfObject) -> Result<Dictionary> {
+        match self.resolve(object)? {
+            PdfObject::Dictionary(dict) => Ok(dict),
+            _ => Err(invalid("expected a dictionary")),
+        }
+    }
+
+    pub(crate) fn get_stream(&self, number: u32) -> Result<PdfStream> {
+        let (offset, _) = self
+            .location(number)
+            .ok_or_else(|| invalid(&format!("stream object {} is missing", number)))?;
+
+        // Resolve an indirect /Length before reading the data
+        let mut parser = Parser::new(self.content, offset);
+        parser.parse_object_header()?;
+        let length = match parser.parse_object()? {
+            PdfObject::Dictionary(dict) => match dict.get(b"Length") {
+                Some(length @ PdfObject::Reference(..)) => {
+                    self.resolve(length)?.as_integer().map(|length| length as usize)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        Ok(read_stream_at(self.content, offset, length)?.1)
+    }
+
+    pub(crate) fn catalog(&self) -> Result<Dictionary> {
+        let root = self
+            .structure
+            .trailer
+            .get(b"Root")
+            .ok_or_else(|| invalid("trailer has no /Root reference"))?;
+        self.get_dictionary(root)
+    }
+
+    /// Object number, generation and dictionary of the first page
+    pub(crate) fn first_page(&self) -> Result<(u32, u16, Dictionary)> {
+        let mut node = self
+            .catalog()?
+            .get(b"Pages")
+            .cloned()
+            .ok_or_else(|| invalid("catalog has no /Pages"))?;
+
+        for _ in 0..32 {
+            let (number, _) = node
+                .as_reference()
+                .ok_or_else(|| invalid("page tree node is not an indirect object"))?;
+            let dict = self.get_dictionary(&node)?;
+            match dict.get(b"Kids") {
+                Some(kids) => {
+                    node = self
+                        .resolve(kids)?
+                        .as_array()
+                        .and_then(|kids| kids.first().cloned())
+                        .ok_or_else(|| invalid("page tree has no pages"))?;
+                }
+                None => {
+                    let gen = self.location(number).map_or(0, |(_, gen)| gen);
+                    return Ok((number, gen, dict));
+                }
+            }
+        }
+        Err(invalid("page tree is too deep"))
+    }
+
+    /// `key` of a page, taken from the nearest ancestor that sets it when the
+    /// page itself does not, as /Resources may be
+    pub(crate) fn inherited(&self, page: &Dictionary, key: &[u8]) -> Result<Option<PdfObject>> {
+        let mut node = page.clone();
+        for _ in 0..32 {
+            if let Some(value) = node.get(key) {
+                return Ok(Some(value.clone()));
+            }
+            match node.get(b"Parent") {
+                Some(parent) => node = self.get_dictionary(parent)?,
+                None => return Ok(None),
+            }
+        }
+        Err(invalid("page tree is too deep"))
+    }
+
+    /// Object numbers of every page's content streams, in page order
+    pub(crate) fn content_streams(&self) -> Result<Vec<u32>> {
+        let pages = self
+            .catalog()?
+            .get(b"Pages")
+            .cloned()
+            .ok_or_else(|| invalid("catalog has no /Pages"))?;
+
+        let mut streams = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![pages];
+
+        while let Some(node) = pending.pop() {
+            if let Some((number, _)) = node.as_reference() {
+                if !visited.insert(number) {
+                    continue;
+                }
+            }
+            let dict = self.get_dictionary(&node)?;
+
+            if let Some(kids) = dict.get(b"Kids") {
+                let kids = self.resolve(kids)?;
+                // Push in reverse so pages come off the stack in order
+                for kid in kids.as_array().unwrap_or_default().iter().rev() {
+                    pending.push(kid.clone());
+                }
+                continue;
+            }
+
+            match dict.get(b"Contents") {
+                Some(PdfObject::Reference(number, _)) => {
+                    // /Contents may be a reference to an array of streams
+                    match self.get(*number)? {
+                        PdfObject::Array(items) => {
+                            streams.extend(items.iter().filter_map(|item| item.as_reference().map(|r| r.0)))
+                        }
+                        _ => streams.push(*number),
+                    }
+                }
+                Some(PdfObject::Array(items)) => {
+                    streams.extend(items.iter().filter_map(|item| item.as_reference().map(|r| r.0)))
+                }
+                _ => {}
+            }
+        }
+
+        let mut seen = HashSet::new();
+        streams.retain(|number| seen.insert(*number));
+        Ok(streams)
+    }
+
+    fn get_compressed(&self, stream_number: u32, idx: u32) -> Result<PdfObject> {
+        let stream = self.get_stream(stream_number)?;
+        let count = stream.dict.get(b"N").and_then(PdfObject::as_integer).unwrap_or(0) as u32;
+        let first = stream.dict.get(b"First").and_then(PdfObject::as_integer).unwrap_or(0) as usize;
+        if idx >= count {
+            return Err(invalid("object stream index out of range"));
+        }
+
+        let data = stream.decode()?;
+        let mut parser = Parser::new(&data, 0);
+        let mut offset = 0;
+        for _ in 0..=idx {
+            parser.parse_unsigned()?;
+            offset = parser.parse_unsigned()? as usize;
+        }
+
+        let mut parser = Parser::new(&data, first + offset);
+        parser.parse_object()
+    }
+}
+
+/// Check for a `%PDF-x.y` header at the start of the file
+pub(crate) fn check_header(content: &[u8]) -> Result<()> {
+    let valid = content.len() >= 8
+        && content.starts_with(b"%PDF-")
+        && content[5].is_ascii_digit()
+        && content[6] == b'.'
+        && content[7].is_ascii_digit();
+    if valid {
+        Ok(())
+    } else {
+        Err(SteganoError::InvalidData("File is not a valid PDF: missing %PDF-x.y header".to_string()))
+    }
+}
+
+/// Offset just past the `%%EOF` (and its line break) that closes the newest
+/// revision, found through the final `startxref` rather than a bare byte scan,
+/// so stray `%%EOF` bytes in appended data are never mistaken for it
+pub(crate) fn revision_end(content: &[u8]) -> Option<usize> {
+    let keyword = b"startxref";
+    let mut search_end = content.len();
+
+    while let Some(pos) = content[..search_end]
+        .windows(keyword.len())
+        .rposition(|window| window == keyword)
+    {
+        let mut end = pos + keyword.len();
+        let skip_space = |mut idx: usize| {
+            while idx < content.len() && matches!(content[idx], b' ' | b'\t' | b'\r' | b'\n') {
+                idx += 1;
+            }
+            idx
+        };
+
+        end = skip_space(end);
+        let digits_start = end;
+        while end < content.len() && content[end].is_ascii_digit() {
+            end += 1;
+        }
+        end = skip_space(end);
+
+        if end > digits_start && content[end..].starts_with(b"%%EOF") {
+            end += 5;
+            if content.get(end) == Some(&b'\r') {
+                end += 1;
+            }
+            if content.get(end) == Some(&b'\n') {
+                end += 1;
+            }
+            return Some(end);
+        }
+
+        search_end = pos;
+    }
+
+    None
+}
+
+/// Find the offset given by the last `startxref` keyword in the file
+fn find_startxref(content: &[u8]) -> Result<usize> {
+    let keyword = b"startxref";
+    let pos = content
+        .windows(keyword.len())
+        .rposition(|window| window == keyword)
+        .ok_or_else(|| invalid("no startxref keyword found"))?;
+
+    let mut parser = Parser::new(content, pos + keyword.len());
+    let offset = parser.parse_unsigned()? as usize;
+    if offset >= content.len() {
+        return Err(invalid("startxref points past the end of the file"));
+    }
+    Ok(offset)
+}
+
+/// Read the cross-reference section at `offset` and return its trailer dictionary
+fn read_xref_section(content: &[u8], offset: usize) -> Result<(XrefKind, Dictionary)> {
+    let mut parser = Parser::new(content, offset);
+
+    if parser.eat_keyword(b"xref") {
+        skip_xref_table(&mut parser)?;
+        parser.expect_keyword(b"trailer")?;
+        let trailer = match parser.parse_object()? {
+            PdfObject::Dictionary(dict) => dict,
+            _ => return Err(parser.error("trailer is not a dictionary")),
+        };
+        Ok((XrefKind::Table, trailer))
+    } else {
+        let (_, stream) = read_stream_object(content, offset)
+            .map_err(|_| invalid("startxref does not point to a cross-reference section"))?;
+        if stream.dict.get(b"Type").and_then(PdfObject::as_name) != Some(b"XRef") {
+            return Err(invalid("startxref does not point to a cross-reference stream"));
+        }
+        Ok((XrefKind::Stream, stream.dict))
+    }
+}
+
+/// Read the subsections of a classic xref table, leaving the parser before `trailer`
+fn skip_xref_table(parser: &mut Parser) -> Result<Vec<(u32, XrefEntry)>> {
+    let mut entries = Vec::new();
+
+    loop {
+        parser.skip_whitespace();
+        let saved = parser.position();
+        if parser.eat_keyword(b"trailer") {
+            parser.set_position(saved);
+            return Ok(entries);
+        }
+
+        let start = parser.parse_unsigned()? as u32;
+        let count = parser.parse_unsigned()? as u32;
+        for idx in 0..count {
+            let offset = parser.parse_unsigned()? as usize;
+            let gen = parser.parse_unsigned()? as u16;
+            if parser.eat_keyword(b"n") {
+                entries.push((start + idx, XrefEntry::Offset(offset, gen)));
+            } else if parser.eat_keyword(b"f") {
+                entries.push((start + idx, XrefEntry::Free));
+            } else {
+                return Err(parser.error("invalid xref entry type"));
+            }
+        }
+    }
+}
+
+/// Read the entries of the cross-reference section (table or stream) at `offset`
+fn read_xref_entries(content: &[u8], offset: usize) -> Result<Vec<(u32, XrefEntry)>> {
+    let mut parser = Parser::new(content, offset);
+    if parser.eat_keyword(b"xref") {
+        skip_xref_table(&mut parser)
+    } else {
+        let stream = read_stream_object(content, offset)?.1;
+        read_xref_stream_entries(&stream)
+    }
+}
+
+/// Decode the entries of a cross-reference stream
+fn read_xref_stream_entries(stream: &PdfStream) -> Result<Vec<(u32, XrefEntry)>> {
+    let widths: Vec<usize> = stream
+        .dict
+        .get(b"W")
+        .and_then(PdfObject::as_array)
+        .map(|items| items.iter().filter_map(PdfObject::as_integer).map(|w| w as usize).collect())
+        .unwrap_or_default();
+    if widths.len() != 3 || widths.iter().any(|&w| w > 8) {
+        return Err(invalid("cross-reference stream has an invalid /W"));
+    }
+
+    let size = stream.dict.get(b"Size").and_then(PdfObject::as_integer).unwrap_or(0);
+    let index: Vec<i64> = match stream.dict.get(b"Index").and_then(PdfObject::as_array) {
+        Some(items) => items.iter().filter_map(PdfObject::as_integer).collect(),
+        None => vec![0, size],
+    };
+
+    let data = stream.decode()?;
+    let row_len: usize = widths.iter().sum();
+    let mut rows = data.chunks_exact(row_len.max(1));
+    let mut entries = Vec::new();
+
+    for pair in index.chunks_exact(2) {
+        for idx in 0..pair[1] {
+            let row = rows.next().ok_or_else(|| invalid("cross-reference stream is truncated"))?;
+            let (type_field, rest) = row.split_at(widths[0]);
+            let (second, third) = rest.split_at(widths[1]);
+            let number = (pair[0] + idx) as u32;
+
+            // A zero-width type field means every entry is type 1
+            let entry_type = if widths[0] == 0 { 1 } else { be_uint(type_field) };
+            let entry = match entry_type {
+                0 => XrefEntry::Free,
+                1 => XrefEntry::Offset(be_uint(second) as usize, be_uint(third) as u16),
+                2 => XrefEntry::Compressed(be_uint(second) as u32, be_uint(third) as u32),
+                // Unknown types are treated as null objects
+                _ => continue,
+            };
+            entries.push((number, entry));
+        }
+    }
+
+    Ok(entries)
+}
+
+fn be_uint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |acc, &byte| acc << 8 | byte as u64)
+}
+
+/// Read an indirect stream object at `offset`
+pub(crate) fn read_stream_object(content: &[u8], offset: usize) -> Result<((u32, u16), PdfStream)> {
+    read_stream_at(content, offset, None)
+}
+
+/// Read a stream object, using `length` when its /Length is indirect
+fn read_stream_at(content: &[u8], offset: usize, length: Option<usize>) -> Result<((u32, u16), PdfStream)> {
+    let mut parser = Parser::new(content, offset);
+    let id = parser.parse_object_header()?;
+    let dict = match parser.parse_object()? {
+        PdfObject::Dictionary(dict) => dict,
+        _ => return Err(parser.error("object is not a stream")),
+    };
+    parser.expect_keyword(b"stream")?;
+
+    // The keyword is followed by CRLF or LF before the data starts
+    let mut start = parser.position();
+    if content.get(start) == Some(&b'\r') {
+        start += 1;
+    }
+    if content.get(start) == Some(&b'\n') {
+        start += 1;
+    }
+
+    let length = dict
+        .get(b"Length")
+        .and_then(PdfObject::as_integer)
+        .and_then(|length| usize::try_from(length).ok())
+        .or(length);
+
+    let end = match length {
+        Some(length) if start + length <= content.len() => start + length,
+        // Indirect or broken lengths fall back to the endstream keyword
+        _ => {
+            let keyword = b"endstream";
+            let relative = content[start..]
+                .windows(keyword.len())
+                .position(|window| window == keyword)
+                .ok_or_else(|| invalid("stream has no endstream"))?;
+            let mut end = start + relative;
+            if end > start && content[end - 1] == b'\n' {
+                end -= 1;
+            }
+            if end > start && content[end - 1] == b'\r' {
+                end -= 1;
+            }
+            end
+        }
+    };
+
+    let data = content[start..end].to_vec();
+    Ok((id, PdfStream { dict, data, data_offset: start }))
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    ZlibDecoder::new(data)
+        .read_to_end(&mut decoded)
+        .map_err(|e| SteganoError::InvalidData(format!("Corrupted PDF stream: {}", e)))?;
+    Ok(decoded)
+}
+
+pub(crate) fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Undo a PNG predictor (/Predictor 10-15), as used by cross-reference streams
+fn apply_predictor(data: &[u8], parms: &Dictionary) -> Result<Vec<u8>> {
+    let predictor = parms.get(b"Predictor").and_then(PdfObject::as_integer).unwrap_or(1);
+    if predictor == 1 {
+        return Ok(data.to_vec());
+    }
+    if predictor < 10 {
+        return Err(SteganoError::InvalidData(format!(
+            "Unsupported PDF predictor {}",
+            predictor
+        )));
+    }
+
+    let columns = parms.get(b"Columns").and_then(PdfObject::as_integer).unwrap_or(1) as usize;
+    let colors = parms.get(b"Colors").and_then(PdfObject::as_integer).unwrap_or(1) as usize;
+    let bits = parms.get(b"BitsPerComponent").and_then(PdfObject::as_integer).unwrap_or(8) as usize;
+    let bpp = (colors * bits).div_ceil(8).max(1);
+    let row_len = (columns * colors * bits).div_ceil(8);
+
+    let mut output = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_len];
+
+    for chunk in data.chunks(row_len + 1) {
+        if chunk.len() < row_len + 1 {
+            break;
+        }
+        let filter = chunk[0];
+        let mut row = chunk[1..].to_vec();
+
+        for i in 0..row_len {
+            let left = if i >= bpp { row[i - bpp] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+            row[i] = row[i].wrapping_add(match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(invalid("invalid PNG predictor row")),
+            });
+        }
+
+        output.extend_from_slice(&row);
+        previous = row;
+    }
+
+    Ok(output)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let dist_left = (estimate - left as i16).abs();
+    let dist_up = (estimate - up as i16).abs();
+    let dist_up_left = (estimate - up_left as i16).abs();
+    if dist_left <= dist_up && dist_left <= dist_up_left {
+        left
+    } else if dist_up <= dist_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// New objects appended to a PDF as an incremental update
+pub(crate) struct IncrementalUpdate {
+    objects: Vec<(u32, u16, Vec<u8>)>,
+    next_number: u32,
+}
+
+impl IncrementalUpdate {
+    pub(crate) fn new(structure: &PdfStructure) -> Self {
+        Self {
+            objects: Vec::new(),
+            next_number: structure.size,
+        }
+    }
+
+    /// Add a stream object and return its object number
+    pub(crate) fn add_stream(&mut self, dict: Dictionary, data: &[u8]) -> u32 {
+        let number = self.next_number;
+        self.next_number += 1;
+        self.objects.push((number, 0, Self::stream_body(dict, data)));
+        number
+    }
+
+    /// Write a new revision of an existing stream object
+    pub(crate) fn replace_stream(&mut self, number: u32, gen: u16, dict: Dictionary, data: &[u8]) {
+        self.objects.push((number, gen, Self::stream_body(dict, data)));
+    }
+
+    /// Write a new revision of an existing object that is not a stream
+    pub(crate) fn replace_object(&mut self, number: u32, gen: u16, object: &PdfObject) {
+        let mut body = Vec::new();
+        object.write(&mut body);
+        self.objects.push((number, gen, body));
+    }
+
+    fn stream_body(mut dict: Dictionary, data: &[u8]) -> Vec<u8> {
+        dict.set(b"Length", PdfObject::Integer(data.len() as i64));
+
+        let mut body = Vec::new();
+        dict.write(&mut body);
+        body.extend_from_slice(b"\nstream\n");
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        body
+    }
+
+    /// Append the objects, a cross-reference section of the same kind as the
+    /// previous revision, and a trailer chained to it with /Prev
+    pub(crate) fn write(self, content: &mut Vec<u8>, structure: &PdfStructure) {
+        if !content.ends_with(b"\n") && !content.ends_with(b"\r") {
+            content.push(b'\n');
+        }
+
+        let mut offsets = Vec::new();
+        for (number, gen, body) in &self.objects {
+            offsets.push((*number, *gen, content.len()));
+            content.extend_from_slice(format!("{} {} obj\n", number, gen).as_bytes());
+            content.extend_from_slice(body);
+            content.extend_from_slice(b"\nendobj\n");
+        }
+        // Subsections and /Index pairs must ascend by object number, while a
+        // rewritten object can come after a new one with a higher number
+        offsets.sort_unstable_by_key(|(number, _, _)| *number);
+
+        let mut trailer = Dictionary::new();
+        for key in [b"Root".as_slice(), b"Info", b"ID"] {
+            if let Some(value) = structure.trailer.get(key) {
+                trailer.set(key, value.clone());
+            }
+        }
+        trailer.set(b"Prev", PdfObject::Integer(structure.startxref as i64));
+
+        let xref_offset = content.len();
+        match structure.kind {
+            XrefKind::Table => {
+                trailer.set(b"Size", PdfObject::Integer(self.next_number.max(structure.size) as i64));
+
+                content.extend_from_slice(b"xref\n");
+                for (number, gen, offset) in &offsets {
+                    content.extend_from_slice(format!("{} 1\n", number).as_bytes());
+                    content.extend_from_slice(format!("{:010} {:05} n\r\n", offset, gen).as_bytes());
+                }
+                content.extend_from_slice(b"trailer\n");
+                trailer.write(content);
+                content.push(b'\n');
+            }
+            XrefKind::Stream => {
+                // The cross-reference stream is itself the last new object, and
+                // has the highest number
+                let xref_number = self.next_number.max(structure.size);
+                offsets.push((xref_number, 0, xref_offset));
+
+                let mut rows = Vec::new();
+                let mut index = Vec::new();
+                for (number, gen, offset) in &offsets {
+                    rows.push(1u8);
+                    rows.extend_from_slice(&(*offset as u32).to_be_bytes());
+                    rows.extend_from_slice(&gen.to_be_bytes());
+                    index.push(PdfObject::Integer(*number as i64));
+                    index.push(PdfObject::Integer(1));
+                }
+
+                trailer.set(b"Type", PdfObject::Name(b"XRef".to_vec()));
+                trailer.set(b"Size", PdfObject::Integer(xref_number as i64 + 1));
+                trailer.set(b"W", PdfObject::Array(vec![
+                    PdfObject::Integer(1),
+                    PdfObject::Integer(4),
+                    PdfObject::Integer(2),
+                ]));
+                trailer.set(b"Index", PdfObject::Array(index));
+                trailer.set(b"Length", PdfObject::Integer(rows.len() as i64));
+
+                content.extend_from_slice(format!("{} 0 obj\n", xref_number).as_bytes());
+                trailer.write(content);
+                content.extend_from_slice(b"\nstream\n");
+                content.extend_from_slice(&rows);
+                content.extend_from_slice(b"\nendstream\nendobj\n");
+            }
+        }
+
+        content.extend_from_slice(format!("startxref\n{}\n%%EOF\n", xref_offset).as_bytes());
+    }
+}
+
+pub(crate) fn invalid(msg: &str) -> SteganoError {
+    SteganoError::InvalidData(format!("Invalid PDF structure: {}", msg))
+}
@@ -0,0 +1,469 @@
+//! Minimal PDF object syntax: just enough to read cross-reference data,
+//! trailers and stream dictionaries, and to write them back out.
+
+use crate::error::{Result, SteganoError};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PdfObject {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Real(f64),
+    Name(Vec<u8>),
+    String(Vec<u8>),
+    HexString(Vec<u8>),
+    Array(Vec<PdfObject>),
+    Dictionary(Dictionary),
+    Reference(u32, u16),
+}
+
+impl PdfObject {
+    pub(crate) fn as_integer(&self) -> Option<i64> {
+        match self {
+            PdfObject::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_name(&self) -> Option<&[u8]> {
+        match self {
+            PdfObject::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[PdfObject]> {
+        match self {
+            PdfObject::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_dictionary(&self) -> Option<&Dictionary> {
+        match self {
+            PdfObject::Dictionary(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_reference(&self) -> Option<(u32, u16)> {
+        match self {
+            PdfObject::Reference(num, gen) => Some((*num, *gen)),
+            _ => None,
+        }
+    }
+
+    /// Serialize the object in PDF syntax
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            PdfObject::Null => out.extend_from_slice(b"null"),
+            PdfObject::Boolean(value) => {
+                out.extend_from_slice(if *value { b"true" } else { b"false" })
+            }
+            PdfObject::Integer(value) => out.extend_from_slice(value.to_string().as_bytes()),
+            PdfObject::Real(value) => out.extend_from_slice(format!("{}", value).as_bytes()),
+            PdfObject::Name(name) => {
+                out.push(b'/');
+                for &byte in name {
+                    if is_regular(byte) && byte != b'#' && (b'!'..=b'~').contains(&byte) {
+                        out.push(byte);
+                    } else {
+                        out.extend_from_slice(format!("#{:02X}", byte).as_bytes());
+                    }
+                }
+            }
+            PdfObject::String(bytes) => {
+                out.push(b'(');
+                for &byte in bytes {
+                    match byte {
+                        b'(' | b')' | b'\\' => {
+                            out.push(b'\\');
+                            out.push(byte);
+                        }
+                        b'\r' => out.extend_from_slice(b"\\r"),
+                        b'\n' => out.extend_from_slice(b"\\n"),
+                        _ => out.push(byte),
+                    }
+                }
+                out.push(b')');
+            }
+            PdfObject::HexString(bytes) => {
+                out.push(b'<');
+                for byte in bytes {
+                    out.extend_from_slice(format!("{:02X}", byte).as_bytes());
+                }
+                out.push(b'>');
+            }
+            PdfObject::Array(items) => {
+                out.push(b'[');
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        out.push(b' ');
+                    }
+                    item.write(out);
+                }
+                out.push(b']');
+            }
+            PdfObject::Dictionary(dict) => dict.write(out),
+            PdfObject::Reference(num, gen) => {
+                out.extend_from_slice(format!("{} {} R", num, gen).as_bytes())
+            }
+        }
+    }
+}
+
+/// A PDF dictionary that keeps its keys in file order
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Dictionary {
+    entries: Vec<(Vec<u8>, PdfObject)>,
+}
+
+impl Dictionary {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn get(&self, key: &[u8]) -> Option<&PdfObject> {
+        self.entries
+            .iter()
+            .find(|(name, _)| name.as_slice() == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Insert or replace an entry
+    pub(crate) fn set(&mut self, key: &[u8], value: PdfObject) {
+        match self.entries.iter_mut().find(|(name, _)| name.as_slice() == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_vec(), value)),
+        }
+    }
+
+    pub(crate) fn remove(&mut self, key: &[u8]) {
+        self.entries.retain(|(name, _)| name.as_slice() != key);
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.entries.iter().map(|(name, _)| name.as_slice())
+    }
+
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(b"<<");
+        for (name, value) in &self.entries {
+            out.push(b' ');
+            PdfObject::Name(name.clone()).write(out);
+            out.push(b' ');
+            value.write(out);
+        }
+        out.extend_from_slice(b" >>");
+    }
+}
+
+pub(crate) fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\r' | b'\n' | b'\x0C' | b'\0')
+}
+
+pub(crate) fn is_delimiter(byte: u8) -> bool {
+    matches!(byte, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+pub(crate) fn is_regular(byte: u8) -> bool {
+    !is_whitespace(byte) && !is_delimiter(byte)
+}
+
+/// Recursive-descent parser over a PDF byte buffer
+pub(crate) struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub(crate) fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn set_position(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    /// Skip whitespace and comments
+    pub(crate) fn skip_whitespace(&mut self) {
+        while self.pos < self.data.len() {
+            let byte = self.data[self.pos];
+            if is_whitespace(byte) {
+                self.pos += 1;
+            } else if byte == b'%' {
+                while self.pos < self.data.len()
+                    && self.data[self.pos] != b'\n'
+                    && self.data[self.pos] != b'\r'
+                {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Consume `keyword` if it is the next token
+    pub(crate) fn eat_keyword(&mut self, keyword: &[u8]) -> bool {
+        self.skip_whitespace();
+        let end = self.pos + keyword.len();
+        if end <= self.data.len()
+            && &self.data[self.pos..end] == keyword
+            && self.data.get(end).is_none_or(|&byte| !is_regular(byte))
+        {
+            self.pos = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn expect_keyword(&mut self, keyword: &[u8]) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", String::from_utf8_lossy(keyword))))
+        }
+    }
+
+    /// Read an unsigned integer token
+    pub(crate) fn parse_unsigned(&mut self) -> Result<u64> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_digit() {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| self.error("expected an integer"))
+    }
+
+    /// Parse an `N G obj` header, returning the object number and generation
+    pub(crate) fn parse_object_header(&mut self) -> Result<(u32, u16)> {
+        let num = self.parse_unsigned()?;
+        let gen = self.parse_unsigned()?;
+        self.expect_keyword(b"obj")?;
+        Ok((num as u32, gen as u16))
+    }
+
+    pub(crate) fn parse_object(&mut self) -> Result<PdfObject> {
+        self.skip_whitespace();
+        let byte = *self.data.get(self.pos).ok_or_else(|| self.error("unexpected end of file"))?;
+
+        match byte {
+            b'/' => {
+                self.pos += 1;
+                Ok(PdfObject::Name(self.parse_name_body()))
+            }
+            b'(' => self.parse_literal_string(),
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                self.parse_dictionary_body().map(PdfObject::Dictionary)
+            }
+            b'<' => self.parse_hex_string(),
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.data.get(self.pos) {
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(PdfObject::Array(items));
+                        }
+                        Some(_) => items.push(self.parse_object()?),
+                        None => return Err(self.error("unterminated array")),
+                    }
+                }
+            }
+            b'+' | b'-' | b'.' | b'0'..=b'9' => self.parse_number_or_reference(),
+            _ => {
+                let start = self.pos;
+                while self.pos < self.data.len() && is_regular(self.data[self.pos]) {
+                    self.pos += 1;
+                }
+                match &self.data[start..self.pos] {
+                    b"null" => Ok(PdfObject::Null),
+                    b"true" => Ok(PdfObject::Boolean(true)),
+                    b"false" => Ok(PdfObject::Boolean(false)),
+                    _ => {
+                        self.pos = start;
+                        Err(self.error("unexpected token"))
+                    }
+                }
+            }
+        }
+    }
+
+    fn parse_name_body(&mut self) -> Vec<u8> {
+        let mut name = Vec::new();
+        while self.pos < self.data.len() && is_regular(self.data[self.pos]) {
+            let byte = self.data[self.pos];
+            if byte == b'#' && self.pos + 2 < self.data.len() {
+                let hex = std::str::from_utf8(&self.data[self.pos + 1..self.pos + 3]).ok();
+                if let Some(value) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    name.push(value);
+                    self.pos += 3;
+                    continue;
+                }
+            }
+            name.push(byte);
+            self.pos += 1;
+        }
+        name
+    }
+
+    fn parse_dictionary_body(&mut self) -> Result<Dictionary> {
+        let mut dict = Dictionary::new();
+        loop {
+            self.skip_whitespace();
+            match self.data.get(self.pos) {
+                Some(b'>') if self.data.get(self.pos + 1) == Some(&b'>') => {
+                    self.pos += 2;
+                    return Ok(dict);
+                }
+                Some(b'/') => {
+                    self.pos += 1;
+                    let key = self.parse_name_body();
+                    let value = self.parse_object()?;
+                    dict.set(&key, value);
+                }
+                Some(_) => return Err(self.error("expected a name as dictionary key")),
+                None => return Err(self.error("unterminated dictionary")),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<PdfObject> {
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        let mut depth = 1;
+
+        while let Some(&byte) = self.data.get(self.pos) {
+            self.pos += 1;
+            match byte {
+                b'(' => {
+                    depth += 1;
+                    bytes.push(byte);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(PdfObject::String(bytes));
+                    }
+                    bytes.push(byte);
+                }
+                b'\\' => {
+                    let escaped = *self.data.get(self.pos).ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'b' => bytes.push(0x08),
+                        b'f' => bytes.push(0x0C),
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.data.get(self.pos) {
+                                    Some(&digit @ b'0'..=b'7') => {
+                                        value = value * 8 + (digit - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            bytes.push(value as u8);
+                        }
+                        // Escaped end-of-line continues the string on the next line
+                        b'\r' => {
+                            if self.data.get(self.pos) == Some(&b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        other => bytes.push(other),
+                    }
+                }
+                _ => bytes.push(byte),
+            }
+        }
+
+        Err(self.error("unterminated string"))
+    }
+
+    fn parse_hex_string(&mut self) -> Result<PdfObject> {
+        self.pos += 1;
+        let mut digits = Vec::new();
+
+        while let Some(&byte) = self.data.get(self.pos) {
+            self.pos += 1;
+            match byte {
+                b'>' => {
+                    if digits.len() % 2 == 1 {
+                        digits.push(0);
+                    }
+                    let bytes = digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect();
+                    return Ok(PdfObject::HexString(bytes));
+                }
+                _ if is_whitespace(byte) => {}
+                _ => {
+                    let digit = (byte as char)
+                        .to_digit(16)
+                        .ok_or_else(|| self.error("invalid hex string"))?;
+                    digits.push(digit as u8);
+                }
+            }
+        }
+
+        Err(self.error("unterminated hex string"))
+    }
+
+    fn parse_number_or_reference(&mut self) -> Result<PdfObject> {
+        let number = self.parse_number()?;
+
+        // "N G R" is an indirect reference; anything else is a plain number
+        if let PdfObject::Integer(num) = number {
+            let saved = self.pos;
+            self.skip_whitespace();
+            if self.data.get(self.pos).is_some_and(u8::is_ascii_digit) {
+                if let Ok(gen) = self.parse_unsigned() {
+                    if num >= 0 && self.eat_keyword(b"R") {
+                        return Ok(PdfObject::Reference(num as u32, gen as u16));
+                    }
+                }
+            }
+            self.pos = saved;
+        }
+
+        Ok(number)
+    }
+
+    fn parse_number(&mut self) -> Result<PdfObject> {
+        let start = self.pos;
+        while self.pos < self.data.len()
+            && matches!(self.data[self.pos], b'+' | b'-' | b'.' | b'0'..=b'9')
+        {
+            self.pos += 1;
+        }
+
+        let text = std::str::from_utf8(&self.data[start..self.pos]).unwrap_or_default();
+        if let Ok(value) = text.parse::<i64>() {
+            Ok(PdfObject::Integer(value))
+        } else if let Ok(value) = text.parse::<f64>() {
+            Ok(PdfObject::Real(value))
+        } else {
+            Err(self.error("invalid number"))
+        }
+    }
+
+    pub(crate) fn error(&self, msg: &str) -> SteganoError {
+        SteganoError::InvalidData(format!("Malformed PDF at byte {}: {}", self.pos, msg))
+    }
+}
@@ -0,0 +1,66 @@
+//! Hides bits in the padding whitespace of an XMP metadata packet.
+//!
+//! XMP packets end with a run of whitespace before `<?xpacket end=...?>` so
+//! that editors can grow the metadata in place. Each padding byte that is not
+//! a line break carries one bit: a space for 0 and a tab for 1. Both are plain
+//! XML whitespace, so the metadata reads exactly the same afterwards.
+
+/// Positions within `packet` of the padding bytes that can carry a bit
+pub(crate) fn padding_slots(packet: &[u8]) -> Option<Vec<usize>> {
+    let marker = b"<?xpacket end=";
+    let end = packet
+        .windows(marker.len())
+        .rposition(|window| window == marker)?;
+
+    let mut start = end;
+    while start > 0 && matches!(packet[start - 1], b' ' | b'\t' | b'\r' | b'\n') {
+        start -= 1;
+    }
+
+    // Line breaks keep their place so the padding keeps its shape
+    Some(
+        (start..end)
+            .filter(|&idx| matches!(packet[idx], b' ' | b'\t'))
+            .collect(),
+    )
+}
+
+pub(crate) fn write_bits(packet: &mut [u8], slots: &[usize], bits: &[bool]) {
+    for (&idx, &bit) in slots.iter().zip(bits) {
+        packet[idx] = if bit { b'\t' } else { b' ' };
+    }
+}
+
+pub(crate) fn read_bits(packet: &[u8], slots: &[usize]) -> Vec<bool> {
+    slots.iter().map(|&idx| packet[idx] == b'\t').collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: &[u8] = b"<?xpacket begin=\"\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+        <x:xmpmeta xmlns:x=\"adobe:ns:meta/\"> </x:xmpmeta>\n      \n      \n<?xpacket end=\"w\"?>";
+
+    #[test]
+    fn padding_carries_bits_and_nothing_else_changes() {
+        let slots = padding_slots(PACKET).unwrap();
+        assert_eq!(slots.len(), 12);
+
+        let bits: Vec<bool> = (0..slots.len()).map(|idx| idx % 2 == 0).collect();
+        let mut packet = PACKET.to_vec();
+        write_bits(&mut packet, &slots, &bits);
+        assert_eq!(read_bits(&packet, &slots), bits);
+        assert_eq!(padding_slots(&packet).unwrap(), slots);
+        for (idx, (before, after)) in PACKET.iter().zip(&packet).enumerate() {
+            if !slots.contains(&idx) {
+                assert_eq!(before, after, "byte {}", idx);
+            }
+        }
+    }
+
+    #[test]
+    fn metadata_without_a_packet_trailer_has_no_slots() {
+        assert!(padding_slots(b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>   ").is_none());
+    }
+}
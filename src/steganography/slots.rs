@@ -0,0 +1,709 @@
+//! Keyed two-slot layout for LSB carriers (image and audio).
+//!
+//! Position `p` of the carrier belongs to slot `p % 2`, and a payload in a
+//! slot is:
+//!
+//! ```text
+//! nonce (16) | masked(check (8) | version (1) | flags (1) | length (4) | data)
+//! ```
+//!
+//! The bits of a payload are scattered over its slot in an order drawn from
+//! the permutation subkey of its master key, the mask is an HMAC-SHA256
+//! keystream under the header subkey and the check value comes from the MAC
+//! subkey, so it only matches under that key. Every position of a slot that
+//! is written gets a bit whatever the slot holds: sealed payload bits where
+//! a payload sits and random bits everywhere else, chosen without any key.
+//! An empty slot and an occupied one therefore change at the same rate,
+//! about half their LSBs, and neither key reveals whether the other slot
+//! holds anything. Readers only visit the positions of the two headers and
+//! of the payload they find. Carriers without a key of their own use the
+//! public carrier key, so no layout leaves a header in the clear.
+
+use hmac::{Hmac, Mac};
+use rand::{rng, Rng, RngCore};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use crate::crypto::{CryptoManager, MasterKey, SecretKey};
+use crate::error::{Result, SteganoError};
+use super::traits::PayloadWriter;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NONCE_LEN: usize = 16;
+const CHECK_LEN: usize = 8;
+/// Check value, version, flags and length
+const HEADER_LEN: usize = CHECK_LEN + 1 + 1 + 4;
+/// Nonce and header in front of every payload
+const OVERHEAD: usize = NONCE_LEN + HEADER_LEN;
+
+const SLOT_VERSION: u8 = 1;
+/// No flags are defined yet; slots with flags this version does not know
+/// are refused rather than misread
+const SLOT_FLAGS: u8 = 0;
+
+/// Check value label of the header
+const CHECK_LABEL: &[u8] = b"stegano-vault slot header";
+
+/// Bits used by the length prefix of the layout before slots
+const LENGTH_BITS: usize = 32;
+
+/// How an LSB carrier lays out its payload: the two-slot layout with the
+/// real payload under `key`, or the public carrier key if none is set, and
+/// an optional decoy under another key
+#[derive(Clone, Default)]
+pub(crate) struct SlotKeys {
+    key: Option<MasterKey>,
+    decoy: Option<(MasterKey, Vec<u8>)>,
+}
+
+impl SlotKeys {
+    pub(crate) fn set_key(&mut self, key: &MasterKey) {
+        self.key = Some(key.clone());
+    }
+
+    pub(crate) fn set_decoy(&mut self, key: &MasterKey, data: &[u8]) {
+        self.decoy = Some((key.clone(), data.to_vec()));
+    }
+
+    /// Largest payload, in bytes, that fits in a carrier with `bits` LSBs
+    pub(crate) fn capacity(&self, bits: usize) -> usize {
+        // The smaller of the two slots, and headers record the length in
+        // 32 bits
+        (slot_bits(bits, 1) / 8).saturating_sub(OVERHEAD).min(u32::MAX as usize)
+    }
+
+    /// The LSBs for a carrier with `bits` LSBs to hold the payload `write`
+    /// produces and the decoy, with random bits in both slots wherever
+    /// neither payload sits
+    pub(crate) fn writer(&self, bits: usize, write: PayloadWriter) -> Result<LsbPlane> {
+        // Payloads go to slots in random order, so slot 0 says nothing about
+        // which payload is the real one
+        self.seal_into(LsbPlane::random(bits), write, rng().random_range(0..2))
+    }
+
+    /// The LSBs that put the payload `write` produces in place of the one
+    /// `source` holds under
+    /// `old_key`. The new payload takes the old one's slot, which is refilled
+    /// with random bits around it, and the other slot is left alone, so a
+    /// decoy there survives without its key being known.
+    pub(crate) fn replacer(&self, source: &mut dyn LsbSource, old_key: &MasterKey, write: PayloadWriter) -> Result<LsbPlane> {
+        let bits = source.bits();
+        let (plane, slot) = match find(old_key, &SlotSubkeys::new(old_key), source)?.1 {
+            Found::Slot(slot) => {
+                let mut plane = LsbPlane::read(source)?;
+                plane.scramble(slot);
+                (plane, slot)
+            }
+            // The layout before slots spans both, and had no decoy
+            Found::Prefixed => (LsbPlane::random(bits), rng().random_range(0..2)),
+        };
+        self.seal_into(plane, write, slot)
+    }
+
+    /// `plane` with the payload `write` produces sealed in slot `slot` and
+    /// the decoy in the other
+    fn seal_into(&self, mut plane: LsbPlane, write: PayloadWriter, slot: usize) -> Result<LsbPlane> {
+        if slot_bits(plane.bits, 1) / 8 < OVERHEAD {
+            return Err(SteganoError::EmbedError("Carrier too small to hold a payload".to_string()));
+        }
+        let key = self.key();
+        let capacity = self.capacity(plane.bits);
+        if let Some((decoy_key, decoy_data)) = &self.decoy {
+            if *decoy_key == key {
+                return Err(SteganoError::EmbedError(
+                    "The decoy payload must be hidden under a different key than the real one".to_string()
+                ));
+            }
+            if decoy_data.len() > capacity {
+                return Err(SteganoError::EmbedError(
+                    format!("Carrier too small to embed {} bytes of decoy data", decoy_data.len())
+                ));
+            }
+            let decoy_keys = SlotSubkeys::new(decoy_key);
+            let mut sink = SlotSink::new(&decoy_keys, &mut plane, 1 - slot, capacity);
+            sink.write_all(decoy_data)?;
+            sink.finish();
+        }
+
+        let keys = SlotSubkeys::new(&key);
+        let mut sink = SlotSink::new(&keys, &mut plane, slot, capacity);
+        let written = write(&mut sink);
+        if sink.full {
+            return Err(SteganoError::EmbedError(
+                format!("Carrier too small: it holds at most {} bytes of data", capacity)
+            ));
+        }
+        written?;
+        sink.finish();
+        Ok(plane)
+    }
+
+    /// The payload under the carrier key, or failing that one embedded in
+    /// the length-prefixed layout before slots existed that the key opens
+    pub(crate) fn extract(&self, source: &mut dyn LsbSource) -> Result<Vec<u8>> {
+        let key = self.key();
+        find(&key, &SlotSubkeys::new(&key), source).map(|(data, _)| data)
+    }
+
+    fn key(&self) -> MasterKey {
+        self.key.clone().unwrap_or_else(CryptoManager::public_carrier_key)
+    }
+}
+
+/// Random access to the LSBs of a carrier, so a layout reads only the
+/// positions it needs
+pub(crate) trait LsbSource {
+    /// Number of LSB positions in the carrier
+    fn bits(&self) -> usize;
+
+    /// The LSBs at `positions`, which are distinct and in ascending order
+    fn read(&mut self, positions: &[usize]) -> Result<Vec<u8>>;
+}
+
+/// The LSBs a carrier is written with, one bit per position, packed. Both
+/// slots are filled, so a carrier writer sets every LSB from the plane.
+pub(crate) struct LsbPlane {
+    words: Vec<u64>,
+    bits: usize,
+}
+
+/// Bits of a word at even positions, which belong to slot 0
+const SLOT_0_MASK: u64 = 0x5555_5555_5555_5555;
+
+impl LsbPlane {
+    /// Random bits at every position
+    fn random(bits: usize) -> Self {
+        let mut words = vec![0u64; bits.div_ceil(64)];
+        rng().fill(&mut words[..]);
+        Self { words, bits }
+    }
+
+    /// The LSBs `source` holds now
+    fn read(source: &mut dyn LsbSource) -> Result<Self> {
+        let bits = source.bits();
+        let mut words = vec![0u64; bits.div_ceil(64)];
+        // A chunk of positions at a time, so the carrier is read in one pass
+        for start in (0..bits).step_by(1 << 16) {
+            let positions: Vec<usize> = (start..bits.min(start + (1 << 16))).collect();
+            for (position, bit) in positions.iter().zip(source.read(&positions)?) {
+                words[position / 64] |= ((bit & 1) as u64) << (position % 64);
+            }
+        }
+        Ok(Self { words, bits })
+    }
+
+    /// Random bits at every position of `slot`; the other slot keeps its bits
+    fn scramble(&mut self, slot: usize) {
+        let mask = if slot == 0 { SLOT_0_MASK } else { !SLOT_0_MASK };
+        for word in &mut self.words {
+            *word = (*word & !mask) | (rng().next_u64() & mask);
+        }
+    }
+
+    /// Number of positions
+    pub(crate) fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// The bit for the LSB at `position`
+    pub(crate) fn bit(&self, position: usize) -> u8 {
+        (self.words[position / 64] >> (position % 64)) as u8 & 1
+    }
+
+    fn set(&mut self, position: usize, bit: u8) {
+        let word = &mut self.words[position / 64];
+        *word = (*word & !(1 << (position % 64))) | ((bit & 1) as u64) << (position % 64);
+    }
+}
+
+/// The subkeys of one slot's master key
+struct SlotSubkeys {
+    permutation: SecretKey,
+    header: SecretKey,
+    mac: SecretKey,
+}
+
+impl SlotSubkeys {
+    fn new(key: &MasterKey) -> Self {
+        Self {
+            permutation: key.permutation_key(),
+            header: key.header_key(),
+            mac: key.mac_key(),
+        }
+    }
+}
+
+/// Keyed order of distinct positions in `0..len`: a Fisher-Yates shuffle
+/// drawn lazily, so only the positions a payload uses are computed
+struct Positions<'a> {
+    key: &'a SecretKey,
+    len: usize,
+    next: usize,
+    swapped: HashMap<usize, usize>,
+    block: [u8; 32],
+    used: usize,
+    counter: u64,
+}
+
+impl<'a> Positions<'a> {
+    fn new(key: &'a SecretKey, len: usize) -> Self {
+        Self { key, len, next: 0, swapped: HashMap::new(), block: [0u8; 32], used: 32, counter: 0 }
+    }
+
+    /// Uniform value in `0..bound` from HMAC-SHA256(key, label | counter)
+    fn below(&mut self, bound: usize) -> usize {
+        if self.used == self.block.len() {
+            let mut mac = HmacSha256::new_from_slice(self.key.expose()).expect("HMAC accepts any key length");
+            mac.update(b"stegano-vault slot permutation");
+            mac.update(&self.counter.to_le_bytes());
+            self.block.copy_from_slice(&mac.finalize().into_bytes());
+            self.counter += 1;
+            self.used = 0;
+        }
+        let value = u64::from_le_bytes(self.block[self.used..self.used + 8].try_into().expect("slice is 8 bytes"));
+        self.used += 8;
+        // Multiply-shift reduction; the bias is below 2^-32 for any carrier
+        ((value as u128 * bound as u128) >> 64) as usize
+    }
+}
+
+impl Iterator for Positions<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.next == self.len {
+            return None;
+        }
+        let i = self.next;
+        let j = i + self.below(self.len - i);
+        // Swap the values at i and j; only moved values are stored
+        let at_i = self.swapped.remove(&i).unwrap_or(i);
+        let at_j = if j == i { at_i } else { self.swapped.insert(j, at_i).unwrap_or(j) };
+        self.next += 1;
+        Some(at_j)
+    }
+}
+
+/// Capacity of the unkeyed layout, after its 32-bit length prefix
+fn prefixed_capacity(bits: usize) -> usize {
+    bits.saturating_sub(LENGTH_BITS) / 8
+}
+
+/// Positions belonging to slot `slot` in a carrier with `bits` LSBs
+fn slot_bits(bits: usize, slot: usize) -> usize {
+    (bits + 1 - slot) / 2
+}
+
+/// Where a payload was found
+enum Found {
+    /// In the given slot
+    Slot(usize),
+    /// In the length-prefixed layout, over the leading LSBs of the carrier
+    Prefixed,
+}
+
+/// The payload under `key`, whose subkeys are `keys`, and where it sits.
+/// Failing that, a payload in the length-prefixed layout before slots
+/// existed, but only if `key` decrypts it: random LSBs can pass for a
+/// plausible length prefix, and would otherwise come back as data.
+fn find(key: &MasterKey, keys: &SlotSubkeys, source: &mut dyn LsbSource) -> Result<(Vec<u8>, Found)> {
+    let bits = source.bits();
+    let mut positions = [
+        Positions::new(&keys.permutation, slot_bits(bits, 0)),
+        Positions::new(&keys.permutation, slot_bits(bits, 1)),
+    ];
+
+    // Both headers are read in one pass, then only the matching payload
+    let headers = read_slots(source, &mut positions, [OVERHEAD, OVERHEAD])?;
+    for (slot, header) in headers.into_iter().enumerate() {
+        let Some(header) = header else { continue };
+        let Some(data_len) = open_header(keys, &header, slot_bits(bits, slot))? else {
+            continue;
+        };
+
+        let mut counts = [0, 0];
+        counts[slot] = (HEADER_LEN + data_len).saturating_sub(header.len() - NONCE_LEN);
+        let Some(rest) = read_slots(source, &mut positions, counts)?.into_iter().nth(slot).flatten() else {
+            continue;
+        };
+
+        let (nonce, body) = header.split_at(NONCE_LEN);
+        let mut body = [body, &rest].concat();
+        apply_keystream(keys, nonce, &mut body);
+        body.truncate(HEADER_LEN + data_len);
+        return Ok((body.split_off(HEADER_LEN), Found::Slot(slot)));
+    }
+
+    let data = read_prefixed(source)
+        .ok()
+        .filter(|data| CryptoManager::is_legacy_payload(key, data))
+        .ok_or_else(|| SteganoError::ExtractError("No hidden data for this key".to_string()))?;
+    Ok((data, Found::Prefixed))
+}
+
+/// Seals a payload into one slot of a plane as it is written. Data bytes
+/// are masked and scattered as they arrive; the nonce and the header, which
+/// records the length, go to the positions reserved for them on `finish`.
+struct SlotSink<'a> {
+    keys: &'a SlotSubkeys,
+    plane: &'a mut LsbPlane,
+    slot: usize,
+    keystream: Keystream<'a>,
+    /// Positions of the nonce and header, the first of the permutation
+    reserved: Vec<usize>,
+    positions: Positions<'a>,
+    len: usize,
+    capacity: usize,
+    /// Set once a write would pass the capacity
+    full: bool,
+}
+
+impl<'a> SlotSink<'a> {
+    /// A sink for up to `capacity` bytes; the slot must hold the overhead
+    fn new(keys: &'a SlotSubkeys, plane: &'a mut LsbPlane, slot: usize, capacity: usize) -> Self {
+        let mut positions = Positions::new(&keys.permutation, slot_bits(plane.bits, slot));
+        let reserved: Vec<usize> = positions.by_ref().take(OVERHEAD * 8).collect();
+        let mut nonce = [0u8; NONCE_LEN];
+        rng().fill_bytes(&mut nonce);
+        Self {
+            keys,
+            plane,
+            slot,
+            keystream: Keystream::new(keys, nonce),
+            reserved,
+            positions,
+            len: 0,
+            capacity,
+            full: false,
+        }
+    }
+
+    /// Write the nonce and the header for the data written so far
+    fn finish(mut self) {
+        let mut header = check_value(self.keys, CHECK_LABEL).to_vec();
+        header.push(SLOT_VERSION);
+        header.push(SLOT_FLAGS);
+        header.extend_from_slice(&(self.len as u32).to_le_bytes());
+        for (offset, byte) in header.iter_mut().enumerate() {
+            *byte ^= self.keystream.byte(offset);
+        }
+        let nonce = self.keystream.nonce;
+        let bits = nonce.iter().chain(&header).flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1));
+        for (&position, bit) in self.reserved.iter().zip(bits) {
+            self.plane.set(2 * position + self.slot, bit);
+        }
+    }
+}
+
+impl Write for SlotSink<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() > self.capacity - self.len {
+            self.full = true;
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "carrier is full"));
+        }
+        for &byte in buf {
+            let masked = byte ^ self.keystream.byte(HEADER_LEN + self.len);
+            for bit_idx in 0..8 {
+                let position = self.positions.next().expect("capacity checked above");
+                self.plane.set(2 * position + self.slot, (masked >> bit_idx) & 1);
+            }
+            self.len += 1;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The length of the data after the header, if the nonce and header read
+/// from a slot match these keys. `None` if they do not, an
+/// error if they do but were written by a newer release.
+fn open_header(keys: &SlotSubkeys, header: &[u8], len: usize) -> Result<Option<usize>> {
+    let (nonce, body) = header.split_at(NONCE_LEN);
+    let mut header = body[..HEADER_LEN].to_vec();
+    apply_keystream(keys, nonce, &mut header);
+
+    if !equal(&header[..CHECK_LEN], &check_value(keys, CHECK_LABEL)) {
+        return Ok(None);
+    }
+    if header[CHECK_LEN] != SLOT_VERSION || header[CHECK_LEN + 1] & !SLOT_FLAGS != 0 {
+        return Err(SteganoError::ExtractError(format!(
+            "Unsupported hidden data version {} (flags {:#04x})",
+            header[CHECK_LEN],
+            header[CHECK_LEN + 1]
+        )));
+    }
+    let len_bytes = header[CHECK_LEN + 2..HEADER_LEN].try_into().expect("slice is 4 bytes");
+    let data_len = u32::from_le_bytes(len_bytes) as usize;
+
+    if data_len > (len / 8).saturating_sub(OVERHEAD) {
+        return Ok(None);
+    }
+    Ok(Some(data_len))
+}
+
+/// The next `counts[slot]` bytes of each slot, least significant bit first,
+/// read in one ascending pass over the carrier. `None` for a slot too short
+/// to hold them.
+fn read_slots(
+    source: &mut dyn LsbSource,
+    positions: &mut [Positions; 2],
+    counts: [usize; 2],
+) -> Result<[Option<Vec<u8>>; 2]> {
+    // (carrier position, bit index within its slot's bytes); the slot is
+    // the parity of the position
+    let mut wanted = Vec::with_capacity((counts[0] + counts[1]) * 8);
+    let mut complete = [true, true];
+    for (slot, slot_positions) in positions.iter_mut().enumerate() {
+        for index in 0..counts[slot] * 8 {
+            let Some(position) = slot_positions.next() else {
+                complete[slot] = false;
+                break;
+            };
+            wanted.push((2 * position + slot, index));
+        }
+    }
+    wanted.sort_unstable();
+
+    let carrier_positions: Vec<usize> = wanted.iter().map(|&(position, _)| position).collect();
+    let bits = source.read(&carrier_positions)?;
+
+    let mut bytes = [vec![0u8; counts[0]], vec![0u8; counts[1]]];
+    for (&(position, index), bit) in wanted.iter().zip(bits) {
+        bytes[position % 2][index / 8] |= (bit & 1) << (index % 8);
+    }
+    let [first, second] = bytes;
+    Ok([complete[0].then_some(first), complete[1].then_some(second)])
+}
+
+/// A payload in the layout before slots: a 32-bit little-endian length, then
+/// the data, in the leading LSBs of the carrier
+fn read_prefixed(source: &mut dyn LsbSource) -> Result<Vec<u8>> {
+    let capacity = prefixed_capacity(source.bits());
+    if source.bits() < LENGTH_BITS {
+        return Err(SteganoError::ExtractError("Carrier too small for a length prefix".to_string()));
+    }
+
+    let len_bytes = bits_to_bytes(&source.read(&(0..LENGTH_BITS).collect::<Vec<_>>())?);
+    let data_len = u32::from_le_bytes(len_bytes.try_into().expect("32 bits make 4 bytes")) as usize;
+    if data_len > capacity {
+        return Err(SteganoError::ExtractError(
+            "Corrupted data: claimed length exceeds carrier capacity".to_string()
+        ));
+    }
+
+    let positions: Vec<usize> = (LENGTH_BITS..LENGTH_BITS + data_len * 8).collect();
+    Ok(bits_to_bytes(&source.read(&positions)?))
+}
+
+/// Pack LSBs into bytes, least significant bit first
+fn bits_to_bytes(bits: &[u8]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|byte| byte.iter().enumerate().fold(0u8, |acc, (i, bit)| acc | (bit & 1) << i))
+        .collect()
+}
+
+/// Value that only decodes correctly from a slot sealed under these keys
+fn check_value(keys: &SlotSubkeys, label: &[u8]) -> [u8; CHECK_LEN] {
+    let mut mac = HmacSha256::new_from_slice(keys.mac.expose()).expect("HMAC accepts any key length");
+    mac.update(label);
+    let mut check = [0u8; CHECK_LEN];
+    check.copy_from_slice(&mac.finalize().into_bytes()[..CHECK_LEN]);
+    check
+}
+
+/// Compare without an early exit, so timing says nothing about the check value
+fn equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// XOR `data` with the keystream under `nonce`
+fn apply_keystream(keys: &SlotSubkeys, nonce: &[u8], data: &mut [u8]) {
+    let mut keystream = Keystream::new(keys, nonce.try_into().expect("nonce is 16 bytes"));
+    for (offset, byte) in data.iter_mut().enumerate() {
+        *byte ^= keystream.byte(offset);
+    }
+}
+
+/// HMAC-SHA256(header key, label | nonce | counter), one 32-byte block per
+/// counter value, computed as offsets reach it
+struct Keystream<'a> {
+    key: &'a SecretKey,
+    nonce: [u8; NONCE_LEN],
+    block: [u8; 32],
+    counter: Option<usize>,
+}
+
+impl<'a> Keystream<'a> {
+    fn new(keys: &'a SlotSubkeys, nonce: [u8; NONCE_LEN]) -> Self {
+        Self { key: &keys.header, nonce, block: [0u8; 32], counter: None }
+    }
+
+    /// The mask byte at `offset` of the body
+    fn byte(&mut self, offset: usize) -> u8 {
+        let counter = offset / 32;
+        if self.counter != Some(counter) {
+            let mut mac = HmacSha256::new_from_slice(self.key.expose()).expect("HMAC accepts any key length");
+            mac.update(b"stegano-vault slot mask");
+            mac.update(&self.nonce);
+            mac.update(&(counter as u64).to_le_bytes());
+            self.block.copy_from_slice(&mac.finalize().into_bytes());
+            self.counter = Some(counter);
+        }
+        self.block[offset % 32]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A carrier held as one LSB per byte
+    struct Lsbs(Vec<u8>);
+
+    impl Lsbs {
+        fn random(bits: usize) -> Self {
+            Self((0..bits).map(|_| rng().random_range(0..2)).collect())
+        }
+
+        fn apply(&mut self, plane: LsbPlane) {
+            for (position, lsb) in self.0.iter_mut().enumerate() {
+                *lsb = plane.bit(position);
+            }
+        }
+    }
+
+    impl LsbSource for Lsbs {
+        fn bits(&self) -> usize {
+            self.0.len()
+        }
+
+        fn read(&mut self, positions: &[usize]) -> Result<Vec<u8>> {
+            Ok(positions.iter().map(|&position| self.0[position]).collect())
+        }
+    }
+
+    fn slot_keys(key: &MasterKey, decoy: Option<(&MasterKey, &[u8])>) -> SlotKeys {
+        let mut keys = SlotKeys::default();
+        keys.set_key(key);
+        if let Some((decoy_key, decoy_data)) = decoy {
+            keys.set_decoy(decoy_key, decoy_data);
+        }
+        keys
+    }
+
+    /// A payload writer for `data`
+    fn bytes(data: &[u8]) -> impl FnMut(&mut dyn Write) -> Result<()> + '_ {
+        move |writer| Ok(writer.write_all(data)?)
+    }
+
+    /// Share of the LSBs of each slot that differ between two carriers
+    fn change_rates(before: &Lsbs, after: &Lsbs) -> [f64; 2] {
+        let mut changed = [0usize; 2];
+        for (position, (a, b)) in before.0.iter().zip(&after.0).enumerate() {
+            changed[position % 2] += (a != b) as usize;
+        }
+        changed.map(|count| count as f64 / slot_bits(before.0.len(), 0) as f64)
+    }
+
+    #[test]
+    fn payload_and_decoy_each_open_under_their_key() {
+        let (key, decoy_key) = (MasterKey::generate(), MasterKey::generate());
+        let mut carrier = Lsbs::random(8192);
+        let keys = slot_keys(&key, Some((&decoy_key, b"decoy")));
+        carrier.apply(keys.writer(carrier.bits(), &mut bytes(b"real payload")).unwrap());
+
+        assert_eq!(keys.extract(&mut carrier).unwrap(), b"real payload");
+        assert_eq!(slot_keys(&decoy_key, None).extract(&mut carrier).unwrap(), b"decoy");
+        assert!(slot_keys(&MasterKey::generate(), None).extract(&mut carrier).is_err());
+    }
+
+    #[test]
+    fn payloads_written_in_pieces_open_whole() {
+        let key = MasterKey::generate();
+        let keys = slot_keys(&key, None);
+        let data: Vec<u8> = (0..200).collect();
+        let mut carrier = Lsbs::random(8192);
+        let plane = keys.writer(carrier.bits(), &mut |writer| {
+            for piece in data.chunks(7) {
+                writer.write_all(piece)?;
+            }
+            Ok(())
+        });
+        carrier.apply(plane.unwrap());
+        assert_eq!(keys.extract(&mut carrier).unwrap(), data);
+    }
+
+    #[test]
+    fn empty_and_occupied_slots_change_at_the_same_rate() {
+        let key = MasterKey::generate();
+        let cover = Lsbs::random(1 << 16);
+        let mut carrier = Lsbs(cover.0.clone());
+        carrier.apply(slot_keys(&key, None).writer(carrier.bits(), &mut bytes(b"a short payload")).unwrap());
+
+        // One slot holds 248 sealed bits, the other nothing, yet both see
+        // about half their 32768 LSBs change
+        let [even, odd] = change_rates(&cover, &carrier);
+        assert!((0.47..0.53).contains(&even), "slot 0 changed {}", even);
+        assert!((0.47..0.53).contains(&odd), "slot 1 changed {}", odd);
+        assert_eq!(slot_keys(&key, None).extract(&mut carrier).unwrap(), b"a short payload");
+    }
+
+    #[test]
+    fn damaged_header_hides_the_payload() {
+        let key = MasterKey::generate();
+        let mut carrier = Lsbs::random(8192);
+        carrier.apply(slot_keys(&key, None).writer(carrier.bits(), &mut bytes(b"payload")).unwrap());
+        for lsb in &mut carrier.0 {
+            *lsb ^= 1;
+        }
+        assert!(slot_keys(&key, None).extract(&mut carrier).is_err());
+    }
+
+    #[test]
+    fn replacing_a_payload_keeps_the_decoy() {
+        let (old_key, new_key, decoy_key) = (MasterKey::generate(), MasterKey::generate(), MasterKey::generate());
+        let mut carrier = Lsbs::random(8192);
+        let keys = slot_keys(&old_key, Some((&decoy_key, b"decoy")));
+        carrier.apply(keys.writer(carrier.bits(), &mut bytes(b"old payload")).unwrap());
+
+        let replacer = slot_keys(&new_key, None).replacer(&mut carrier, &old_key, &mut bytes(b"new payload")).unwrap();
+        carrier.apply(replacer);
+
+        assert_eq!(slot_keys(&new_key, None).extract(&mut carrier).unwrap(), b"new payload");
+        assert_eq!(slot_keys(&decoy_key, None).extract(&mut carrier).unwrap(), b"decoy");
+        assert!(slot_keys(&old_key, None).extract(&mut carrier).is_err());
+    }
+
+    #[test]
+    fn replacing_a_payload_refills_only_its_slot() {
+        let (old_key, new_key) = (MasterKey::generate(), MasterKey::generate());
+        let mut carrier = Lsbs::random(1 << 16);
+        carrier.apply(slot_keys(&old_key, None).writer(carrier.bits(), &mut bytes(b"old")).unwrap());
+        let before = Lsbs(carrier.0.clone());
+
+        carrier.apply(slot_keys(&new_key, None).replacer(&mut Lsbs(before.0.clone()), &old_key, &mut bytes(b"new")).unwrap());
+        let rates = change_rates(&before, &carrier);
+        let (refilled, kept) = if rates[0] > rates[1] { (rates[0], rates[1]) } else { (rates[1], rates[0]) };
+        assert!((0.47..0.53).contains(&refilled), "refilled slot changed {}", refilled);
+        assert_eq!(kept, 0.0);
+    }
+
+    #[test]
+    fn oversized_payloads_and_shared_decoy_keys_are_refused() {
+        let key = MasterKey::generate();
+        let keys = slot_keys(&key, None);
+        assert!(keys.writer(1024, &mut bytes(&vec![0u8; keys.capacity(1024) + 1])).is_err());
+        let streamed = keys.writer(1024, &mut |writer| {
+            for _ in 0..=keys.capacity(1024) {
+                writer.write_all(&[0])?;
+            }
+            Ok(())
+        });
+        assert!(matches!(streamed, Err(SteganoError::EmbedError(_))));
+        assert!(slot_keys(&key, Some((&key, b"decoy"))).writer(8192, &mut bytes(b"payload")).is_err());
+    }
+}
@@ -1,14 +1,51 @@
+use std::io::Write;
+
+use crate::crypto::MasterKey;
 use crate::error::Result;
 
+/// Writes a payload into the writer it is given, so it can be produced
+/// while it is embedded rather than held in memory first
+pub type PayloadWriter<'a> = &'a mut dyn FnMut(&mut dyn Write) -> Result<()>;
+
 /// Trait for steganography operations
 /// Implement this trait to add new steganography methods
 pub trait Steganography {
     /// Embed data into a carrier file
     fn embed(&self, carrier_path: &str, data: &[u8], output_path: &str) -> Result<()>;
+
+    /// Embed the payload `write` produces into a carrier file. The default
+    /// collects it in memory and embeds that; methods that can place bytes
+    /// as they arrive take them straight from the writer.
+    fn embed_stream(&self, carrier_path: &str, write: PayloadWriter, output_path: &str) -> Result<()> {
+        let mut data = Vec::new();
+        write(&mut data)?;
+        self.embed(carrier_path, &data, output_path)
+    }
     
+    /// Embed data into a carrier file that already holds a payload hidden
+    /// under `old_key`, so that the old payload cannot be recovered from the
+    /// output. The default embeds afresh, which is enough for methods that
+    /// overwrite everything an earlier embedding wrote.
+    fn replace(&self, carrier_path: &str, _old_key: &MasterKey, data: &[u8], output_path: &str) -> Result<()> {
+        self.embed(carrier_path, data, output_path)
+    }
+
     /// Extract hidden data from a carrier file
     fn extract(&self, carrier_path: &str) -> Result<Vec<u8>>;
     
     /// Check if a carrier file can hold the given amount of data
     fn can_embed(&self, carrier_path: &str, data_size: usize) -> Result<bool>;
+
+    /// Largest payload, in bytes, that a carrier file can hold, or `None` if
+    /// the method grows the file to fit whatever it is given
+    fn capacity(&self, _carrier_path: &str) -> Result<Option<usize>> {
+        Ok(None)
+    }
+
+    /// SHA-256 of the parts of a carrier file that embedding leaves unchanged,
+    /// equal for a cover and any file made from it, so payloads can be bound
+    /// to their carrier. `None` if the method cannot tell those parts apart.
+    fn carrier_digest(&self, _carrier_path: &str) -> Result<Option<[u8; 32]>> {
+        Ok(None)
+    }
 }
@@ -0,0 +1,66 @@
+//! Helpers shared by unit tests: scratch directories and small carriers
+//! filled with noise, written the way real covers would be.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::{Rgb, RgbImage};
+use rand::{rng, Rng};
+
+/// An empty directory of its own for each test
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("stegano-vault-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub(crate) fn path_str(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+/// A `width` x `height` RGB PNG of random pixels
+pub(crate) fn write_png(path: &Path, width: u32, height: u32) {
+    let mut random = rng();
+    RgbImage::from_fn(width, height, |_, _| Rgb(random.random()))
+        .save(path)
+        .unwrap();
+}
+
+/// A mono 16-bit WAV of `samples` random samples
+pub(crate) fn write_wav(path: &Path, samples: usize) {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 8000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    let mut random = rng();
+    for _ in 0..samples {
+        writer.write_sample(random.random::<i16>()).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+/// A PDF with a classic cross-reference table holding `objects`, numbered
+/// from 1, with object 1 as the catalog
+pub(crate) fn write_pdf(path: &Path, objects: &[&[u8]]) {
+    let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::new();
+    for (idx, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", idx + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f\r\n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n\r\n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes(),
+    );
+    fs::write(path, pdf).unwrap();
+}